@@ -0,0 +1,76 @@
+use crate::Player;
+use egui_macroquad::macroquad::prelude::*;
+use shared::{
+   Location,
+   constants::{CAMERA_HEIGHT, CAMERA_WIDTH, TILE_HEIGHT, TILE_WIDTH},
+};
+
+/// Maps the world to the screen. It follows where the player is drawn, so
+/// the view scrolls along while they step from one tile to the next.
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+   center: Vec2, // in tiles
+}
+
+impl Camera {
+   pub fn following(player: &Player) -> Camera {
+      Camera {
+         center: player.glide.position(get_time()),
+      }
+   }
+
+   /// The screen position of the top left corner of `position`, in tiles.
+   pub fn to_screen(&self, position: Vec2) -> Vec2 {
+      (position - self.center + half_view()) * vec2(TILE_WIDTH, TILE_HEIGHT)
+   }
+
+   pub fn tile_to_screen(&self, (x, y, _): Location) -> Vec2 {
+      self.to_screen(vec2(x as f32, y as f32))
+   }
+
+   /// Whether a tile drawn at `screen` is at least partly in view.
+   pub fn shows(&self, screen: Vec2) -> bool {
+      let view = vec2(
+         CAMERA_WIDTH as f32 * TILE_WIDTH,
+         CAMERA_HEIGHT as f32 * TILE_HEIGHT,
+      );
+      screen.x > -TILE_WIDTH && screen.y > -TILE_HEIGHT && screen.x < view.x && screen.y < view.y
+   }
+
+   /// Paints over whatever was drawn past the right and bottom edges of the
+   /// view while tiles scroll into it.
+   pub fn hide_overflow(&self, background: Color) {
+      let view = vec2(
+         CAMERA_WIDTH as f32 * TILE_WIDTH,
+         CAMERA_HEIGHT as f32 * TILE_HEIGHT,
+      );
+      draw_rectangle(
+         view.x,
+         0.0,
+         screen_width() - view.x,
+         screen_height(),
+         background,
+      );
+      draw_rectangle(0.0, view.y, view.x, screen_height() - view.y, background);
+   }
+
+   /// The tile under a point of the screen. It can be off the map.
+   pub fn screen_to_tile(&self, screen: Vec2) -> (i32, i32) {
+      let position = screen / vec2(TILE_WIDTH, TILE_HEIGHT) - half_view() + self.center;
+      (position.x.floor() as i32, position.y.floor() as i32)
+   }
+
+   /// Every tile at least partly in view, as `(x, y)`. They can be off the map.
+   pub fn visible_tiles(&self) -> impl Iterator<Item = (i32, i32)> {
+      let left = self.center.x.floor() as i32 - (CAMERA_WIDTH / 2) as i32;
+      let top = self.center.y.floor() as i32 - (CAMERA_HEIGHT / 2) as i32;
+      // one more row and column for the ones scrolling into view
+      (top..=top + CAMERA_HEIGHT as i32)
+         .flat_map(move |y| (left..=left + CAMERA_WIDTH as i32).map(move |x| (x, y)))
+   }
+}
+
+/// The player is drawn this many tiles away from the top left corner.
+fn half_view() -> Vec2 {
+   vec2((CAMERA_WIDTH / 2) as f32, (CAMERA_HEIGHT / 2) as f32)
+}
@@ -0,0 +1,142 @@
+use crate::{
+   BattleEntry, MmoContext, OtherPlayers, Player, pathfinding::get_mouse_map_tile_position,
+};
+use egui_macroquad::macroquad::prelude::*;
+use shared::{
+   AttackTarget, Creatures, GameObject, GameObjects, distance, is_on_screen,
+   network::tcp::TcpClientMsg,
+};
+use thin_logger::log::info;
+
+/// Clicking a monster or another player, in the view or in the battle list,
+/// attacks it, clicking it again or pressing escape stops. So do Attack and
+/// Stop attack in the context menu, see [`set_attack_target`]. The server does
+/// the hitting as long as the target is set.
+pub fn handle_attack_target(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
+) {
+   // the target died or walked out of sight
+   let is_gone = match &player.attack_target {
+      Some(AttackTarget::Monster(uid)) => game_objects.find_monster(*uid).is_none(),
+      Some(AttackTarget::Player(name)) => !other_players.0.contains_key(name),
+      None => false,
+   };
+   if is_gone {
+      player.attack_target = None;
+   }
+
+   let picked = mmo_ctx.picked_target.take();
+   let target = if is_key_pressed(KeyCode::Escape) {
+      None
+   } else if let Some(picked) = picked {
+      (player.attack_target.as_ref() != Some(&picked)).then_some(picked)
+   } else if is_mouse_button_pressed(MouseButton::Left) && !mmo_ctx.pointer_over_ui {
+      let Some((x, y)) = get_mouse_map_tile_position(player) else {
+         return;
+      };
+      let location = (x, y, player.z_level);
+      let monster = game_objects
+         .0
+         .get(&location)
+         .and_then(|obj| obj.uid())
+         .map(AttackTarget::Monster);
+      let other_player = || {
+         other_players
+            .0
+            .values()
+            .find(|op| op.location == location)
+            .map(|op| AttackTarget::Player(op.username.clone()))
+      };
+      let Some(clicked) = monster.or_else(other_player) else {
+         return;
+      };
+      (player.attack_target.as_ref() != Some(&clicked)).then_some(clicked)
+   } else {
+      return;
+   };
+
+   set_attack_target(player, target, mmo_ctx);
+}
+
+/// Tells the server what to attack, `None` to stop.
+pub fn set_attack_target(player: &mut Player, target: Option<AttackTarget>, mmo_ctx: &MmoContext) {
+   if target != player.attack_target {
+      info!("attack target: {target:?}");
+      player.attack_target = target.clone();
+      mmo_ctx.send_tcp_msg(&TcpClientMsg::Attack(target));
+   }
+}
+
+/// The monsters and other players in view, in no particular order.
+pub fn battle_list(
+   player: &Player,
+   game_objects: &GameObjects,
+   creatures: &Creatures,
+   other_players: &OtherPlayers,
+) -> Vec<BattleEntry> {
+   let here = player.curr_location;
+
+   let monsters = (game_objects.0.iter())
+      .filter(|&(&location, _)| is_on_screen(here, location))
+      .filter_map(|(&location, object)| {
+         let GameObject::Creature { uid, hp, .. } = object else {
+            return None;
+         };
+         let creature = creatures.of(object)?;
+         let target = AttackTarget::Monster(*uid);
+         Some(BattleEntry {
+            is_target: player.attack_target.as_ref() == Some(&target),
+            target,
+            name: creature.name.clone(),
+            hp_percent: (hp * 100 / creature.hp.max(1)).min(100) as u8,
+            distance: distance(here, location),
+         })
+      });
+
+   let players = (other_players.0.values())
+      .filter(|op| is_on_screen(here, op.location))
+      .map(|op| {
+         let target = AttackTarget::Player(op.username.clone());
+         BattleEntry {
+            is_target: player.attack_target.as_ref() == Some(&target),
+            target,
+            name: op.username.clone(),
+            hp_percent: op.hp_percent,
+            distance: distance(here, op.location),
+         }
+      });
+
+   monsters.chain(players).collect()
+}
+
+/// Spells are cast by saying their words. These keys say them for you, one
+/// key for each spell the server sent, in its order.
+const SPELL_HOTKEYS: [KeyCode; 12] = [
+   KeyCode::F1,
+   KeyCode::F2,
+   KeyCode::F3,
+   KeyCode::F4,
+   KeyCode::F5,
+   KeyCode::F6,
+   KeyCode::F7,
+   KeyCode::F8,
+   KeyCode::F9,
+   KeyCode::F10,
+   KeyCode::F11,
+   KeyCode::F12,
+];
+
+/// The words only show up in the chat once the server says them back, which
+/// it does not when the spell fails.
+pub fn handle_spell_hotkeys(mmo_ctx: &mut MmoContext) {
+   let pressed = (SPELL_HOTKEYS.into_iter().zip(&mmo_ctx.spells))
+      .find(|&(key, _)| is_key_pressed(key))
+      .map(|(_, spell)| TcpClientMsg::ChatMsg(spell.words.clone()));
+   if let Some(msg) = pressed {
+      info!("casting {msg:?}");
+      mmo_ctx.send_tcp_msg(&msg);
+   }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use clap::Parser;
+use shared::{ClientConfig, load_toml};
+use std::path::PathBuf;
+
+const DEFAULT_CONFIG: &str = "client.toml";
+
+/// Overrides for the settings in the config file.
+#[derive(Debug, Parser)]
+#[command(about = "The game client")]
+struct Args {
+   /// TOML file with the settings, read over the defaults. Without one,
+   /// client.toml is read if it exists.
+   #[arg(long, env = "MMO_CLIENT_CONFIG")]
+   config: Option<PathBuf>,
+   /// The server the login screen starts with, as host:port.
+   #[arg(long, env = "MMO_CLIENT_SERVER_ADDR")]
+   server_addr: Option<String>,
+   #[arg(long, env = "MMO_CLIENT_SERVER_UDP_PORT")]
+   server_udp_port: Option<u16>,
+   /// off, error, warn, info, debug or trace.
+   #[arg(long, env = "MMO_CLIENT_LOG_LEVEL")]
+   log_level: Option<String>,
+   #[arg(long, env = "MMO_CLIENT_MAP_PATH")]
+   map_path: Option<PathBuf>,
+   #[arg(long, env = "MMO_CLIENT_CONNECTION_RETRIES")]
+   connection_retries: Option<u8>,
+   #[arg(long, env = "MMO_CLIENT_DATA_DIR")]
+   data_dir: Option<PathBuf>,
+}
+
+/// The settings from the command line, then the environment, then the
+/// config file, then the defaults.
+pub fn load() -> Result<ClientConfig> {
+   let args = Args::parse();
+   let mut config: ClientConfig = load_toml(args.config.as_deref(), DEFAULT_CONFIG)?;
+
+   if let Some(server_addr) = args.server_addr {
+      config.server_addr = server_addr;
+   }
+   if let Some(server_udp_port) = args.server_udp_port {
+      config.server_udp_port = server_udp_port;
+   }
+   if let Some(log_level) = args.log_level {
+      config.log_level = log_level;
+   }
+   if let Some(map_path) = args.map_path {
+      config.map_path = map_path;
+   }
+   if let Some(connection_retries) = args.connection_retries {
+      config.connection_retries = connection_retries;
+   }
+   if let Some(data_dir) = args.data_dir {
+      config.data_dir = data_dir;
+   }
+
+   config.check()?;
+   Ok(config)
+}
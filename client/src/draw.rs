@@ -1,11 +1,23 @@
 use crate::{
-   Cc, ChatMessage, ClientChannel, FpsLogger, GameObjects, Location, MmoContext, MmoTilesheets,
-   OtherPlayer, OtherPlayers, PingMonitor, Player, make_egui,
-   movement::{check_ladder_interaction, handle_player_movement, send_pos_to_server},
+   BattleSort, Cc, ChatMessage, ChatTab, ChatTabKind, ClientChannel, FpsLogger, GameObjects,
+   Location, MmoContext, MmoTilesheets, OtherPlayer, OtherPlayers, PingMonitor, Player,
+   camera::Camera,
+   combat::{battle_list, handle_attack_target, handle_spell_hotkeys},
+   follow::handle_follow,
+   glide::Glide,
+   interaction::{handle_chosen_action, open_context_menu},
+   interpolation::{CreatureTimelines, ServerClock},
+   login::{Connection, Session, SessionEnd},
+   make_egui,
+   minimap::Minimap,
+   movement::{handle_player_movement, reconcile, send_pos_to_server},
    object_interaction::{handle_end_move_object, handle_start_move_object},
    pathfinding::{handle_route, program_route_if_user_clicks_map},
-   rendering::{render_objects, render_view},
-   tasks::tcp_reader_task,
+   rendering::{
+      render_condition_icons, render_fields, render_objects, render_route, render_status_line,
+      render_view,
+   },
+   tasks::{tcp_reader_task, udp_recv_task},
 };
 use egui_macroquad::macroquad::prelude::*;
 
@@ -14,48 +26,72 @@ struct DamageNumber {
    damage: u32,
    spawn_time: f64,
 }
+
+#[derive(Debug, Clone)]
+struct MagicEffectAnimation {
+   effect: MagicEffect,
+   tiles: Vec<Location>,
+   spawn_time: f64,
+}
+
+#[derive(Debug, Clone)]
+struct ProjectileAnimation {
+   projectile: Projectile,
+   spawn_time: f64,
+}
+
+impl ProjectileAnimation {
+   /// Missiles take longer to fly farther.
+   fn duration(&self) -> f64 {
+      let tiles = distance(self.projectile.from, self.projectile.to).max(1);
+      tiles as f64 * PROJECTILE_SECONDS_PER_TILE
+   }
+}
 use shared::{
-   constants::{MAX_CONNECTION_RETRIES, SERVER_TCP_ADDR},
-   tcp::TcpClientMsg,
+   ChatChannel, ClientConfig, Creatures, MagicEffect, Missile, Projectile, distance, is_adjacent,
+   load_map,
+   tcp::{TcpClientMsg, encode},
    udp::UdpClientMsg,
+   zones::Fields,
 };
 use std::{
-   collections::{HashMap, hash_map::Entry},
-   net::SocketAddr,
+   collections::{HashMap, HashSet, hash_map::Entry},
    sync::{Arc, Mutex},
    time::Duration,
 };
 use thin_logger::log::{debug, info, warn};
-use tiled::Loader;
-use tokio::{
-   io::AsyncWriteExt,
-   net::{TcpSocket, TcpStream, UdpSocket},
-   sync::mpsc::{UnboundedReceiver, UnboundedSender},
-};
-
-pub async fn draw(
-   socket: Arc<UdpSocket>,
-   tcp_stream: TcpStream,
-   mut cc_rx: UnboundedReceiver<ClientChannel>,
-   cc_tx: UnboundedSender<ClientChannel>,
-   mut player: Player,
-) {
-   prevent_quit();
-
-   let (tcp_reader, tcp_writer) = tcp_stream.into_split();
+use tokio::{io::AsyncWriteExt, net::TcpSocket, sync::mpsc};
+
+/// Plays a session until the player logs out or the connection is lost for good.
+pub async fn draw(session: Session, config: &ClientConfig) -> SessionEnd {
+   let Session {
+      server,
+      socket,
+      stream: tcp_stream,
+      init_player,
+   } = session;
+   let mut player = Player::new(&init_player);
+
+   let (cc_tx, mut cc_rx) = mpsc::unbounded_channel::<ClientChannel>();
+   let udp_task = udp_recv_task(socket.clone(), cc_tx.clone(), player.id);
+
+   let Connection {
+      reader: tcp_reader,
+      writer: tcp_writer,
+   } = tcp_stream;
    let tcp_writer = Arc::new(Mutex::new(tcp_writer));
 
    let tcp_writer_ = Arc::clone(&tcp_writer);
    let socket_clone = socket.clone();
-   tokio::spawn(async move {
+   let connection_retries = config.connection_retries;
+   let connection = tokio::spawn(async move {
       let jh = tcp_reader_task(tcp_reader, cc_tx.clone(), player.id);
       _ = tokio::join!(jh);
       let mut attempts_to_reconnect = 0;
-      while attempts_to_reconnect <= MAX_CONNECTION_RETRIES {
+      while attempts_to_reconnect <= connection_retries {
          warn!("attempting re-connection to server (TCP).");
          let tcp_socket = TcpSocket::new_v4().unwrap();
-         let server_addr: SocketAddr = SERVER_TCP_ADDR.parse().unwrap();
-         let Ok(mut tcp_stream) = tcp_socket.connect(server_addr).await else {
+         let Ok(mut tcp_stream) = tcp_socket.connect(server).await else {
             attempts_to_reconnect += 1;
             tokio::time::sleep(Duration::from_secs(5)).await;
             continue;
@@ -63,7 +99,7 @@ pub async fn draw(
 
          // reconnect to the server
          let reconnect_msg = TcpClientMsg::Reconnect(player.id);
-         let reconnect_msg = bincode::serialize(&reconnect_msg).unwrap();
+         let reconnect_msg = encode(&reconnect_msg).unwrap();
          tcp_stream.write_all(&reconnect_msg).await.unwrap();
 
          // Send initial UDP ping to re-establish UDP socket on server after reconnection
@@ -76,25 +112,32 @@ pub async fn draw(
             debug!("sent initial UDP ping after reconnection");
          }
 
-         let (tcp_reader, tcp_writer_new) = tcp_stream.into_split();
+         let Connection {
+            reader: tcp_reader,
+            writer: tcp_writer_new,
+         } = Connection::new(tcp_stream);
          *tcp_writer_.lock().unwrap() = tcp_writer_new;
          let jh = tcp_reader_task(tcp_reader, cc_tx.clone(), player.id);
          _ = tokio::join!(jh);
       }
-      info!("exiting program.");
-      std::process::exit(1);
+      warn!("giving up on reconnecting to the server.");
+      _ = cc_tx.send(ClientChannel {
+         id: player.id,
+         msg: Cc::ConnectionLost,
+      });
    });
 
    let mut other_players = OtherPlayers(HashMap::new());
 
-   let map = {
-      let mut loader = Loader::new();
-      loader.load_tmx_map("assets/basic-map.tmx").unwrap()
-   };
+   let map = load_map(&config.map_path).expect("failed to load the map");
 
    let tilesheets = MmoTilesheets::new(&map);
+   let fields = Fields::from_map(&map);
+   let creatures = Creatures::load().expect("failed to load creatures");
 
-   let mut game_objects = GameObjects::new();
+   let mut game_objects = GameObjects::new(&map, &creatures);
+   let mut creature_timelines = CreatureTimelines::default();
+   let mut server_clock = ServerClock::new(init_player.tick_rate_ms);
    let mut moving_object: Option<Location> = None;
 
    let mut fps_logger = FpsLogger::new();
@@ -102,23 +145,69 @@ pub async fn draw(
 
    let mut is_disconnected = false;
    let mut damage_numbers: Vec<DamageNumber> = Vec::new();
+   let mut magic_effects: Vec<MagicEffectAnimation> = Vec::new();
+   let mut projectiles: Vec<ProjectileAnimation> = Vec::new();
 
    let mut mmo_context = MmoContext {
       username: player.username.clone(),
       user_text: "".to_string(),
-      user_chat: vec![],
+      chat_tabs: vec![
+         ChatTab::new(ChatTabKind::Local),
+         ChatTab::new(ChatTabKind::Server),
+      ],
+      active_tab: 0,
+      channel_text: "".to_string(),
       server_tcp_write_stream: tcp_writer.clone(),
       is_dead: false,
+      death_message: String::new(),
       player_id: player.id,
+      inventory: init_player.inventory,
+      trade_selection: HashSet::new(),
+      trade: None,
+      adjacent_players: vec![],
+      vip: vec![],
+      vip_text: "".to_string(),
+      party: None,
+      party_invites: vec![],
+      party_text: "".to_string(),
+      guild: None,
+      minimap: Minimap::load(&config.data_dir, &player.username),
+      player_location: player.curr_location,
+      party_locations: vec![],
+      battle_list: vec![],
+      battle_sort: BattleSort::default(),
+      picked_target: None,
+      context_menu: None,
+      chosen_action: None,
+      status: None,
+      spells: vec![],
+      pointer_over_ui: false,
+      logged_out: false,
    };
 
    loop {
-      clear_background(Color::from_rgba(31, 31, 31, 255)); // dark gray
+      clear_background(BACKGROUND);
 
       if is_disconnected {
          continue;
       }
 
+      mmo_context.adjacent_players = other_players
+         .0
+         .values()
+         .filter(|op| is_adjacent(op.location, player.curr_location))
+         .map(|op| op.username.clone())
+         .collect();
+      mmo_context.player_location = player.curr_location;
+      mmo_context.party_locations = match &mmo_context.party {
+         Some(party) => (other_players.0.values())
+            .filter(|op| party.members.contains(&op.username))
+            .map(|op| (op.username.clone(), op.location))
+            .collect(),
+         None => vec![],
+      };
+      mmo_context.battle_list = battle_list(&player, &game_objects, &creatures, &other_players);
+
       make_egui(&mut mmo_context);
 
       while let Ok(msg) = cc_rx.try_recv() {
@@ -127,25 +216,40 @@ pub async fn draw(
                client_request_id,
                location,
             } => {
-               if client_request_id >= player.request_id {
-                  player.prev_location = location;
-                  player.curr_location = location;
-               } else {
-                  player.prev_location = player.curr_location;
-               }
+               reconcile(
+                  &mut player,
+                  location,
+                  client_request_id,
+                  &game_objects,
+                  get_time(),
+               );
             }
             Cc::MoveObject { from, to } => {
                if let Some(val) = game_objects.0.remove(&from) {
                   game_objects.0.insert(to, val);
                }
             }
-            Cc::ChatMsg { from, msg } => {
+            Cc::ChatMsg { from, msg, channel } => {
                debug!("received message from: {from}. pushing it to the chat.");
-               mmo_context.user_chat.push(ChatMessage::new(from, msg));
+               let chat_msg = match channel {
+                  ChatChannel::Local(mode) => ChatMessage::spoken(from, msg, mode),
+                  _ => ChatMessage::new(from, msg),
+               };
+               mmo_context.push_chat(channel.into(), chat_msg);
+            }
+            Cc::ServerMsg(msg) => mmo_context.push_server_msg(msg),
+            Cc::ChannelJoined(channel) => {
+               mmo_context.active_tab = mmo_context.open_tab(ChatTabKind::Channel(channel));
+            }
+            Cc::ChannelLeft(channel) => {
+               mmo_context.close_tab(&ChatTabKind::Channel(channel));
             }
             Cc::Pong(ping_id) => ping_monitor.log_ping(&ping_id),
-            Cc::Objects(game_obj) => {
-               game_objects = game_obj;
+            Cc::Objects { tick, objects } => {
+               server_clock.sync(tick, get_time());
+               if creature_timelines.update(server_clock.time_of(tick), &objects) {
+                  game_objects = objects;
+               }
             }
             Cc::Disconnect => {
                is_disconnected = true;
@@ -153,19 +257,37 @@ pub async fn draw(
             Cc::ReconnectOk => {
                is_disconnected = false;
             }
-            Cc::OtherPlayer(op) => match other_players.0.entry(op.username.clone()) {
-               Entry::Occupied(mut entry) => {
-                  let player = entry.get_mut();
-                  if player.location != op.location || player.direction != op.direction {
-                     player.frame = (player.frame + 1) % 3;
-                     player.location = op.location;
-                     player.direction = op.direction;
+            Cc::ConnectionLost => {
+               mmo_context.minimap.save();
+               udp_task.abort();
+               return SessionEnd::ConnectionLost;
+            }
+            Cc::OtherPlayer { tick, player: op } => {
+               server_clock.sync(tick, get_time());
+               let time = server_clock.time_of(tick);
+               match other_players.0.entry(op.username.clone()) {
+                  Entry::Occupied(mut entry) => {
+                     let player = entry.get_mut();
+                     player.timeline.insert(time, op.location);
+                     // a late snapshot only fills in the timeline
+                     if tick < player.tick {
+                        continue;
+                     }
+                     player.tick = tick;
+                     player.guild = op.guild;
+                     player.skull = op.skull;
+                     player.hp_percent = op.hp_percent;
+                     if player.location != op.location || player.direction != op.direction {
+                        player.frame = (player.frame + 1) % 3;
+                        player.location = op.location;
+                        player.direction = op.direction;
+                     }
+                  }
+                  Entry::Vacant(entry) => {
+                     entry.insert(OtherPlayer::from_shared(&op, tick, time));
                   }
                }
-               Entry::Vacant(entry) => {
-                  entry.insert(OtherPlayer::new(op.username, op.location, op.direction));
-               }
-            },
+            }
             Cc::PlayerHealthUpdate { hp } => {
                player.hp = hp;
                debug!("Health updated: {}/{}", hp, player.max_hp);
@@ -173,6 +295,8 @@ pub async fn draw(
             Cc::PlayerDeath { message } => {
                info!("Player died: {}", message);
                mmo_context.is_dead = true;
+               mmo_context.death_message = message.clone();
+               mmo_context.push_server_msg(message);
             }
             Cc::DamageNumber { damage } => {
                damage_numbers.push(DamageNumber {
@@ -180,27 +304,146 @@ pub async fn draw(
                   spawn_time: get_time(),
                });
             }
+            Cc::MagicEffect { effect, tiles } => {
+               magic_effects.push(MagicEffectAnimation {
+                  effect,
+                  tiles,
+                  spawn_time: get_time(),
+               });
+            }
+            Cc::Projectile(projectile) => {
+               projectiles.push(ProjectileAnimation {
+                  projectile,
+                  spawn_time: get_time(),
+               });
+            }
             Cc::RespawnOk { hp, location } => {
                info!("Respawned at location {:?} with {} HP", location, hp);
                player.hp = hp;
                player.curr_location = location;
-               player.prev_location = location;
-               player.route.clear();
+               player.z_level = location.2;
+               player.glide = Glide::new(location);
+               player.stop_walking();
+               player.following = None;
+               player.pending_moves.clear();
                mmo_context.is_dead = false;
             }
+            Cc::VipList(entries) => mmo_context.vip = entries,
+            Cc::VipStatus { name, online } => {
+               if let Some(entry) = mmo_context.vip.iter_mut().find(|e| e.name == name) {
+                  entry.online = online;
+               }
+               let status = if online { "logged in" } else { "logged out" };
+               mmo_context.push_server_msg(format!("{name} has {status}."));
+            }
+            Cc::Stats(stats) => {
+               player.hp = stats.hp;
+               player.max_hp = stats.max_hp;
+               player.mana = stats.mana;
+               player.max_mana = stats.max_mana;
+               player.level = stats.level;
+               player.experience = stats.experience;
+            }
+            Cc::Party(Some(party)) => {
+               mmo_context
+                  .party_invites
+                  .retain(|leader| *leader != party.leader);
+               mmo_context.open_tab(ChatTabKind::Party);
+               mmo_context.party = Some(party);
+            }
+            Cc::Party(None) => {
+               mmo_context.party = None;
+               mmo_context.close_tab(&ChatTabKind::Party);
+            }
+            Cc::Skull(skull) => player.skull = skull,
+            Cc::Conditions(kinds) => player.conditions = kinds,
+            Cc::Status(status) => mmo_context.status = Some((status, get_time())),
+            Cc::Spells(spells) => mmo_context.spells = spells,
+            Cc::Guild(Some(guild)) => {
+               mmo_context.open_tab(ChatTabKind::Guild);
+               mmo_context.guild = Some(guild);
+            }
+            Cc::Guild(None) => {
+               mmo_context.guild = None;
+               mmo_context.close_tab(&ChatTabKind::Guild);
+            }
+            Cc::PartyInvite(leader) => {
+               if !mmo_context.party_invites.contains(&leader) {
+                  mmo_context.party_invites.push(leader);
+               }
+            }
+            Cc::Teleport(location) => {
+               info!("teleported to {:?}", location);
+               player.curr_location = location;
+               player.glide = Glide::new(location);
+               player.z_level = location.2;
+               player.stop_walking();
+               player.following = None;
+               player.pending_moves.clear();
+            }
+            Cc::Inventory(inventory) => {
+               mmo_context.inventory = inventory;
+               mmo_context.trade_selection.clear();
+            }
+            Cc::TradeUpdate(window) => {
+               mmo_context.trade = Some(window);
+            }
+            Cc::TradeClosed(reason) => {
+               mmo_context.trade = None;
+               mmo_context.push_server_msg(reason);
+            }
          }
       }
 
-      render_view(&player, &map, &tilesheets);
+      let camera = Camera::following(&player);
+      render_view(
+         &player,
+         &camera,
+         &map,
+         &tilesheets,
+         &mut mmo_context.minimap,
+      );
+      mmo_context.minimap.save_every_now_and_then(get_time());
+      render_fields(&player, &camera, &fields);
+      render_route(&player, &camera);
 
       // Only render player sprite if alive
       if !mmo_context.is_dead {
-         player.render(&tilesheets);
+         player.render(
+            &tilesheets,
+            mmo_context.shield_of(&player.username),
+            mmo_context.guild.as_deref(),
+         );
       }
 
-      other_players.render(&player, &tilesheets);
-
-      render_objects(&player, &tilesheets, &game_objects);
+      let render_time = server_clock.render_time(get_time());
+      other_players.render(&player, &camera, &tilesheets, render_time, |name| {
+         mmo_context.shield_of(name)
+      });
+
+      render_objects(
+         &player,
+         &camera,
+         &tilesheets,
+         &game_objects,
+         &creatures,
+         &creature_timelines,
+         render_time,
+      );
+      render_condition_icons(&player.conditions);
+
+      render_magic_effects(&player, &camera, &magic_effects);
+      magic_effects.retain(|me| get_time() - me.spawn_time < MAGIC_EFFECT_LIFETIME);
+
+      render_projectiles(&player, &camera, &projectiles);
+      projectiles.retain(|p| get_time() - p.spawn_time < p.duration());
+
+      camera.hide_overflow(BACKGROUND);
+      if let Some((status, since)) = &mmo_context.status
+         && get_time() - since < STATUS_LIFETIME
+      {
+         render_status_line(status);
+      }
 
       // Render damage numbers
       render_damage_numbers(&damage_numbers);
@@ -211,19 +454,30 @@ pub async fn draw(
 
       // Skip player interactions if dead
       if !mmo_context.is_dead {
-         program_route_if_user_clicks_map(&mut player, &game_objects, &other_players);
+         // before a click on the map routes the player somewhere else
+         handle_follow(&mut player, &game_objects, &other_players, &mut mmo_context);
+         if !mmo_context.pointer_over_ui {
+            program_route_if_user_clicks_map(
+               &mut player,
+               &game_objects,
+               &other_players,
+               &mut mmo_context,
+            );
+            open_context_menu(&player, &game_objects, &other_players, &mut mmo_context);
+         }
+         handle_chosen_action(&mut player, &mut mmo_context);
 
-         handle_route(&mut player, &game_objects, &other_players);
+         handle_attack_target(&mut player, &game_objects, &other_players, &mut mmo_context);
+         handle_spell_hotkeys(&mut mmo_context);
 
-         handle_player_movement(&mut player, &other_players);
+         handle_route(&mut player, &game_objects, &other_players, &mut mmo_context);
 
-         // Check for ladder interaction after movement
-         if player.curr_location != player.prev_location {
-            check_ladder_interaction(&mut player, &game_objects);
-         }
+         handle_player_movement(&mut player, &game_objects, &other_players);
 
          // Object movements
-         handle_start_move_object(&game_objects, &mut moving_object, &player);
+         if !mmo_context.pointer_over_ui {
+            handle_start_move_object(&game_objects, &mut moving_object, &player);
+         }
          handle_end_move_object(&mut game_objects, &mut moving_object, &player, &socket);
 
          // Send player state to server if changed
@@ -235,9 +489,18 @@ pub async fn draw(
 
       egui_macroquad::draw();
 
+      if mmo_context.logged_out {
+         info!("{} logged out.", player.username);
+         mmo_context.minimap.save();
+         connection.abort();
+         udp_task.abort();
+         return SessionEnd::LoggedOut;
+      }
+
       if is_quit_requested() {
-         let ser = bincode::serialize(&TcpClientMsg::Disconnect).unwrap();
+         let ser = encode(&TcpClientMsg::Disconnect).unwrap();
          _ = tcp_writer.lock().unwrap().try_write(&ser);
+         mmo_context.minimap.save();
          info!("shutting down client program.");
          std::process::exit(0);
       }
@@ -246,6 +509,45 @@ pub async fn draw(
    }
 }
 
+pub const BACKGROUND: Color = Color::from_rgba(31, 31, 31, 255); // dark gray
+const MAGIC_EFFECT_LIFETIME: f64 = 0.6;
+const STATUS_LIFETIME: f64 = 5.0;
+const PROJECTILE_SECONDS_PER_TILE: f64 = 0.05;
+
+/// Flashes the tiles a spell reached, fading out as the effect gets older.
+fn render_magic_effects(player: &Player, camera: &Camera, magic_effects: &[MagicEffectAnimation]) {
+   use shared::constants::*;
+
+   let current_time = get_time();
+
+   for magic_effect in magic_effects {
+      let elapsed = current_time - magic_effect.spawn_time;
+      let alpha = (1.0 - elapsed / MAGIC_EFFECT_LIFETIME).max(0.0) as f32;
+      let color = match magic_effect.effect {
+         MagicEffect::Healing => Color::new(0.3, 0.6, 1.0, alpha * 0.6),
+         MagicEffect::Energy => Color::new(0.7, 0.3, 1.0, alpha * 0.6),
+         MagicEffect::Fire => Color::new(1.0, 0.4, 0.0, alpha * 0.6),
+      };
+
+      for &tile in &magic_effect.tiles {
+         if tile.2 != player.z_level {
+            continue;
+         }
+         let Vec2 { x, y } = camera.tile_to_screen(tile);
+
+         // grows to fill the tile while it fades
+         let inset = alpha * 4.0;
+         draw_rectangle(
+            x + inset,
+            y + inset,
+            TILE_WIDTH - inset * 2.0,
+            TILE_HEIGHT - inset * 2.0,
+            color,
+         );
+      }
+   }
+}
+
 fn render_damage_numbers(damage_numbers: &[DamageNumber]) {
    use shared::constants::*;
 
@@ -276,3 +578,38 @@ fn render_damage_numbers(damage_numbers: &[DamageNumber]) {
       draw_text(&damage_text, x, y, 24.0, color);
    }
 }
+
+/// Draws every missile on its way from the attacker to the target.
+fn render_projectiles(player: &Player, camera: &Camera, projectiles: &[ProjectileAnimation]) {
+   use shared::constants::*;
+
+   let current_time = get_time();
+   // the screen position of the center of a tile
+   let to_screen =
+      |location: Location| camera.tile_to_screen(location) + vec2(TILE_WIDTH, TILE_HEIGHT) / 2.0;
+
+   for animation in projectiles {
+      let Projectile { missile, from, to } = animation.projectile;
+      if from.2 != player.z_level {
+         continue;
+      }
+      let progress = ((current_time - animation.spawn_time) / animation.duration()).min(1.0);
+      let (from, to) = (to_screen(from), to_screen(to));
+      let position = from.lerp(to, progress as f32);
+      let heading = (to - from).normalize_or_zero();
+
+      match missile {
+         Missile::Arrow => {
+            let tail = position - heading * 10.0;
+            draw_line(tail.x, tail.y, position.x, position.y, 2.0, BROWN);
+         }
+         Missile::Spear => {
+            let tail = position - heading * 16.0;
+            draw_line(tail.x, tail.y, position.x, position.y, 3.0, GRAY);
+         }
+         Missile::Energy => {
+            draw_circle(position.x, position.y, 5.0, Color::new(0.7, 0.3, 1.0, 0.9));
+         }
+      }
+   }
+}
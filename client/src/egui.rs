@@ -1,48 +1,305 @@
+mod battle_window;
 mod chat_window;
+mod context_menu;
+mod inventory_window;
+mod minimap_window;
+mod party_window;
+mod trade_window;
+mod vip_window;
 
+use crate::minimap::Minimap;
+use battle_window::create_battle_window;
 use chat_window::create_chat_window;
 use chrono::{DateTime, Local};
-use shared::network::tcp::TcpClientMsg;
+use context_menu::create_context_menu;
+use inventory_window::create_inventory_window;
+use minimap_window::create_minimap_window;
+use party_window::create_party_window;
+use shared::{
+   AttackTarget, ChatChannel, Inventory, Item, Location, PartyInfo, PartyShield, SpeechMode, Spell,
+   TradeWindow, VipEntry,
+   network::tcp::{TcpClientMsg, encode},
+};
 use std::{
+   collections::HashSet,
    fmt,
    sync::{Arc, Mutex},
 };
+use thin_logger::log::error;
 use tokio::net::tcp::OwnedWriteHalf;
+use trade_window::create_trade_window;
+use vip_window::create_vip_window;
 
 pub struct MmoContext {
    pub username: String,
    pub user_text: String,
-   pub user_chat: Vec<ChatMessage>,
+   pub chat_tabs: Vec<ChatTab>,
+   pub active_tab: usize,
+   /// Name typed into the "join channel" field.
+   pub channel_text: String,
    pub server_tcp_write_stream: Arc<Mutex<OwnedWriteHalf>>,
    pub is_dead: bool,
+   pub death_message: String,
    pub player_id: uuid::Uuid,
+   pub inventory: Inventory,
+   /// Inventory indices checked for the next trade offer.
+   pub trade_selection: HashSet<usize>,
+   pub trade: Option<TradeWindow>,
+   pub adjacent_players: Vec<String>,
+   pub vip: Vec<VipEntry>,
+   /// Name typed into the "add VIP" field.
+   pub vip_text: String,
+   pub party: Option<PartyInfo>,
+   /// Leaders whose party invitation has not been answered yet.
+   pub party_invites: Vec<String>,
+   /// Name typed into the "invite to party" field.
+   pub party_text: String,
+   pub guild: Option<String>,
+   pub minimap: Minimap,
+   pub player_location: Location,
+   /// Party members in sight, by name.
+   pub party_locations: Vec<(String, Location)>,
+   pub battle_list: Vec<BattleEntry>,
+   pub battle_sort: BattleSort,
+   /// An entry clicked in the battle list, to attack or stop attacking.
+   pub picked_target: Option<AttackTarget>,
+   pub context_menu: Option<ContextMenu>,
+   /// What was picked in the context menu, done at the end of the frame.
+   pub chosen_action: Option<Action>,
+   /// The status line and when it was set.
+   pub status: Option<(String, f64)>,
+   /// As the server defines them, in the order of their hotkeys.
+   pub spells: Vec<Spell>,
+   /// Whether the mouse is on a window rather than on the game view.
+   pub pointer_over_ui: bool,
+   /// Set when the player logs out. The session ends at the end of the frame.
+   pub logged_out: bool,
+}
+
+impl MmoContext {
+   pub fn send_tcp_msg(&self, msg: &TcpClientMsg) {
+      let Ok(serialized) = encode(msg) else {
+         error!("failed to serialize {msg:?}");
+         return;
+      };
+      if self
+         .server_tcp_write_stream
+         .lock()
+         .unwrap()
+         .try_write(&serialized)
+         .is_err()
+      {
+         error!("could not send {msg:?} to the server");
+      }
+   }
+
+   pub fn log_out(&mut self) {
+      self.send_tcp_msg(&TcpClientMsg::Disconnect);
+      self.logged_out = true;
+   }
+
+   pub fn push_chat(&mut self, kind: ChatTabKind, msg: ChatMessage) {
+      let idx = self.open_tab(kind);
+      self.chat_tabs[idx].messages.push(msg);
+      self.chat_tabs[idx].unread = idx != self.active_tab;
+   }
+
+   pub fn push_server_msg(&mut self, msg: String) {
+      self.push_chat(ChatTabKind::Server, ChatMessage::server(msg));
+   }
+
+   /// Returns the index of the tab, creating it if needed.
+   pub fn open_tab(&mut self, kind: ChatTabKind) -> usize {
+      match self.chat_tabs.iter().position(|tab| tab.kind == kind) {
+         Some(idx) => idx,
+         None => {
+            self.chat_tabs.push(ChatTab::new(kind));
+            self.chat_tabs.len() - 1
+         }
+      }
+   }
+
+   /// Whether `text` are the words of a spell.
+   pub fn is_spell(&self, text: &str) -> bool {
+      let text = text.trim();
+      (self.spells.iter()).any(|spell| spell.words.eq_ignore_ascii_case(text))
+   }
+
+   pub fn close_tab(&mut self, kind: &ChatTabKind) {
+      // closing tabs before the active one shifts it to the left
+      let closed_before = (self.chat_tabs.iter().take(self.active_tab))
+         .filter(|tab| tab.kind == *kind)
+         .count();
+      self.chat_tabs.retain(|tab| tab.kind != *kind);
+      self.active_tab -= closed_before;
+      if self.active_tab >= self.chat_tabs.len() {
+         self.active_tab = 0;
+      }
+   }
+
+   pub fn shield_of(&self, username: &str) -> Option<PartyShield> {
+      self
+         .party
+         .as_ref()
+         .and_then(|party| party.shield_of(username))
+         .or_else(|| {
+            self
+               .party_invites
+               .iter()
+               .any(|leader| leader == username)
+               .then_some(PartyShield::Inviter)
+         })
+   }
+
+   pub fn selected_items(&self) -> Vec<Item> {
+      let mut selection: Vec<_> = self.trade_selection.iter().copied().collect();
+      selection.sort();
+      selection
+         .into_iter()
+         .filter_map(|idx| self.inventory.0.get(idx).copied())
+         .collect()
+   }
+}
+
+/// A monster or another player in view.
+#[derive(Debug, Clone)]
+pub struct BattleEntry {
+   pub target: AttackTarget,
+   pub name: String,
+   pub hp_percent: u8,
+   pub distance: u32,
+   pub is_target: bool,
+}
+
+/// What can be done to a tile or whatever is on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+   Look(Location),
+   Use(Location),
+   Attack(AttackTarget),
+   StopAttack,
+   Follow(AttackTarget),
+   StopFollow,
+}
+
+impl fmt::Display for Action {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      match self {
+         Action::Look(_) => write!(f, "Look"),
+         Action::Use(_) => write!(f, "Use"),
+         Action::Attack(_) => write!(f, "Attack"),
+         Action::StopAttack => write!(f, "Stop attack"),
+         Action::Follow(_) => write!(f, "Follow"),
+         Action::StopFollow => write!(f, "Stop following"),
+      }
+   }
+}
+
+/// The actions for what was right clicked, shown where it was clicked.
+#[derive(Debug, Clone)]
+pub struct ContextMenu {
+   pub screen: (f32, f32), // pixels
+   pub actions: Vec<Action>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BattleSort {
+   #[default]
+   Distance,
+   Name,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatTabKind {
+   Local,
+   Server,
+   Private(String),
+   Channel(String),
+   Party,
+   Guild,
+}
+
+impl From<ChatChannel> for ChatTabKind {
+   fn from(channel: ChatChannel) -> Self {
+      match channel {
+         ChatChannel::Local(_) => ChatTabKind::Local,
+         ChatChannel::Private(username) => ChatTabKind::Private(username),
+         ChatChannel::Named(name) => ChatTabKind::Channel(name),
+         ChatChannel::Party => ChatTabKind::Party,
+         ChatChannel::Guild => ChatTabKind::Guild,
+      }
+   }
+}
+
+impl fmt::Display for ChatTabKind {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      match self {
+         ChatTabKind::Local => write!(f, "Local"),
+         ChatTabKind::Server => write!(f, "Server"),
+         ChatTabKind::Private(username) => write!(f, "{username}"),
+         ChatTabKind::Channel(name) => write!(f, "#{name}"),
+         ChatTabKind::Party => write!(f, "Party"),
+         ChatTabKind::Guild => write!(f, "Guild"),
+      }
+   }
+}
+
+pub struct ChatTab {
+   pub kind: ChatTabKind,
+   pub messages: Vec<ChatMessage>,
+   pub unread: bool,
+}
+
+impl ChatTab {
+   pub fn new(kind: ChatTabKind) -> Self {
+      Self {
+         kind,
+         messages: vec![],
+         unread: false,
+      }
+   }
 }
 
 pub struct ChatMessage {
-   username: String,
+   username: String, // empty for server messages
    message: String,
+   mode: SpeechMode,
    timestamp: DateTime<Local>,
 }
 
 impl ChatMessage {
    pub fn new(username: String, message: String) -> Self {
+      Self::spoken(username, message, SpeechMode::Say)
+   }
+
+   pub fn spoken(username: String, message: String, mode: SpeechMode) -> Self {
       Self {
          username,
          message,
+         mode,
          timestamp: Local::now(),
       }
    }
+
+   pub fn server(message: String) -> Self {
+      Self::new(String::new(), message)
+   }
 }
 
 impl fmt::Display for ChatMessage {
    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-      write!(
-         f,
-         "{} {}: {}",
-         self.timestamp.format("%I:%M%p"),
-         self.username,
-         self.message
-      )
+      let time = self.timestamp.format("%I:%M%p");
+      let (username, message) = (&self.username, &self.message);
+
+      if username.is_empty() {
+         return write!(f, "{time} {message}");
+      }
+
+      match self.mode {
+         SpeechMode::Whisper => write!(f, "{time} {username} whispers: {message}"),
+         SpeechMode::Say => write!(f, "{time} {username}: {message}"),
+         SpeechMode::Yell => write!(f, "{time} {username} yells: {message}"),
+      }
    }
 }
 
@@ -50,13 +307,33 @@ pub fn make_egui(mmo_ctx: &mut MmoContext) {
    egui_macroquad::ui(|egui_ctx| {
       egui_ctx.set_zoom_factor(2.0);
       create_chat_window(mmo_ctx, egui_ctx);
+      create_inventory_window(mmo_ctx, egui_ctx);
+      create_trade_window(mmo_ctx, egui_ctx);
+      create_vip_window(mmo_ctx, egui_ctx);
+      create_party_window(mmo_ctx, egui_ctx);
+      create_minimap_window(mmo_ctx, egui_ctx);
+      create_battle_window(mmo_ctx, egui_ctx);
+      create_context_menu(mmo_ctx, egui_ctx);
+      create_log_out_button(mmo_ctx, egui_ctx);
 
       if mmo_ctx.is_dead {
          create_death_dialog(mmo_ctx, egui_ctx);
       }
+
+      mmo_ctx.pointer_over_ui = egui_ctx.is_pointer_over_area();
    });
 }
 
+fn create_log_out_button(mmo_ctx: &mut MmoContext, ctx: &egui_macroquad::egui::Context) {
+   egui_macroquad::egui::Area::new(egui_macroquad::egui::Id::new("log out"))
+      .anchor(egui_macroquad::egui::Align2::RIGHT_TOP, [-4.0, 4.0])
+      .show(ctx, |ui| {
+         if ui.button("Log out").clicked() {
+            mmo_ctx.log_out();
+         }
+      });
+}
+
 fn create_death_dialog(mmo_ctx: &mut MmoContext, ctx: &egui_macroquad::egui::Context) {
    egui_macroquad::egui::Window::new("You Died")
       .collapsible(false)
@@ -64,34 +341,18 @@ fn create_death_dialog(mmo_ctx: &mut MmoContext, ctx: &egui_macroquad::egui::Con
       .anchor(egui_macroquad::egui::Align2::CENTER_CENTER, [0.0, 0.0])
       .show(ctx, |ui| {
          ui.vertical_centered(|ui| {
-            ui.label("You have been defeated!");
+            ui.label(&mmo_ctx.death_message);
             ui.add_space(10.0);
 
             ui.horizontal(|ui| {
                if ui.button("Respawn").clicked() {
                   // Send respawn request
-                  let respawn_msg = TcpClientMsg::Respawn(mmo_ctx.player_id);
-                  if let Ok(serialized) = bincode::serialize(&respawn_msg) {
-                     _ = mmo_ctx
-                        .server_tcp_write_stream
-                        .lock()
-                        .unwrap()
-                        .try_write(&serialized);
-                  }
+                  mmo_ctx.send_tcp_msg(&TcpClientMsg::Respawn(mmo_ctx.player_id));
                   mmo_ctx.is_dead = false;
                }
 
-               if ui.button("Exit").clicked() {
-                  // Send disconnect and exit
-                  let disconnect_msg = TcpClientMsg::Disconnect;
-                  if let Ok(serialized) = bincode::serialize(&disconnect_msg) {
-                     _ = mmo_ctx
-                        .server_tcp_write_stream
-                        .lock()
-                        .unwrap()
-                        .try_write(&serialized);
-                  }
-                  std::process::exit(0);
+               if ui.button("Log out").clicked() {
+                  mmo_ctx.log_out();
                }
             });
          });
@@ -0,0 +1,60 @@
+use super::{BattleSort, MmoContext};
+use egui_macroquad::{
+   egui::{self, Color32, Pos2, Stroke},
+   macroquad::prelude::*,
+};
+use shared::AttackTarget;
+
+pub fn create_battle_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   egui::Window::new("Battle")
+      .default_pos(Pos2::new(screen_width(), screen_height() * 2. / 3.))
+      .resizable(false)
+      .show(egui_ctx, |ui| {
+         ui.horizontal(|ui| {
+            ui.label("Sort by");
+            ui.selectable_value(&mut mmo_ctx.battle_sort, BattleSort::Distance, "distance");
+            ui.selectable_value(&mut mmo_ctx.battle_sort, BattleSort::Name, "name");
+         });
+         ui.separator();
+
+         if mmo_ctx.battle_list.is_empty() {
+            ui.label("Nothing in sight.");
+         }
+
+         let mut entries = mmo_ctx.battle_list.clone();
+         match mmo_ctx.battle_sort {
+            BattleSort::Distance => {
+               entries.sort_by(|a, b| (a.distance, &a.name).cmp(&(b.distance, &b.name)))
+            }
+            BattleSort::Name => entries.sort_by(|a, b| a.name.cmp(&b.name)),
+         }
+
+         for entry in entries {
+            ui.horizontal(|ui| {
+               let color = match entry.target {
+                  AttackTarget::Monster(_) => Color32::LIGHT_GRAY,
+                  AttackTarget::Player(_) => Color32::WHITE,
+               };
+               let name = ui
+                  .selectable_label(
+                     entry.is_target,
+                     egui::RichText::new(&entry.name).color(color),
+                  )
+                  .on_hover_text("Attack");
+               if entry.is_target {
+                  ui.painter()
+                     .rect_stroke(name.rect, 0.0, Stroke::new(1.5, Color32::RED));
+               }
+               if name.clicked() {
+                  mmo_ctx.picked_target = Some(entry.target.clone());
+               }
+
+               ui.add(
+                  egui::ProgressBar::new(entry.hp_percent as f32 / 100.0)
+                     .desired_width(60.)
+                     .text(format!("{}%", entry.hp_percent)),
+               );
+            });
+         }
+      });
+}
@@ -1,48 +1,160 @@
-use super::{ChatMessage, MmoContext};
+use super::{ChatMessage, ChatTabKind, MmoContext};
 use egui_macroquad::{
    egui::{self, Key, Modifiers, Pos2},
    macroquad::prelude::*,
 };
-use shared::network::tcp::TcpClientMsg;
+use shared::{
+   Command, PartyAction, Speech, network::tcp::TcpClientMsg, parse_command, parse_speech,
+};
 
 pub fn create_chat_window(mmo_context: &mut MmoContext, egui_ctx: &egui::Context) {
-   let chat = &mut mmo_context.user_chat;
-   let text = &mut mmo_context.user_text;
-   let tcp_writer = &mmo_context.server_tcp_write_stream;
    egui::Window::new("Chat Box")
       .default_pos(Pos2::new((screen_width()) / 2., screen_height()))
       .resizable([true, true])
       .show(egui_ctx, |ui| {
-         ui.horizontal(|ui| ui.label("Messages"));
+         // tabs
+         ui.horizontal_wrapped(|ui| {
+            for (idx, tab) in mmo_context.chat_tabs.iter().enumerate() {
+               let title = if tab.unread {
+                  format!("{}*", tab.kind)
+               } else {
+                  tab.kind.to_string()
+               };
+               if ui
+                  .selectable_label(mmo_context.active_tab == idx, title)
+                  .clicked()
+               {
+                  mmo_context.active_tab = idx;
+               }
+            }
+         });
+         mmo_context.chat_tabs[mmo_context.active_tab].unread = false;
+         let active_kind = mmo_context.chat_tabs[mmo_context.active_tab].kind.clone();
+
+         // channel management
+         ui.horizontal(|ui| {
+            egui::text_edit::TextEdit::singleline(&mut mmo_context.channel_text)
+               .hint_text("channel")
+               .desired_width(100.)
+               .show(ui);
+
+            let channel = mmo_context.channel_text.trim().to_string();
+            if ui.button("Join").clicked() && !channel.is_empty() {
+               mmo_context.send_tcp_msg(&TcpClientMsg::JoinChannel(channel));
+               mmo_context.channel_text.clear();
+            }
+
+            match &active_kind {
+               ChatTabKind::Channel(name) if ui.button("Leave").clicked() => {
+                  mmo_context.send_tcp_msg(&TcpClientMsg::LeaveChannel(name.clone()));
+               }
+               ChatTabKind::Party if ui.button("Leave").clicked() => {
+                  mmo_context
+                     .send_tcp_msg(&TcpClientMsg::Command(Command::Party(PartyAction::Leave)));
+               }
+               ChatTabKind::Private(_) if ui.button("Close").clicked() => {
+                  mmo_context.close_tab(&active_kind);
+               }
+               _ => {}
+            }
+         });
          ui.add_space(4.);
 
+         let chat = &mmo_context.chat_tabs[mmo_context.active_tab].messages;
          let row_height = ui.text_style_height(&egui::TextStyle::Body);
          egui::ScrollArea::vertical()
             .max_height(200.)
             .stick_to_bottom(true)
             .show_rows(ui, row_height, chat.len(), |ui, row_range| {
-               for (_, msg) in row_range.zip(chat.iter()) {
+               for msg in &chat[row_range] {
                   ui.label(msg.to_string());
                }
             });
 
          // text input
-         let text_edit_output = egui::text_edit::TextEdit::singleline(text)
+         let text_edit_output = egui::text_edit::TextEdit::singleline(&mut mmo_context.user_text)
             .hint_text("type text here")
             .show(ui);
 
-         if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter)) && !text.is_empty() {
-            let msg = TcpClientMsg::ChatMsg(text.clone());
-            let serialized = bincode::serialize(&msg).unwrap();
-            if let Ok(size) = tcp_writer.lock().unwrap().try_write(&serialized) {
-               info!("sent {} bytes", size);
-               info!("sent chat message: {}", text);
-            } else {
-               error!("could not send chat message: {}", text);
-            }
-            chat.push(ChatMessage::new(mmo_context.username.clone(), text.clone()));
-            text.clear();
+         if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter))
+            && !mmo_context.user_text.is_empty()
+         {
+            let text = std::mem::take(&mut mmo_context.user_text);
+            send_chat_text(mmo_context, active_kind, text);
             text_edit_output.response.request_focus();
          }
       });
 }
+
+/// Sends what was typed to wherever the active tab points and echoes it
+/// locally, since the server does not send our own messages back. Spell
+/// words are the exception.
+fn send_chat_text(mmo_context: &mut MmoContext, tab: ChatTabKind, text: String) {
+   let username = mmo_context.username.clone();
+
+   // commands are checked here so typos never reach the server
+   if let Some(command) = parse_command(&text) {
+      match command {
+         Ok(Command::PrivateMsg { to, msg }) => {
+            let echo = ChatMessage::new(username, msg.clone());
+            mmo_context.send_tcp_msg(&TcpClientMsg::Command(Command::PrivateMsg {
+               to: to.clone(),
+               msg,
+            }));
+            mmo_context.push_chat(ChatTabKind::Private(to), echo);
+         }
+         Ok(command) => mmo_context.send_tcp_msg(&TcpClientMsg::Command(command)),
+         Err(e) => mmo_context.push_server_msg(e.to_string()),
+      }
+      return;
+   }
+
+   // only the server knows whether the spell worked, and says the words back if so
+   let is_local = matches!(tab, ChatTabKind::Local | ChatTabKind::Server);
+   if is_local && mmo_context.is_spell(&text) {
+      mmo_context.send_tcp_msg(&TcpClientMsg::ChatMsg(text));
+      return;
+   }
+
+   let (msg, echo_tab, echo) = match tab {
+      ChatTabKind::Local | ChatTabKind::Server => match parse_speech(&text) {
+         Speech::Private { to, msg } => (
+            TcpClientMsg::ChatMsg(text),
+            ChatTabKind::Private(to),
+            ChatMessage::new(username, msg),
+         ),
+         Speech::Local { mode, msg } => (
+            TcpClientMsg::ChatMsg(text),
+            ChatTabKind::Local,
+            ChatMessage::spoken(username, msg, mode),
+         ),
+      },
+      ChatTabKind::Private(to) => (
+         TcpClientMsg::ChatMsg(format!("*{to}* {text}")),
+         ChatTabKind::Private(to),
+         ChatMessage::new(username, text),
+      ),
+      ChatTabKind::Party => (
+         TcpClientMsg::PartyMsg(text.clone()),
+         ChatTabKind::Party,
+         ChatMessage::new(username, text),
+      ),
+      ChatTabKind::Guild => (
+         TcpClientMsg::GuildMsg(text.clone()),
+         ChatTabKind::Guild,
+         ChatMessage::new(username, text),
+      ),
+      ChatTabKind::Channel(channel) => (
+         TcpClientMsg::ChannelMsg {
+            channel: channel.clone(),
+            msg: text.clone(),
+         },
+         ChatTabKind::Channel(channel),
+         ChatMessage::new(username, text),
+      ),
+   };
+
+   mmo_context.send_tcp_msg(&msg);
+   info!("sent chat message: {:?}", msg);
+   mmo_context.push_chat(echo_tab, echo);
+}
@@ -0,0 +1,29 @@
+use super::MmoContext;
+use egui_macroquad::egui::{self, Key, Order, Pos2};
+
+pub fn create_context_menu(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   let Some(menu) = &mmo_ctx.context_menu else {
+      return;
+   };
+   let zoom = egui_ctx.zoom_factor();
+   let (x, y) = menu.screen;
+
+   let mut chosen = None;
+   egui::Area::new(egui::Id::new("context menu"))
+      .fixed_pos(Pos2::new(x / zoom, y / zoom))
+      .order(Order::Foreground)
+      .show(egui_ctx, |ui| {
+         egui::Frame::popup(ui.style()).show(ui, |ui| {
+            for action in &menu.actions {
+               if ui.button(action.to_string()).clicked() {
+                  chosen = Some(action.clone());
+               }
+            }
+         });
+      });
+
+   if chosen.is_some() || egui_ctx.input(|i| i.key_pressed(Key::Escape)) {
+      mmo_ctx.context_menu = None;
+   }
+   mmo_ctx.chosen_action = chosen;
+}
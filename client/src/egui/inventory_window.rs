@@ -0,0 +1,58 @@
+use super::MmoContext;
+use egui_macroquad::{
+   egui::{self, Pos2},
+   macroquad::prelude::*,
+};
+use shared::network::tcp::TcpClientMsg;
+
+pub fn create_inventory_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   egui::Window::new("Inventory")
+      .default_pos(Pos2::new(screen_width(), 0.))
+      .resizable(false)
+      .show(egui_ctx, |ui| {
+         if mmo_ctx.inventory.0.is_empty() {
+            ui.label("Empty.");
+         }
+
+         // checked items are the ones that go into the next trade offer
+         let mut used = None;
+         for (idx, item) in mmo_ctx.inventory.0.iter().enumerate() {
+            ui.horizontal(|ui| {
+               let mut selected = mmo_ctx.trade_selection.contains(&idx);
+               if ui.checkbox(&mut selected, item.to_string()).changed() {
+                  if selected {
+                     mmo_ctx.trade_selection.insert(idx);
+                  } else {
+                     mmo_ctx.trade_selection.remove(&idx);
+                  }
+               }
+               if item.kind.food_duration().is_some() && ui.small_button("Eat").clicked() {
+                  used = Some(item.kind);
+               }
+               if item.kind.weapon().is_some() && ui.small_button("Wield").clicked() {
+                  used = Some(item.kind);
+               }
+            });
+         }
+         if let Some(kind) = used {
+            mmo_ctx.send_tcp_msg(&TcpClientMsg::UseItem(kind));
+         }
+
+         // answering a trade request happens in the trade window
+         if mmo_ctx.trade.is_some() || mmo_ctx.adjacent_players.is_empty() {
+            return;
+         }
+
+         ui.separator();
+         ui.label("Offer selected items to:");
+         for username in mmo_ctx.adjacent_players.clone() {
+            if ui.button(&username).clicked() {
+               let items = mmo_ctx.selected_items();
+               mmo_ctx.send_tcp_msg(&TcpClientMsg::TradeOffer {
+                  to: username,
+                  items,
+               });
+            }
+         }
+      });
+}
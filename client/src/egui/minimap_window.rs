@@ -0,0 +1,122 @@
+use super::MmoContext;
+use egui_macroquad::{
+   egui::{self, Color32, Pos2, Rect, Sense, Stroke, Vec2},
+   macroquad::prelude::*,
+};
+
+/// Width and height of the map, in points.
+const SIZE: f32 = 128.0;
+const SCALES: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+
+pub fn create_minimap_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   egui::Window::new("Minimap")
+      .default_pos(Pos2::new(screen_width(), 40.))
+      .resizable(false)
+      .show(egui_ctx, |ui| {
+         let (px, py, floor) = mmo_ctx.player_location;
+         let minimap = &mut mmo_ctx.minimap;
+
+         ui.horizontal(|ui| {
+            ui.label(format!("Floor {floor}"));
+            let zoom = SCALES.iter().position(|&s| s == minimap.scale).unwrap_or(1);
+            if ui.small_button("-").clicked() {
+               minimap.scale = SCALES[zoom.saturating_sub(1)];
+            }
+            if ui.small_button("+").clicked() {
+               minimap.scale = SCALES[(zoom + 1).min(SCALES.len() - 1)];
+            }
+         });
+
+         let (response, painter) = ui.allocate_painter(Vec2::splat(SIZE), Sense::click());
+         let painter = painter.with_clip_rect(response.rect);
+         let scale = minimap.scale;
+         let center = response.rect.center();
+         // the top left corner of a tile, the player's being in the middle
+         let to_screen = |x: u32, y: u32| {
+            center + Vec2::new(x as f32 - px as f32 - 0.5, y as f32 - py as f32 - 0.5) * scale
+         };
+
+         painter.rect_filled(response.rect, 0.0, Color32::BLACK);
+         let reach = (SIZE / scale / 2.0).ceil() as i64 + 1;
+         for y in py as i64 - reach..=py as i64 + reach {
+            for x in px as i64 - reach..=px as i64 + reach {
+               if x.is_negative() || y.is_negative() {
+                  continue;
+               }
+               let (x, y) = (x as u32, y as u32);
+               if let Some([r, g, b]) = minimap.explored((x, y, floor)) {
+                  let tile = Rect::from_min_size(to_screen(x, y), Vec2::splat(scale));
+                  painter.rect_filled(tile, 0.0, Color32::from_rgb(r, g, b));
+               }
+            }
+         }
+
+         let tile_center = |(x, y, _): (u32, u32, u32)| to_screen(x, y) + Vec2::splat(scale / 2.0);
+         let dot = scale.max(2.0);
+
+         for &(_, location) in &mmo_ctx.party_locations {
+            if location.2 == floor {
+               painter.circle_filled(tile_center(location), dot, Color32::LIGHT_BLUE);
+            }
+         }
+
+         let mut hovered = None;
+         for marker in minimap.markers().iter().filter(|m| m.location.2 == floor) {
+            let at = tile_center(marker.location);
+            painter.circle(at, dot, Color32::YELLOW, Stroke::new(1.0, Color32::BLACK));
+            if response
+               .hover_pos()
+               .is_some_and(|pos| pos.distance(at) <= dot + 2.0)
+            {
+               hovered = Some(marker.label.clone());
+            }
+         }
+
+         painter.rect_filled(
+            Rect::from_center_size(center, Vec2::splat(dot)),
+            0.0,
+            Color32::WHITE,
+         );
+
+         if response.secondary_clicked()
+            && let Some(pos) = response.interact_pointer_pos()
+         {
+            let offset = (pos - center) / scale + Vec2::splat(0.5);
+            let x = px as i64 + offset.x.floor() as i64;
+            let y = py as i64 + offset.y.floor() as i64;
+            if !x.is_negative() && !y.is_negative() {
+               let label = match minimap.marker_text.trim() {
+                  "" => format!("Marker {}", minimap.markers().len() + 1),
+                  text => text.to_string(),
+               };
+               minimap.add_marker((x as u32, y as u32, floor), label);
+               minimap.marker_text.clear();
+            }
+         }
+         match hovered {
+            Some(label) => response.on_hover_text_at_pointer(label),
+            None => response.on_hover_text("Right-click to place a marker"),
+         };
+
+         egui::text_edit::TextEdit::singleline(&mut minimap.marker_text)
+            .hint_text("marker label")
+            .desired_width(SIZE)
+            .show(ui);
+
+         let mut removed = None;
+         for (index, marker) in minimap.markers().iter().enumerate() {
+            if marker.location.2 != floor {
+               continue;
+            }
+            ui.horizontal(|ui| {
+               ui.label(&marker.label);
+               if ui.small_button("x").clicked() {
+                  removed = Some(index);
+               }
+            });
+         }
+         if let Some(index) = removed {
+            minimap.remove_marker(index);
+         }
+      });
+}
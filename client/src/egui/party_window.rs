@@ -0,0 +1,75 @@
+use super::MmoContext;
+use egui_macroquad::{
+   egui::{self, Pos2},
+   macroquad::prelude::*,
+};
+use shared::{Command, PartyAction, network::tcp::TcpClientMsg};
+
+fn send_party_action(mmo_ctx: &MmoContext, action: PartyAction) {
+   mmo_ctx.send_tcp_msg(&TcpClientMsg::Command(Command::Party(action)));
+}
+
+pub fn create_party_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   egui::Window::new("Party")
+      .default_pos(Pos2::new(0., screen_height() / 3.))
+      .default_open(false)
+      .resizable(false)
+      .show(egui_ctx, |ui| {
+         for leader in mmo_ctx.party_invites.clone() {
+            ui.horizontal(|ui| {
+               ui.label(format!("{leader} invited you."));
+               if ui.button("Join").clicked() {
+                  send_party_action(mmo_ctx, PartyAction::Accept(leader.clone()));
+               }
+               if ui.button("Ignore").clicked() {
+                  mmo_ctx.party_invites.retain(|l| *l != leader);
+               }
+            });
+         }
+
+         let is_leader = match &mmo_ctx.party {
+            None => {
+               ui.label("You are not in a party.");
+               true
+            }
+            Some(party) => {
+               let is_leader = party.leader == mmo_ctx.username;
+               for member in &party.members {
+                  ui.horizontal(|ui| {
+                     match *member == party.leader {
+                        true => ui.label(format!("{member} (leader)")),
+                        false => ui.label(member),
+                     };
+                     if is_leader && *member != mmo_ctx.username && ui.small_button("x").clicked() {
+                        send_party_action(mmo_ctx, PartyAction::Kick(member.clone()));
+                     }
+                  });
+               }
+               for invited in &party.invited {
+                  ui.weak(format!("{invited} (invited)"));
+               }
+               if ui.button("Leave").clicked() {
+                  send_party_action(mmo_ctx, PartyAction::Leave);
+               }
+               is_leader
+            }
+         };
+
+         // anybody outside a party can start one by inviting
+         if is_leader {
+            ui.separator();
+            ui.horizontal(|ui| {
+               egui::text_edit::TextEdit::singleline(&mut mmo_ctx.party_text)
+                  .hint_text("name")
+                  .desired_width(100.)
+                  .show(ui);
+
+               let name = mmo_ctx.party_text.trim().to_string();
+               if ui.button("Invite").clicked() && !name.is_empty() {
+                  send_party_action(mmo_ctx, PartyAction::Invite(name));
+                  mmo_ctx.party_text.clear();
+               }
+            });
+         }
+      });
+}
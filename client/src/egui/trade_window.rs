@@ -0,0 +1,78 @@
+use super::MmoContext;
+use egui_macroquad::egui::{self, Align2, Color32};
+use shared::{Item, network::tcp::TcpClientMsg};
+
+pub fn create_trade_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   let Some(trade) = mmo_ctx.trade.clone() else {
+      return;
+   };
+
+   egui::Window::new(format!("Trade with {}", trade.partner))
+      .collapsible(false)
+      .resizable(false)
+      .anchor(Align2::CENTER_TOP, [0.0, 10.0])
+      .show(egui_ctx, |ui| {
+         ui.columns(2, |columns| {
+            render_offer(
+               &mut columns[0],
+               "Your offer",
+               trade.own_offer.as_deref(),
+               trade.own_accepted,
+            );
+            render_offer(
+               &mut columns[1],
+               &format!("{}'s offer", trade.partner),
+               trade.partner_offer.as_deref(),
+               trade.partner_accepted,
+            );
+         });
+
+         ui.separator();
+
+         ui.horizontal(|ui| {
+            // we were asked to trade and have not answered yet
+            if trade.own_offer.is_none() && ui.button("Offer selected items").clicked() {
+               let items = mmo_ctx.selected_items();
+               mmo_ctx.send_tcp_msg(&TcpClientMsg::TradeOffer {
+                  to: trade.partner.clone(),
+                  items,
+               });
+            }
+
+            let can_accept =
+               trade.own_offer.is_some() && trade.partner_offer.is_some() && !trade.own_accepted;
+            if ui
+               .add_enabled(can_accept, egui::Button::new("Accept"))
+               .clicked()
+            {
+               mmo_ctx.send_tcp_msg(&TcpClientMsg::TradeAccept);
+            }
+
+            if ui.button("Cancel").clicked() {
+               mmo_ctx.send_tcp_msg(&TcpClientMsg::TradeCancel);
+            }
+         });
+      });
+}
+
+fn render_offer(ui: &mut egui::Ui, title: &str, offer: Option<&[Item]>, accepted: bool) {
+   ui.strong(title);
+
+   match offer {
+      None => {
+         ui.label("Waiting...");
+      }
+      Some([]) => {
+         ui.label("Nothing");
+      }
+      Some(items) => {
+         for item in items {
+            ui.label(item.to_string());
+         }
+      }
+   }
+
+   if accepted {
+      ui.colored_label(Color32::GREEN, "Accepted");
+   }
+}
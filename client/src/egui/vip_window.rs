@@ -0,0 +1,53 @@
+use super::{ChatTabKind, MmoContext};
+use egui_macroquad::{
+   egui::{self, Color32, Pos2, RichText},
+   macroquad::prelude::*,
+};
+use shared::network::tcp::TcpClientMsg;
+
+pub fn create_vip_window(mmo_ctx: &mut MmoContext, egui_ctx: &egui::Context) {
+   egui::Window::new("VIP")
+      .default_pos(Pos2::new(screen_width(), screen_height() / 3.))
+      .resizable(false)
+      .show(egui_ctx, |ui| {
+         if mmo_ctx.vip.is_empty() {
+            ui.label("Nobody yet.");
+         }
+
+         // online names first, otherwise in the order the server sent them
+         let mut entries = mmo_ctx.vip.clone();
+         entries.sort_by_key(|e| !e.online);
+
+         for entry in entries {
+            ui.horizontal(|ui| {
+               let color = match entry.online {
+                  true => Color32::LIGHT_GREEN,
+                  false => Color32::GRAY,
+               };
+               let name = ui
+                  .selectable_label(false, RichText::new(&entry.name).color(color))
+                  .on_hover_text("Send a private message");
+               if name.clicked() {
+                  mmo_ctx.active_tab = mmo_ctx.open_tab(ChatTabKind::Private(entry.name.clone()));
+               }
+               if ui.small_button("x").clicked() {
+                  mmo_ctx.send_tcp_msg(&TcpClientMsg::VipRemove(entry.name));
+               }
+            });
+         }
+
+         ui.separator();
+         ui.horizontal(|ui| {
+            egui::text_edit::TextEdit::singleline(&mut mmo_ctx.vip_text)
+               .hint_text("name")
+               .desired_width(100.)
+               .show(ui);
+
+            let name = mmo_ctx.vip_text.trim().to_string();
+            if ui.button("Add").clicked() && !name.is_empty() {
+               mmo_ctx.send_tcp_msg(&TcpClientMsg::VipAdd(name));
+               mmo_ctx.vip_text.clear();
+            }
+         });
+      });
+}
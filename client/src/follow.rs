@@ -0,0 +1,75 @@
+use crate::{MmoContext, OtherPlayers, Player, pathfinding::route_next_to};
+use egui_macroquad::macroquad::prelude::*;
+use shared::{AttackTarget, GameObjects, Location, is_adjacent};
+use std::collections::VecDeque;
+use thin_logger::log::info;
+
+const MOVEMENT_KEYS: [KeyCode; 4] = [KeyCode::Up, KeyCode::Down, KeyCode::Left, KeyCode::Right];
+
+pub fn follow(player: &mut Player, target: AttackTarget) {
+   info!("following {target:?}");
+   player.following = Some(target);
+   player.stop_walking();
+}
+
+pub fn stop_following(player: &mut Player) {
+   if player.following.take().is_some() {
+      player.stop_walking();
+   }
+}
+
+/// Walks up to whoever the player follows and waits next to them, planning
+/// the way again whenever they move off it. Walking by hand, clicking the
+/// map or pressing escape stops following, and so does losing sight of them.
+pub fn handle_follow(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
+) {
+   let Some(target) = &player.following else {
+      return;
+   };
+
+   let is_manual = MOVEMENT_KEYS.into_iter().any(is_key_pressed)
+      || is_key_pressed(KeyCode::Escape)
+      || (is_mouse_button_pressed(MouseButton::Left) && !mmo_ctx.pointer_over_ui);
+   if is_manual {
+      stop_following(player);
+      return;
+   }
+
+   let Some(location) = locate(target, game_objects, other_players) else {
+      stop_following(player);
+      mmo_ctx.status = Some(("Target lost.".to_string(), get_time()));
+      return;
+   };
+   if is_adjacent(player.curr_location, location) {
+      player.route.clear();
+      return;
+   }
+
+   let is_stale = player.route.contains(&location)
+      || player
+         .route
+         .back()
+         .is_none_or(|&end| !is_adjacent(end, location));
+   if is_stale {
+      // stays empty while there is no way, until the target moves
+      player.route = VecDeque::from(route_next_to(player, location, game_objects, other_players));
+   }
+}
+
+/// Where a monster or another player in sight is.
+pub fn locate(
+   target: &AttackTarget,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+) -> Option<Location> {
+   match target {
+      AttackTarget::Monster(uid) => game_objects
+         .find_monster(*uid)
+         .map(|(location, _)| location),
+      AttackTarget::Player(name) => other_players.0.get(name).map(|op| op.location),
+   }
+}
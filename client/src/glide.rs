@@ -0,0 +1,53 @@
+use egui_macroquad::macroquad::prelude::*;
+use shared::{Location, distance};
+
+/// Slides an entity from where it was drawn to the tile it moved to, so it
+/// does not jump from one tile to the next.
+#[derive(Debug, Clone, Copy)]
+pub struct Glide {
+   from: Vec2,
+   to: Location,
+   started: f64,
+   duration: f64, // seconds
+}
+
+impl Glide {
+   /// Standing still on `location`.
+   pub fn new(location: Location) -> Glide {
+      Glide {
+         from: tile_position(location),
+         to: location,
+         started: 0.0,
+         duration: 0.0,
+      }
+   }
+
+   /// Starts sliding towards `to`, from wherever the entity is drawn right
+   /// now. Anything farther than a step, like a teleport, is not slid to.
+   pub fn move_to(&mut self, to: Location, now: f64, duration: f64) {
+      if to == self.to {
+         return;
+      }
+      let is_step = to.2 == self.to.2 && distance(to, self.to) <= 1;
+      self.from = match is_step {
+         true => self.position(now),
+         false => tile_position(to),
+      };
+      self.to = to;
+      self.started = now;
+      self.duration = duration;
+   }
+
+   /// Where the entity is drawn, in tiles.
+   pub fn position(&self, now: f64) -> Vec2 {
+      let progress = match self.duration > 0.0 {
+         true => ((now - self.started) / self.duration).clamp(0.0, 1.0),
+         false => 1.0,
+      };
+      self.from.lerp(tile_position(self.to), progress as f32)
+   }
+}
+
+fn tile_position((x, y, _): Location) -> Vec2 {
+   vec2(x as f32, y as f32)
+}
@@ -0,0 +1,80 @@
+use crate::{
+   Action, ContextMenu, MmoContext, OtherPlayers, Player,
+   combat::set_attack_target,
+   follow::{follow, stop_following},
+   pathfinding::get_mouse_map_tile_position,
+};
+use egui_macroquad::macroquad::prelude::*;
+use shared::{AttackTarget, GameObject, GameObjects, network::tcp::TcpClientMsg};
+use thin_logger::log::info;
+
+/// Right clicking the view opens a menu with what can be done to the tile
+/// under the mouse. Any other click closes it.
+pub fn open_context_menu(
+   player: &Player,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
+) {
+   let is_clicked =
+      is_mouse_button_pressed(MouseButton::Left) || is_mouse_button_pressed(MouseButton::Right);
+   if !is_clicked {
+      return;
+   }
+   mmo_ctx.context_menu = None;
+   if !is_mouse_button_pressed(MouseButton::Right) {
+      return;
+   }
+   let Some((x, y)) = get_mouse_map_tile_position(player) else {
+      return;
+   };
+   let location = (x, y, player.z_level);
+
+   let mut actions = vec![Action::Look(location)];
+   let object = game_objects.0.get(&location);
+   if let Some(GameObject::Ladder { .. } | GameObject::Door { .. }) = object {
+      actions.push(Action::Use(location));
+   }
+
+   let other_player = || {
+      (other_players.0.values())
+         .find(|op| op.location == location)
+         .map(|op| AttackTarget::Player(op.username.clone()))
+   };
+   if let Some(target) = object
+      .and_then(|object| object.uid())
+      .map(AttackTarget::Monster)
+      .or_else(other_player)
+   {
+      match player.attack_target.as_ref() == Some(&target) {
+         true => actions.push(Action::StopAttack),
+         false => actions.push(Action::Attack(target.clone())),
+      }
+      match player.following.as_ref() == Some(&target) {
+         true => actions.push(Action::StopFollow),
+         false => actions.push(Action::Follow(target)),
+      }
+   }
+
+   mmo_ctx.context_menu = Some(ContextMenu {
+      screen: mouse_position(),
+      actions,
+   });
+}
+
+/// Does what was picked in the context menu.
+pub fn handle_chosen_action(player: &mut Player, mmo_ctx: &mut MmoContext) {
+   let Some(action) = mmo_ctx.chosen_action.take() else {
+      return;
+   };
+   info!("{action} chosen");
+
+   match action {
+      Action::Look(location) => mmo_ctx.send_tcp_msg(&TcpClientMsg::Look(location)),
+      Action::Use(location) => mmo_ctx.send_tcp_msg(&TcpClientMsg::Use(location)),
+      Action::Attack(target) => set_attack_target(player, Some(target), mmo_ctx),
+      Action::StopAttack => set_attack_target(player, None, mmo_ctx),
+      Action::Follow(target) => follow(player, target),
+      Action::StopFollow => stop_following(player),
+   }
+}
@@ -0,0 +1,217 @@
+use egui_macroquad::macroquad::prelude::*;
+use shared::{
+   Creatures, GameObject, GameObjects, Location, constants::INTERPOLATION_DELAY, distance,
+};
+use std::collections::{HashMap, VecDeque};
+use uuid::Uuid;
+
+/// Seconds of history kept past the newest snapshot.
+const KEPT: f64 = 1.0;
+
+/// Tells the server time from the client's clock, going by the ticks that
+/// snapshots carry.
+#[derive(Debug)]
+pub struct ServerClock {
+   tick_rate_ms: u64,   // as the server told on entering the game
+   offset: Option<f64>, // server time minus client time
+}
+
+impl ServerClock {
+   pub fn new(tick_rate_ms: u64) -> ServerClock {
+      ServerClock {
+         tick_rate_ms,
+         offset: None,
+      }
+   }
+
+   /// The server time of a tick, in seconds.
+   pub fn time_of(&self, tick: u64) -> f64 {
+      (tick * self.tick_rate_ms) as f64 / 1000.0
+   }
+
+   /// Packets that took the shortest way tell the time best, so the clock
+   /// jumps ahead for them and only eases back for the late ones. It starts
+   /// over when the server does.
+   pub fn sync(&mut self, tick: u64, now: f64) {
+      let sample = self.time_of(tick) - now;
+      self.offset = match self.offset {
+         Some(offset) if sample < offset && offset - sample < 1.0 => {
+            Some(offset + (sample - offset) * 0.01)
+         }
+         _ => Some(sample),
+      };
+   }
+
+   /// The server time other players and creatures are drawn at.
+   pub fn render_time(&self, now: f64) -> f64 {
+      now + self.offset.unwrap_or_default() - INTERPOLATION_DELAY
+   }
+}
+
+/// Where an entity has been, as the server times at which it got to each tile.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+   arrivals: VecDeque<(f64, Location)>,
+   newest: f64,
+}
+
+impl Timeline {
+   pub fn new(time: f64, location: Location) -> Timeline {
+      Timeline {
+         arrivals: VecDeque::from([(time, location)]),
+         newest: time,
+      }
+   }
+
+   /// Records that the entity was on `location` at `time`, in whatever order
+   /// the snapshots come.
+   pub fn insert(&mut self, time: f64, location: Location) {
+      // so far back it can only be a restarted server
+      if time + KEPT < self.newest {
+         *self = Timeline::new(time, location);
+         return;
+      }
+      let at = self.arrivals.partition_point(|&(t, _)| t <= time);
+      if at > 0 && self.arrivals[at - 1].1 == location {
+         return;
+      }
+      // a snapshot that came late can make the entity arrive sooner
+      if self
+         .arrivals
+         .get(at)
+         .is_some_and(|&(_, next)| next == location)
+      {
+         self.arrivals.remove(at);
+      }
+      self.arrivals.insert(at, (time, location));
+
+      self.newest = self.newest.max(time);
+      while self.arrivals.len() > 2 && self.arrivals[2].0 + KEPT < self.newest {
+         self.arrivals.pop_front();
+      }
+   }
+
+   /// Where the entity is drawn at server time `time`, in tiles. It slides
+   /// from one tile to the next in `step_duration` seconds and snaps for
+   /// anything that is not a step.
+   pub fn position(&self, time: f64, step_duration: f64) -> Vec2 {
+      let at = self.arrivals.partition_point(|&(t, _)| t <= time);
+      let (arrived, to) = self.arrivals[at.saturating_sub(1)];
+      let Some(&(_, from)) = at.checked_sub(2).and_then(|i| self.arrivals.get(i)) else {
+         return tile_position(to);
+      };
+      if from.2 != to.2 || distance(from, to) > 1 || step_duration <= 0.0 {
+         return tile_position(to);
+      }
+      let progress = ((time - arrived) / step_duration).clamp(0.0, 1.0);
+      tile_position(from).lerp(tile_position(to), progress as f32)
+   }
+}
+
+fn tile_position((x, y, _): Location) -> Vec2 {
+   vec2(x as f32, y as f32)
+}
+
+/// The timeline of every creature in sight, by uid.
+#[derive(Debug, Default)]
+pub struct CreatureTimelines {
+   timelines: HashMap<Uuid, Timeline>,
+   newest: f64,
+}
+
+impl CreatureTimelines {
+   /// Records where the creatures of a snapshot were. Returns whether it is
+   /// the newest snapshot so far, the one to play by. Only that one forgets
+   /// the creatures it does not have.
+   pub fn update(&mut self, time: f64, game_objects: &GameObjects) -> bool {
+      let is_newest = time >= self.newest || time + KEPT < self.newest;
+      let mut seen = vec![];
+      for (&location, object) in &game_objects.0 {
+         let GameObject::Creature { uid, .. } = object else {
+            continue;
+         };
+         match self.timelines.get_mut(uid) {
+            Some(timeline) => timeline.insert(time, location),
+            None if is_newest => {
+               self.timelines.insert(*uid, Timeline::new(time, location));
+            }
+            None => continue,
+         }
+         seen.push(*uid);
+      }
+
+      if is_newest {
+         self.newest = time;
+         self.timelines.retain(|uid, _| seen.contains(uid));
+      }
+      is_newest
+   }
+
+   /// Where the creature is drawn at server time `time`, in tiles.
+   pub fn position(&self, object: &GameObject, creatures: &Creatures, time: f64) -> Option<Vec2> {
+      let GameObject::Creature { uid, .. } = object else {
+         return None;
+      };
+      let step_duration = creatures
+         .of(object)
+         .map_or(0.0, |creature| creature.move_cooldown_ms as f64 / 1000.0);
+      Some(self.timelines.get(uid)?.position(time, step_duration))
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   const A: Location = (1, 1, 0);
+   const B: Location = (2, 1, 0);
+   const C: Location = (3, 1, 0);
+
+   fn arrivals(timeline: &Timeline) -> Vec<(f64, Location)> {
+      timeline.arrivals.iter().copied().collect()
+   }
+
+   #[test]
+   fn test_insert_out_of_order() {
+      let mut timeline = Timeline::new(0.0, A);
+      timeline.insert(0.2, C);
+      timeline.insert(0.1, B);
+      assert_eq!(arrivals(&timeline), [(0.0, A), (0.1, B), (0.2, C)]);
+
+      // still standing there adds nothing
+      timeline.insert(0.3, C);
+      timeline.insert(0.15, B);
+      assert_eq!(arrivals(&timeline), [(0.0, A), (0.1, B), (0.2, C)]);
+   }
+
+   #[test]
+   fn test_late_snapshot_arrives_sooner() {
+      let mut timeline = Timeline::new(0.0, A);
+      timeline.insert(0.2, B);
+      timeline.insert(0.1, B);
+      assert_eq!(arrivals(&timeline), [(0.0, A), (0.1, B)]);
+   }
+
+   #[test]
+   fn test_insert_after_restart() {
+      let mut timeline = Timeline::new(5.0, A);
+      timeline.insert(1.0, B);
+      assert_eq!(arrivals(&timeline), [(1.0, B)]);
+   }
+
+   #[test]
+   fn test_position() {
+      let mut timeline = Timeline::new(0.0, A);
+      timeline.insert(0.2, C);
+      timeline.insert(0.1, B);
+
+      assert_eq!(timeline.position(0.05, 0.1), vec2(1.0, 1.0));
+      assert_eq!(timeline.position(0.15, 0.1), vec2(1.5, 1.0));
+      assert_eq!(timeline.position(0.25, 0.1), vec2(2.5, 1.0));
+      assert_eq!(timeline.position(1.0, 0.1), vec2(3.0, 1.0));
+
+      // anything that is not a step snaps
+      timeline.insert(0.4, (3, 3, 0));
+      assert_eq!(timeline.position(0.45, 0.1), vec2(3.0, 3.0));
+   }
+}
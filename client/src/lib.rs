@@ -1,5 +1,14 @@
+pub mod camera;
+pub mod combat;
+pub mod config;
 pub mod draw;
 pub mod egui;
+pub mod follow;
+pub mod glide;
+pub mod interaction;
+pub mod interpolation;
+pub mod login;
+pub mod minimap;
 pub mod movement;
 pub mod object_interaction;
 pub mod pathfinding;
@@ -11,7 +20,10 @@ pub mod utils;
 
 pub use egui::*;
 pub use player::{ClientOtherPlayer as OtherPlayer, OtherPlayers, Player};
-use shared::{GameObjects, Location};
+use shared::{
+   ChatChannel, ConditionKind, GameObjects, Inventory, Location, MagicEffect, PartyInfo,
+   PlayerStats, Projectile, Skull, Spell, TradeWindow, VipEntry,
+};
 pub use tilesheet::MmoTilesheets;
 pub use utils::{FpsLogger, PingMonitor};
 use uuid::Uuid;
@@ -26,19 +38,31 @@ pub enum Cc {
       client_request_id: u32,
       location: Location,
    },
-   OtherPlayer(shared::OtherPlayer),
+   OtherPlayer {
+      tick: u64,
+      player: shared::OtherPlayer,
+   },
    Disconnect,
    MoveObject {
       from: Location,
       to: Location,
    },
-   Objects(GameObjects),
+   Objects {
+      tick: u64,
+      objects: GameObjects,
+   },
    ChatMsg {
       from: String,
       msg: String,
+      channel: ChatChannel,
    },
+   ServerMsg(String),
+   ChannelJoined(String),
+   ChannelLeft(String),
    Pong(u32), // ping_id
    ReconnectOk,
+   /// Reconnecting failed, the session is over.
+   ConnectionLost,
    PlayerHealthUpdate {
       hp: u32,
    },
@@ -52,4 +76,26 @@ pub enum Cc {
    DamageNumber {
       damage: u32,
    },
+   MagicEffect {
+      effect: MagicEffect,
+      tiles: Vec<Location>,
+   },
+   Projectile(Projectile),
+   Inventory(Inventory),
+   TradeUpdate(TradeWindow),
+   TradeClosed(String), // reason
+   Teleport(Location),
+   VipList(Vec<VipEntry>),
+   VipStatus {
+      name: String,
+      online: bool,
+   },
+   Stats(PlayerStats),
+   Party(Option<PartyInfo>),
+   PartyInvite(String), // leader
+   Guild(Option<String>),
+   Skull(Option<Skull>),
+   Conditions(Vec<ConditionKind>),
+   Status(String),
+   Spells(Vec<Spell>),
 }
@@ -0,0 +1,348 @@
+use crate::draw::BACKGROUND;
+use anyhow::{Result, bail};
+use egui_macroquad::{
+   egui::{self, Align2, Color32, Key, Modifiers},
+   macroquad::prelude::*,
+};
+use shared::{
+   ClientConfig, InitPlayer,
+   network::{
+      tcp::{MsgReader, TcpClientMsg, TcpServerMsg, encode, msg_reader, read_msg},
+      udp::UdpClientMsg,
+   },
+};
+use std::{net::SocketAddr, sync::Arc};
+use thin_logger::log::{debug, info};
+use tokio::{
+   io::AsyncWriteExt,
+   net::{
+      TcpStream, UdpSocket,
+      tcp::{OwnedReadHalf, OwnedWriteHalf},
+   },
+   sync::oneshot::{self, error::TryRecvError},
+};
+
+/// A character in the game, from entering it until logging out.
+pub struct Session {
+   pub server: SocketAddr, // TCP
+   pub socket: Arc<UdpSocket>,
+   pub stream: Connection,
+   pub init_player: InitPlayer,
+}
+
+/// The TCP stream split in two. The reader may have read ahead of the reply
+/// it was waiting for, so it is kept from logging in on into the game.
+pub struct Connection {
+   pub reader: MsgReader<OwnedReadHalf>,
+   pub writer: OwnedWriteHalf,
+}
+
+impl Connection {
+   pub fn new(stream: TcpStream) -> Connection {
+      let (reader, writer) = stream.into_split();
+      Connection {
+         reader: msg_reader(reader),
+         writer,
+      }
+   }
+}
+
+/// Why the game gave way to the login screen again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEnd {
+   LoggedOut,
+   ConnectionLost,
+}
+
+enum Answer {
+   Characters(Vec<String>),
+   Session(Box<Session>),
+}
+
+/// What a request to the server came to, along with the connection if it
+/// is still good for the next one.
+type Outcome = (Option<Connection>, Result<Answer, String>);
+
+/// Logs into an account and picks the character to play with.
+pub struct LoginScreen {
+   server: String,
+   udp_port: u16,
+   account: String,
+   password: String,
+   new_character: String,
+   /// `None` until logged in.
+   characters: Option<Vec<String>>,
+   error: Option<String>,
+   stream: Option<Connection>,
+   pending: Option<oneshot::Receiver<Outcome>>,
+}
+
+impl LoginScreen {
+   pub fn new(config: &ClientConfig) -> Self {
+      LoginScreen {
+         server: config.server_addr.clone(),
+         udp_port: config.server_udp_port,
+         account: String::new(),
+         password: String::new(),
+         new_character: String::new(),
+         characters: None,
+         error: None,
+         stream: None,
+         pending: None,
+      }
+   }
+
+   /// Shows the screen until a character enters the game.
+   pub async fn run(&mut self) -> Session {
+      loop {
+         clear_background(BACKGROUND);
+
+         if let Some(session) = self.poll() {
+            return session;
+         }
+         egui_macroquad::ui(|egui_ctx| {
+            egui_ctx.set_zoom_factor(2.0);
+            match self.characters.clone() {
+               None => self.show_credentials(egui_ctx),
+               Some(characters) => self.show_characters(egui_ctx, &characters),
+            }
+         });
+         egui_macroquad::draw();
+
+         if is_quit_requested() {
+            info!("shutting down client program.");
+            std::process::exit(0);
+         }
+
+         next_frame().await;
+      }
+   }
+
+   /// Back from the game. The account has to be logged into again.
+   pub fn session_ended(&mut self, end: SessionEnd) {
+      self.characters = None;
+      self.password.clear();
+      self.error = match end {
+         SessionEnd::LoggedOut => None,
+         SessionEnd::ConnectionLost => Some("Lost the connection to the server.".to_string()),
+      };
+   }
+
+   fn poll(&mut self) -> Option<Session> {
+      let pending = self.pending.as_mut()?;
+      let (stream, answer) = match pending.try_recv() {
+         Ok(outcome) => outcome,
+         Err(TryRecvError::Empty) => return None,
+         Err(TryRecvError::Closed) => (None, Err("Something went wrong. Try again.".to_string())),
+      };
+      self.pending = None;
+      self.stream = stream;
+
+      match answer {
+         Ok(Answer::Characters(characters)) => {
+            self.characters = Some(characters);
+            self.error = None;
+         }
+         Ok(Answer::Session(session)) => {
+            self.characters = None;
+            self.error = None;
+            return Some(*session);
+         }
+         Err(e) => self.error = Some(e),
+      }
+      if self.stream.is_none() {
+         self.characters = None;
+      }
+      None
+   }
+
+   /// Sends `msg` in the background, connecting first if need be.
+   fn request(&mut self, msg: TcpClientMsg) {
+      let (tx, rx) = oneshot::channel();
+      let server = self.server.trim().to_string();
+      let udp_port = self.udp_port;
+      let stream = self.stream.take();
+      tokio::spawn(async move {
+         _ = tx.send(exchange(server, udp_port, stream, msg).await);
+      });
+      self.pending = Some(rx);
+   }
+
+   fn show_credentials(&mut self, egui_ctx: &egui::Context) {
+      egui::Window::new("Log in")
+         .collapsible(false)
+         .resizable(false)
+         .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+         .show(egui_ctx, |ui| {
+            egui::Grid::new("credentials")
+               .num_columns(2)
+               .show(ui, |ui| {
+                  ui.label("Server");
+                  ui.text_edit_singleline(&mut self.server);
+                  ui.end_row();
+
+                  ui.label("Account");
+                  ui.text_edit_singleline(&mut self.account);
+                  ui.end_row();
+
+                  ui.label("Password");
+                  ui.add(egui::TextEdit::singleline(&mut self.password).password(true));
+                  ui.end_row();
+               });
+
+            self.show_error(ui);
+
+            let log_in = ui.add_enabled(self.pending.is_none(), egui::Button::new("Log in"));
+            let submitted = self.pending.is_none()
+               && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Enter));
+            if log_in.clicked() || submitted {
+               self.log_in();
+            }
+         });
+   }
+
+   fn log_in(&mut self) {
+      let account = self.account.trim().to_string();
+      if account.is_empty() || self.password.is_empty() {
+         self.error = Some("Enter an account name and a password.".to_string());
+         return;
+      }
+      // a new login starts over on a new connection
+      self.stream = None;
+      self.request(TcpClientMsg::Login {
+         account,
+         password: self.password.clone(),
+      });
+   }
+
+   fn show_characters(&mut self, egui_ctx: &egui::Context, characters: &[String]) {
+      egui::Window::new("Characters")
+         .collapsible(false)
+         .resizable(false)
+         .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+         .show(egui_ctx, |ui| {
+            let is_idle = self.pending.is_none();
+
+            if characters.is_empty() {
+               ui.label("No characters yet.");
+            }
+            for name in characters {
+               ui.horizontal(|ui| {
+                  ui.label(name);
+                  if ui.add_enabled(is_idle, egui::Button::new("Play")).clicked() {
+                     self.request(TcpClientMsg::Init(name.clone()));
+                  }
+               });
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+               egui::TextEdit::singleline(&mut self.new_character)
+                  .hint_text("name")
+                  .desired_width(100.)
+                  .show(ui);
+
+               let name = self.new_character.trim().to_string();
+               let create = ui.add_enabled(is_idle, egui::Button::new("Create"));
+               if create.clicked() && !name.is_empty() {
+                  self.request(TcpClientMsg::CreateCharacter(name));
+                  self.new_character.clear();
+               }
+            });
+
+            self.show_error(ui);
+
+            if ui.add_enabled(is_idle, egui::Button::new("Back")).clicked() {
+               self.stream = None;
+               self.characters = None;
+               self.error = None;
+            }
+         });
+   }
+
+   fn show_error(&self, ui: &mut egui::Ui) {
+      if let Some(error) = &self.error {
+         ui.colored_label(Color32::LIGHT_RED, error);
+      }
+   }
+}
+
+/// Sends `msg` on `stream`, or on a new connection to `server` if there is no
+/// stream, and reads the answer.
+async fn exchange(
+   server: String,
+   udp_port: u16,
+   stream: Option<Connection>,
+   msg: TcpClientMsg,
+) -> Outcome {
+   let mut stream = match stream {
+      Some(stream) => stream,
+      None => match TcpStream::connect(&server).await {
+         Ok(stream) => Connection::new(stream),
+         Err(e) => return (None, Err(format!("Could not connect to {server}: {e}."))),
+      },
+   };
+
+   let reply = match request(&mut stream, &msg).await {
+      Ok(reply) => reply,
+      Err(e) => {
+         return (
+            None,
+            Err(format!("Lost the connection to the server: {e}.")),
+         );
+      }
+   };
+
+   match reply {
+      TcpServerMsg::Characters(characters) => (Some(stream), Ok(Answer::Characters(characters))),
+      TcpServerMsg::InitErr(e) => (Some(stream), Err(e)),
+      TcpServerMsg::InitOk(init_player) => match enter_game(stream, udp_port, init_player).await {
+         Ok(session) => (None, Ok(Answer::Session(Box::new(session)))),
+         Err(e) => (None, Err(format!("Could not reach the server: {e}."))),
+      },
+      reply => {
+         debug!("unexpected reply while logging in: {reply:?}");
+         (
+            None,
+            Err("The server answered something unexpected.".to_string()),
+         )
+      }
+   }
+}
+
+async fn request(stream: &mut Connection, msg: &TcpClientMsg) -> Result<TcpServerMsg> {
+   stream.writer.write_all(&encode(msg)?).await?;
+
+   match read_msg(&mut stream.reader).await? {
+      Some(reply) => Ok(reply),
+      None => bail!("the server closed the connection"),
+   }
+}
+
+/// Opens the UDP side of the session. The server listens for it on the same
+/// host as for TCP, on `udp_port`.
+async fn enter_game(stream: Connection, udp_port: u16, init_player: InitPlayer) -> Result<Session> {
+   let server = stream.writer.peer_addr()?;
+
+   let socket = UdpSocket::bind("0.0.0.0:0").await?;
+   socket
+      .connect(SocketAddr::new(server.ip(), udp_port))
+      .await?;
+
+   // lets the server know the address of the UDP socket
+   let initial_ping = UdpClientMsg::Ping {
+      id: init_player.id,
+      client_request_id: 0,
+   };
+   socket.send(&bincode::serialize(&initial_ping)?).await?;
+   debug!("sent initial UDP ping to establish connection");
+
+   info!("{} entered the game on {server}", init_player.username);
+
+   Ok(Session {
+      server,
+      socket: Arc::new(socket),
+      stream,
+      init_player,
+   })
+}
@@ -0,0 +1,136 @@
+use crate::{MmoTilesheets, rendering::TERRAIN_TILESET};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shared::Location;
+use std::{
+   collections::HashMap,
+   fs,
+   io::ErrorKind,
+   path::{Path, PathBuf},
+};
+use thin_logger::log::{error, info};
+
+/// Seconds between saves while playing, so a crash loses little.
+const SAVE_INTERVAL: f64 = 30.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+   pub location: Location,
+   pub label: String,
+}
+
+/// What a character has seen of the world.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Memory {
+   explored: HashMap<Location, [u8; 3]>, // tile -> terrain color
+   markers: Vec<Marker>,
+}
+
+/// The terrain a character has explored and the markers they placed on it,
+/// kept in a file of their own between sessions.
+pub struct Minimap {
+   path: PathBuf,
+   memory: Memory,
+   /// The average color of every terrain tile seen so far, by tile id.
+   tile_colors: HashMap<u32, Option<[u8; 3]>>,
+   has_changed: bool,
+   last_save: f64,
+   /// Points per tile in the minimap window.
+   pub scale: f32,
+   /// Label typed for the next marker.
+   pub marker_text: String,
+}
+
+impl Minimap {
+   /// Reads what `character` explored in earlier sessions. Anything wrong
+   /// with the file only costs the memory, not the session.
+   pub fn load(data_dir: &Path, character: &str) -> Minimap {
+      let path = data_dir.join("minimap").join(format!("{character}.bin"));
+      let memory = match fs::read(&path) {
+         Ok(bytes) => bincode::deserialize(&bytes).unwrap_or_else(|e| {
+            error!("unreadable minimap in {}: {e}", path.display());
+            Memory::default()
+         }),
+         Err(e) if e.kind() == ErrorKind::NotFound => Memory::default(),
+         Err(e) => {
+            error!("failed to read {}: {e}", path.display());
+            Memory::default()
+         }
+      };
+      info!("{character} has explored {} tiles", memory.explored.len());
+
+      Minimap {
+         path,
+         memory,
+         tile_colors: HashMap::new(),
+         has_changed: false,
+         last_save: 0.0,
+         scale: 2.0,
+         marker_text: String::new(),
+      }
+   }
+
+   /// Remembers the terrain tile drawn on `location`.
+   pub fn explore(&mut self, location: Location, tile_id: u32, tilesheets: &MmoTilesheets) {
+      let color = *self.tile_colors.entry(tile_id).or_insert_with(|| {
+         let [r, g, b, _]: [u8; 4] = tilesheets.tile_color(TERRAIN_TILESET, tile_id)?.into();
+         Some([r, g, b])
+      });
+      let Some(color) = color else {
+         return;
+      };
+      if self.memory.explored.insert(location, color) != Some(color) {
+         self.has_changed = true;
+      }
+   }
+
+   /// The terrain color of `location`, if it was ever explored.
+   pub fn explored(&self, location: Location) -> Option<[u8; 3]> {
+      self.memory.explored.get(&location).copied()
+   }
+
+   pub fn markers(&self) -> &[Marker] {
+      &self.memory.markers
+   }
+
+   pub fn add_marker(&mut self, location: Location, label: String) {
+      self.memory.markers.push(Marker { location, label });
+      self.has_changed = true;
+   }
+
+   pub fn remove_marker(&mut self, index: usize) {
+      if index < self.memory.markers.len() {
+         self.memory.markers.remove(index);
+         self.has_changed = true;
+      }
+   }
+
+   /// Saves if there is anything new since the last save.
+   pub fn save(&mut self) {
+      if !self.has_changed {
+         return;
+      }
+      match self.write() {
+         Ok(()) => self.has_changed = false,
+         Err(e) => error!(
+            "failed to save the minimap to {}: {e:#}",
+            self.path.display()
+         ),
+      }
+   }
+
+   pub fn save_every_now_and_then(&mut self, now: f64) {
+      if now - self.last_save >= SAVE_INTERVAL {
+         self.last_save = now;
+         self.save();
+      }
+   }
+
+   fn write(&self) -> Result<()> {
+      if let Some(dir) = self.path.parent() {
+         fs::create_dir_all(dir)?;
+      }
+      fs::write(&self.path, bincode::serialize(&self.memory)?)?;
+      Ok(())
+   }
+}
@@ -1,27 +1,68 @@
 use crate::{OtherPlayers, Player};
 use egui_macroquad::macroquad::prelude::*;
-use shared::{Direction, GameObject, GameObjects, constants::BASE_MOVE_DELAY};
-use thin_logger::log::{debug, info};
+use shared::{Direction, GameObjects, Location, constants::BASE_MOVE_DELAY, move_delay_factor};
+use thin_logger::log::debug;
 
+/// A step the server has not answered yet.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingMove {
+   pub request_id: u32,
+   pub step: (i32, i32),
+   pub sent: bool,
+}
+
+/// Sends the steps taken since the last time.
 pub fn send_pos_to_server(player: &mut Player, socket: &tokio::net::UdpSocket) {
    use shared::network::{sendable::SendableSync, udp::UdpClientMsg};
 
-   if player.curr_location == player.prev_location {
+   let id = player.id;
+   for pending in player.pending_moves.iter_mut().filter(|m| !m.sent) {
+      let msg = UdpClientMsg::PlayerMove {
+         id,
+         client_request_id: pending.request_id,
+         step: pending.step,
+      };
+      socket.send_msg_and_log(&msg, None);
+      pending.sent = true;
+   }
+}
+
+/// Puts the player where the server says they are, with the steps the server
+/// has not answered yet replayed on top. The steps it answered are done with:
+/// the ones it took are part of `location` and the ones it refused are undone.
+pub fn reconcile(
+   player: &mut Player,
+   location: Location,
+   acked: u32,
+   game_objects: &GameObjects,
+   current_time: f64,
+) {
+   // answers can arrive out of order
+   if acked < player.last_acked {
       return;
    }
+   player.last_acked = acked;
+   player.pending_moves.retain(|m| m.request_id > acked);
 
-   let msg = UdpClientMsg::PlayerMove {
-      id: player.id,
-      client_request_id: {
-         player.request_id += 1;
-         player.request_id
-      },
-      location: player.curr_location,
-   };
-   socket.send_msg_and_log(&msg, None);
+   let predicted = player.pending_moves.iter().fold(location, |location, m| {
+      game_objects.step(location, m.step).unwrap_or(location)
+   });
+   if predicted == player.curr_location {
+      return;
+   }
+
+   debug!(
+      "server moved the player from {:?} to {predicted:?}",
+      player.curr_location
+   );
+   player.curr_location = predicted;
+   player.z_level = predicted.2;
+   player
+      .glide
+      .move_to(predicted, current_time, player.speed.into());
 }
 
-pub fn handle_player_movement(player: &mut Player, op: &OtherPlayers) {
+pub fn handle_player_movement(player: &mut Player, game_objects: &GameObjects, op: &OtherPlayers) {
    let current_time = get_time();
    let can_move = current_time - player.last_move_timer >= player.speed.into();
 
@@ -32,14 +73,16 @@ pub fn handle_player_movement(player: &mut Player, op: &OtherPlayers) {
    let mut keys_down = get_keys_down();
 
    if keys_down.len() == 1 {
-      handle_single_key_movement(player, op, keys_down.drain().next().unwrap(), current_time);
+      let key = keys_down.drain().next().unwrap();
+      handle_single_key_movement(player, game_objects, op, key, current_time);
    } else if keys_down.len() == 2 {
-      handle_double_key_movement(player, op, current_time);
+      handle_double_key_movement(player, game_objects, op, current_time);
    }
 }
 
 pub fn handle_single_key_movement(
    player: &mut Player,
+   game_objects: &GameObjects,
    op: &OtherPlayers,
    key: KeyCode,
    current_time: f64,
@@ -48,58 +91,107 @@ pub fn handle_single_key_movement(
    let (x, y) = (x as i32, y as i32);
    match key {
       KeyCode::Right if Player::can_move((x + 1, y), op) => {
-         move_player(player, (1, 0), current_time, BASE_MOVE_DELAY);
+         move_player(player, game_objects, (1, 0), current_time, BASE_MOVE_DELAY);
       }
       KeyCode::Left if Player::can_move((x - 1, y), op) => {
-         move_player(player, (-1, 0), current_time, BASE_MOVE_DELAY);
+         move_player(player, game_objects, (-1, 0), current_time, BASE_MOVE_DELAY);
       }
       KeyCode::Up if Player::can_move((x, y - 1), op) => {
-         move_player(player, (0, -1), current_time, BASE_MOVE_DELAY);
+         move_player(player, game_objects, (0, -1), current_time, BASE_MOVE_DELAY);
       }
       KeyCode::Down if Player::can_move((x, y + 1), op) => {
-         move_player(player, (0, 1), current_time, BASE_MOVE_DELAY);
+         move_player(player, game_objects, (0, 1), current_time, BASE_MOVE_DELAY);
       }
       _ => {}
    }
 }
 
-pub fn handle_double_key_movement(player: &mut Player, op: &OtherPlayers, current_time: f64) {
+pub fn handle_double_key_movement(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   op: &OtherPlayers,
+   current_time: f64,
+) {
    let (x, y, _) = player.curr_location;
    let (x, y) = (x as i32, y as i32);
    if is_key_down(KeyCode::Right)
       && is_key_down(KeyCode::Up)
       && Player::can_move((x + 1, y - 1), op)
    {
-      move_player(player, (1, -1), current_time, BASE_MOVE_DELAY * 2.0);
+      move_player(
+         player,
+         game_objects,
+         (1, -1),
+         current_time,
+         BASE_MOVE_DELAY * 2.0,
+      );
    }
    if is_key_down(KeyCode::Right)
       && is_key_down(KeyCode::Down)
       && Player::can_move((x + 1, y + 1), op)
    {
-      move_player(player, (1, 1), current_time, BASE_MOVE_DELAY * 2.0);
+      move_player(
+         player,
+         game_objects,
+         (1, 1),
+         current_time,
+         BASE_MOVE_DELAY * 2.0,
+      );
    }
    if is_key_down(KeyCode::Left) && is_key_down(KeyCode::Up) && Player::can_move((x - 1, y - 1), op)
    {
-      move_player(player, (-1, -1), current_time, BASE_MOVE_DELAY * 2.0);
+      move_player(
+         player,
+         game_objects,
+         (-1, -1),
+         current_time,
+         BASE_MOVE_DELAY * 2.0,
+      );
    }
    if is_key_down(KeyCode::Left)
       && is_key_down(KeyCode::Down)
       && Player::can_move((x - 1, y + 1), op)
    {
-      move_player(player, (-1, 1), current_time, BASE_MOVE_DELAY * 2.0);
+      move_player(
+         player,
+         game_objects,
+         (-1, 1),
+         current_time,
+         BASE_MOVE_DELAY * 2.0,
+      );
    }
 }
 
-pub fn move_player(player: &mut Player, direction: (isize, isize), current_time: f64, speed: f32) {
-   player.prev_location = player.curr_location;
-   player.curr_location.0 = (player.curr_location.0 as isize + direction.0) as u32;
-   player.curr_location.1 = (player.curr_location.1 as isize + direction.1) as u32;
+/// Takes a step right away, before the server says it may. See [`reconcile`]
+/// for when it does not.
+pub fn move_player(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   step: (i32, i32),
+   current_time: f64,
+   speed: f32,
+) {
+   let Some(location) = game_objects.step(player.curr_location, step) else {
+      return;
+   };
+   player.curr_location = location;
+   player.z_level = location.2;
    player.last_move_timer = current_time;
-   player.speed = speed;
+   player.speed = speed * move_delay_factor(&player.conditions);
+   player
+      .glide
+      .move_to(player.curr_location, current_time, player.speed.into());
+
+   player.request_id += 1;
+   player.pending_moves.push_back(PendingMove {
+      request_id: player.request_id,
+      step,
+      sent: false,
+   });
 
    debug!("moving player to {:?}", player.curr_location);
 
-   let direction = match direction {
+   let direction = match step {
       (1, 0) => Direction::East,
       (-1, 0) => Direction::West,
       (0, -1) => Direction::North,
@@ -115,17 +207,38 @@ pub fn move_player(player: &mut Player, direction: (isize, isize), current_time:
    player.frame = (player.frame + 1) % 3; // Cycle through frames 0, 1, 2
 }
 
-pub fn check_ladder_interaction(player: &mut Player, game_objects: &GameObjects) {
-   // Check if player is standing on a ladder
-   if let Some(GameObject::Ladder { target_z, .. }) = game_objects.0.get(&player.curr_location)
-      && player.z_level != *target_z
-   {
-      info!(
-         "Player stepping on ladder, changing z_level from {} to {}",
-         player.z_level, target_z
-      );
-      player.z_level = *target_z;
-      player.curr_location.2 = *target_z;
-      player.prev_location.2 = *target_z;
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::player::test_player;
+   use std::collections::HashMap;
+
+   #[test]
+   fn test_reconcile() {
+      let game_objects = GameObjects(HashMap::new());
+      let mut player = test_player((5, 5, 0));
+      for step in [(1, 0), (1, 0), (0, 1)] {
+         move_player(&mut player, &game_objects, step, 0.0, BASE_MOVE_DELAY);
+      }
+      assert_eq!(player.curr_location, (7, 6, 0));
+
+      // the first step was taken, the rest still stands
+      reconcile(&mut player, (6, 5, 0), 1, &game_objects, 0.1);
+      assert_eq!(player.curr_location, (7, 6, 0));
+      assert_eq!(player.pending_moves.len(), 2);
+
+      // the second one was refused and is undone, the third replayed
+      reconcile(&mut player, (6, 5, 0), 2, &game_objects, 0.2);
+      assert_eq!(player.curr_location, (6, 6, 0));
+      assert_eq!(player.pending_moves.len(), 1);
+
+      // a late answer to the first step changes nothing
+      reconcile(&mut player, (6, 5, 0), 1, &game_objects, 0.3);
+      assert_eq!(player.curr_location, (6, 6, 0));
+      assert_eq!(player.last_acked, 2);
+
+      reconcile(&mut player, (6, 6, 0), 3, &game_objects, 0.4);
+      assert_eq!(player.curr_location, (6, 6, 0));
+      assert!(player.pending_moves.is_empty());
    }
 }
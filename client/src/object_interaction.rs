@@ -1,8 +1,7 @@
-use crate::Player;
+use crate::{Player, pathfinding::get_mouse_map_tile_position};
 use egui_macroquad::macroquad::prelude::*;
 use shared::{
    GameObjects, Location,
-   constants::{CAMERA_HEIGHT, CAMERA_WIDTH, TILE_HEIGHT, TILE_WIDTH},
    network::{sendable::SendableSync, udp::UdpClientMsg},
 };
 use thin_logger::log::debug;
@@ -21,27 +20,15 @@ pub fn handle_start_move_object(
       return;
    };
 
-   let (x, y) = mouse_position();
-
-   if x < 0. || y < 0. {
-      return;
-   }
-
-   let (x, y) = ((x / 32.) as usize, (y / 32.) as usize);
-
-   if x >= CAMERA_WIDTH as usize || y >= CAMERA_HEIGHT as usize {
+   let Some((x, y)) = get_mouse_map_tile_position(player) else {
       return;
-   }
-
-   let player_x = player.curr_location.0 as i32 - CAMERA_WIDTH as i32 / 2;
-   let player_y = player.curr_location.1 as i32 - CAMERA_HEIGHT as i32 / 2;
-
-   let abs_x = player_x + x as i32;
-   let abs_y = player_y + y as i32;
-
-   let (x, y) = (abs_x as u32, abs_y as u32);
+   };
 
-   if !game_objects.0.contains_key(&(x, y, 0)) {
+   let is_movable = game_objects
+      .0
+      .get(&(x, y, 0))
+      .is_some_and(|object| object.is_movable());
+   if !is_movable {
       return;
    }
 
@@ -67,20 +54,9 @@ pub fn handle_end_move_object(
       return;
    }
 
-   let (x, y) = mouse_position();
-   if x < 0. || y < 0. {
-      return;
-   }
-
-   let (x, y) = ((x / TILE_WIDTH) as usize, (y / TILE_HEIGHT) as usize);
-   if x >= CAMERA_WIDTH as usize || y >= CAMERA_HEIGHT as usize {
+   let Some((x, y)) = get_mouse_map_tile_position(player) else {
       return;
-   }
-
-   let player_x = player.curr_location.0 as i32 - CAMERA_WIDTH as i32 / 2;
-   let player_y = player.curr_location.1 as i32 - CAMERA_HEIGHT as i32 / 2;
-   let (abs_x, abs_y) = (player_x + x as i32, player_y + y as i32);
-   let (x, y) = (abs_x as u32, abs_y as u32);
+   };
 
    if let Some(moving_obj) = moving_object.take()
       && let Some(obj) = game_objects.0.remove(&moving_obj)
@@ -1,25 +1,32 @@
-use crate::{OtherPlayers, Player, movement::handle_single_key_movement};
+use crate::{
+   MmoContext, OtherPlayers, Player, camera::Camera, movement::handle_single_key_movement,
+};
 use egui_macroquad::macroquad::prelude::*;
 use shared::{
    GameObject, GameObjects, Location,
    constants::{CAMERA_HEIGHT, CAMERA_WIDTH, MAP_HEIGHT, MAP_WIDTH, TILE_HEIGHT, TILE_WIDTH},
 };
 use std::collections::{HashMap, HashSet, VecDeque};
-use thin_logger::log::info;
+use thin_logger::log::{debug, info};
 
 type QuickMap = [[bool; MAP_WIDTH as usize]; MAP_HEIGHT as usize];
 
+/// Which tiles of floor `z` can be walked on. Monsters, other players and
+/// closed doors are in the way.
 pub fn construct_map_from_unwalkable_objects(
    game_objects: &GameObjects,
    other_players: &OtherPlayers,
+   z: u32,
 ) -> QuickMap {
    let mut map = [[true; MAP_WIDTH as usize]; MAP_HEIGHT as usize];
-   for (location, game_object) in game_objects.0.iter() {
-      if let GameObject::Orc { .. } = game_object {
+   for (location, game_object) in game_objects.0.iter().filter(|(l, _)| l.2 == z) {
+      let is_unwalkable =
+         game_object.is_monster() || matches!(game_object, GameObject::Door { is_open: false, .. });
+      if is_unwalkable {
          map[location.1 as usize][location.0 as usize] = false;
       }
    }
-   for player in other_players.0.values() {
+   for player in other_players.0.values().filter(|op| op.location.2 == z) {
       map[player.location.1 as usize][player.location.0 as usize] = false;
    }
    map
@@ -74,27 +81,19 @@ pub fn bfs_find_path(map: &QuickMap, start: Location, end: Location) -> Vec<Loca
    vec![]
 }
 
-pub fn get_mouse_map_tile_position(player_location: Location) -> Option<(u32, u32)> {
+/// The map tile under the mouse, as the view shows it right now.
+pub fn get_mouse_map_tile_position(player: &Player) -> Option<(u32, u32)> {
    let (mouse_x, mouse_y) = mouse_position();
 
    if mouse_x < 0. || mouse_y < 0. {
       return None;
    }
 
-   let (tile_x, tile_y) = (
-      (mouse_x / TILE_WIDTH) as usize,
-      (mouse_y / TILE_HEIGHT) as usize,
-   );
-
-   if tile_x >= CAMERA_WIDTH as usize || tile_y >= CAMERA_HEIGHT as usize {
+   if mouse_x >= CAMERA_WIDTH as f32 * TILE_WIDTH || mouse_y >= CAMERA_HEIGHT as f32 * TILE_HEIGHT {
       return None;
    }
 
-   let player_x = player_location.0 as i32 - CAMERA_WIDTH as i32 / 2;
-   let player_y = player_location.1 as i32 - CAMERA_HEIGHT as i32 / 2;
-
-   let abs_x = player_x + tile_x as i32;
-   let abs_y = player_y + tile_y as i32;
+   let (abs_x, abs_y) = Camera::following(player).screen_to_tile(vec2(mouse_x, mouse_y));
 
    if abs_x < 0 || abs_y < 0 {
       return None;
@@ -107,28 +106,78 @@ pub fn program_route_if_user_clicks_map(
    player: &mut Player,
    game_objects: &GameObjects,
    other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
 ) {
    if !is_mouse_button_pressed(MouseButton::Left) {
       return;
    }
 
-   let Some((x, y)) = get_mouse_map_tile_position(player.curr_location) else {
+   let Some((x, y)) = get_mouse_map_tile_position(player) else {
       return;
    };
 
-   let map = construct_map_from_unwalkable_objects(game_objects, other_players);
-   let path = bfs_find_path(&map, player.curr_location, (x, y, 0));
+   // clicking a monster or a player targets them rather than walks there
+   let map = construct_map_from_unwalkable_objects(game_objects, other_players, player.z_level);
+   let is_walkable = (map.get(y as usize)).is_some_and(|row| row.get(x as usize) == Some(&true));
+   if !is_walkable {
+      return;
+   }
+
+   player.destination = Some((x, y, player.z_level));
+   plan_route(player, game_objects, other_players, mmo_ctx);
+}
+
+/// Plans the way to the destination from where the player stands now, and
+/// gives up on it when there is none.
+fn plan_route(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
+) {
+   let Some(destination) = player.destination else {
+      return;
+   };
+   let map = construct_map_from_unwalkable_objects(game_objects, other_players, destination.2);
+   let path = bfs_find_path(&map, player.curr_location, destination);
 
    info!("path: {:?}", path);
 
    if path.is_empty() {
+      if player.curr_location != destination {
+         mmo_ctx.status = Some(("There is no way.".to_string(), get_time()));
+      }
+      player.stop_walking();
       return;
    }
 
    player.route = VecDeque::from(path);
 }
 
-pub fn handle_route(player: &mut Player, game_objects: &GameObjects, other_players: &OtherPlayers) {
+/// A path to a tile next to `target`, which is taken by whatever is there.
+/// Empty if there is none or if the player is next to it already.
+pub fn route_next_to(
+   player: &Player,
+   target: Location,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+) -> Vec<Location> {
+   let mut map = construct_map_from_unwalkable_objects(game_objects, other_players, target.2);
+   map[target.1 as usize][target.0 as usize] = true;
+   let mut path = bfs_find_path(&map, player.curr_location, target);
+   path.pop();
+   path
+}
+
+/// Takes the next step of the route. The route was planned from what was
+/// known back then, so it is planned again when something got in the way or
+/// the server put the player somewhere else.
+pub fn handle_route(
+   player: &mut Player,
+   game_objects: &GameObjects,
+   other_players: &OtherPlayers,
+   mmo_ctx: &mut MmoContext,
+) {
    if player.route.is_empty() {
       return;
    }
@@ -142,26 +191,33 @@ pub fn handle_route(player: &mut Player, game_objects: &GameObjects, other_playe
 
    let next_location = player.route.front().unwrap();
 
-   // TODO: there might be other objects on the path that you can't move
-   // through
-   if let Some(obj) = game_objects.0.get(next_location)
-      && obj.is_monster()
-   {
-      return;
-   }
-
    let key = match (
       next_location.0 as isize - player.curr_location.0 as isize,
       next_location.1 as isize - player.curr_location.1 as isize,
    ) {
-      (0, -1) => KeyCode::Up,
-      (0, 1) => KeyCode::Down,
-      (1, 0) => KeyCode::Right,
-      (-1, 0) => KeyCode::Left,
-      _ => return,
+      (0, -1) => Some(KeyCode::Up),
+      (0, 1) => Some(KeyCode::Down),
+      (1, 0) => Some(KeyCode::Right),
+      (-1, 0) => Some(KeyCode::Left),
+      _ => None,
+   };
+   let map = construct_map_from_unwalkable_objects(game_objects, other_players, player.z_level);
+   let is_blocked = (player.route.iter()).any(|&(x, y, _)| !map[y as usize][x as usize]);
+
+   let Some(key) = key.filter(|_| !is_blocked) else {
+      debug!("route from {:?} is blocked", player.curr_location);
+      match player.destination {
+         Some(_) => plan_route(player, game_objects, other_players, mmo_ctx),
+         // following plans its own way
+         None => player.route.clear(),
+      }
+      return;
    };
 
-   handle_single_key_movement(player, other_players, key, get_time());
+   handle_single_key_movement(player, game_objects, other_players, key, get_time());
 
    player.route.pop_front();
+   if player.route.is_empty() {
+      player.destination = None;
+   }
 }
@@ -1,6 +1,11 @@
-use crate::tilesheet::MmoTilesheets;
+use crate::{
+   camera::Camera, glide::Glide, interpolation::Timeline, movement::PendingMove,
+   tilesheet::MmoTilesheets,
+};
 use egui_macroquad::macroquad::prelude::*;
-use shared::{Direction, Location, constants::*};
+use shared::{
+   AttackTarget, ConditionKind, Direction, InitPlayer, Location, PartyShield, Skull, constants::*,
+};
 use std::collections::{HashMap, VecDeque};
 use uuid::Uuid;
 
@@ -9,35 +14,85 @@ pub struct Player {
    pub id: Uuid,
    pub username: String,
    pub request_id: u32,
+   /// The steps taken but not acknowledged by the server yet, oldest first.
+   pub pending_moves: VecDeque<PendingMove>,
+   pub last_acked: u32,
    pub level: u32,
+   pub experience: u64,
    pub hp: u32,
    pub max_hp: u32,
+   pub mana: u32,
+   pub max_mana: u32,
    pub curr_location: Location,
-   pub prev_location: Location,
    pub z_level: u32,
    pub route: VecDeque<Location>,
+   /// The tile clicked to walk to, kept to plan the way again when something
+   /// steps into it.
+   pub destination: Option<Location>,
    pub last_move_timer: f64,
    pub speed: f32,
+   pub glide: Glide,
    pub direction: Direction,
    pub frame: u32,
+   pub attack_target: Option<AttackTarget>,
+   /// Who the player keeps walking up to. See [`crate::follow`].
+   pub following: Option<AttackTarget>,
+   pub skull: Option<Skull>,
+   pub conditions: Vec<ConditionKind>,
 }
 
 impl Player {
+   pub fn new(init_player: &InitPlayer) -> Player {
+      Player {
+         id: init_player.id,
+         username: init_player.username.clone(),
+         level: init_player.level,
+         experience: init_player.experience,
+         hp: init_player.hp,
+         max_hp: init_player.max_hp,
+         mana: init_player.mana,
+         max_mana: init_player.max_mana,
+         frame: 0,
+         request_id: 0,
+         pending_moves: VecDeque::new(),
+         last_acked: 0,
+         speed: BASE_MOVE_DELAY,
+         glide: Glide::new(init_player.location),
+         curr_location: init_player.location,
+         z_level: init_player.z_level,
+         route: VecDeque::new(),
+         destination: None,
+         last_move_timer: 0.0,
+         direction: init_player.direction,
+         attack_target: None,
+         following: None,
+         skull: None,
+         conditions: vec![],
+      }
+   }
+
+   pub fn stop_walking(&mut self) {
+      self.route.clear();
+      self.destination = None;
+   }
+
    /// Renders the player in the middle of the viewport.
-   pub fn render(&self, tilesheets: &MmoTilesheets) {
+   pub fn render(
+      &self,
+      tilesheets: &MmoTilesheets,
+      shield: Option<PartyShield>,
+      guild: Option<&str>,
+   ) {
       let x = (CAMERA_WIDTH / 2) as f32 * TILE_WIDTH;
       let y = (CAMERA_HEIGHT / 2) as f32 * TILE_HEIGHT;
 
       self.render_health_bar();
+      self.render_mana_bar();
 
-      render_entity_name(&self.username, (x, y));
+      render_player_name(&self.username, guild, self.skull, (x, y));
+      render_party_shield(shield, (x, y));
 
-      render_player(
-         self.direction,
-         (CAMERA_WIDTH / 2, CAMERA_HEIGHT / 2, 0),
-         tilesheets,
-         self.frame,
-      );
+      render_player(self.direction, vec2(x, y), tilesheets, self.frame);
    }
 
    /// Renders the player's health bar above the player.
@@ -67,6 +122,17 @@ impl Player {
       );
    }
 
+   /// Renders a thin mana bar right under the health bar.
+   fn render_mana_bar(&self) {
+      let manabar_pct: f32 = self.mana as f32 / self.max_mana.max(1) as f32;
+
+      let x = (CAMERA_WIDTH / 2) as f32 * TILE_WIDTH;
+      let y = (CAMERA_HEIGHT / 2) as f32 * TILE_HEIGHT - 2.0;
+
+      draw_rectangle(x, y, 32.0, 2.0, DARKBLUE);
+      draw_rectangle(x, y, 32.0 * manabar_pct, 2.0, SKYBLUE);
+   }
+
    pub fn can_move((x, y): (i32, i32), op: &OtherPlayers) -> bool {
       if x.is_negative() || y.is_negative() {
          return false;
@@ -90,32 +156,34 @@ impl Player {
 pub struct OtherPlayers(pub HashMap<String, ClientOtherPlayer>);
 
 impl OtherPlayers {
-   pub fn render(&self, player: &Player, tilesheets: &MmoTilesheets) {
+   /// Renders the other players where they were at server time `time`.
+   pub fn render(
+      &self,
+      player: &Player,
+      camera: &Camera,
+      tilesheets: &MmoTilesheets,
+      time: f64,
+      shield_of: impl Fn(&str) -> Option<PartyShield>,
+   ) {
       for op in self.0.values() {
-         let (x, y) = (op.location.0 as i32, op.location.1 as i32);
-         let (px, py) = (player.curr_location.0 as i32, player.curr_location.1 as i32);
-
-         let relative_offset_x = (CAMERA_WIDTH / 2) as i32;
-         let relative_offset_y = (CAMERA_HEIGHT / 2) as i32;
+         let screen = camera.to_screen(op.timeline.position(time, BASE_MOVE_DELAY.into()));
 
          // is the `other_player` outside the viewport?
-         if x < px - relative_offset_x
-            || x > px + relative_offset_x
-            || y < py - relative_offset_y
-            || y > py + relative_offset_y
-         {
+         if !camera.shows(screen) {
             continue;
          }
 
-         // determine where to render relative to the player
-         let x = x - px + CAMERA_WIDTH as i32 / 2;
-         let y = y - py + CAMERA_HEIGHT as i32 / 2;
-
-         render_entity_name(
-            &op.username,
-            (x as f32 * TILE_WIDTH, y as f32 * TILE_HEIGHT),
-         );
-         render_player(op.direction, (x as u32, y as u32, 0), tilesheets, op.frame);
+         let screen_location = (screen.x, screen.y);
+         if matches!(&player.attack_target, Some(AttackTarget::Player(name)) if *name == op.username)
+         {
+            draw_rectangle_lines(screen.x, screen.y, TILE_WIDTH, TILE_HEIGHT, 2.0, RED);
+         }
+         if matches!(&player.following, Some(AttackTarget::Player(name)) if *name == op.username) {
+            render_follow_frame(screen);
+         }
+         render_player_name(&op.username, op.guild.as_deref(), op.skull, screen_location);
+         render_party_shield(shield_of(&op.username), screen_location);
+         render_player(op.direction, screen, tilesheets, op.frame);
       }
    }
 }
@@ -127,36 +195,47 @@ pub struct ClientOtherPlayer {
    pub username: String,
    pub location: Location,
    pub direction: Direction,
+   pub guild: Option<String>,
+   pub skull: Option<Skull>,
+   pub hp_percent: u8,
    pub frame: u32,
+   /// The tick of the newest snapshot of this player.
+   pub tick: u64,
+   pub timeline: Timeline,
 }
 
 impl ClientOtherPlayer {
-   pub fn new(username: String, location: Location, direction: Direction) -> Self {
-      Self {
-         username,
-         location,
-         direction,
-         frame: 0,
-      }
-   }
-
-   pub fn from_shared(other: &shared::OtherPlayer) -> Self {
+   /// `time` is the server time of `tick`.
+   pub fn from_shared(other: &shared::OtherPlayer, tick: u64, time: f64) -> Self {
       Self {
          username: other.username.clone(),
          location: other.location,
          direction: other.direction,
+         guild: other.guild.clone(),
+         skull: other.skull,
+         hp_percent: other.hp_percent,
          frame: 0,
+         tick,
+         timeline: Timeline::new(time, other.location),
       }
    }
 }
 
+/// The frame around whoever the player follows, inside the attack frame so
+/// both show.
+pub fn render_follow_frame(screen: Vec2) {
+   draw_rectangle_lines(
+      screen.x + 2.0,
+      screen.y + 2.0,
+      TILE_WIDTH - 4.0,
+      TILE_HEIGHT - 4.0,
+      2.0,
+      GREEN,
+   );
+}
+
 /// Renders only the game master avatar for the time being.
-pub fn render_player(
-   direction: Direction,
-   location: Location,
-   tilesheet: &MmoTilesheets,
-   frame: u32,
-) {
+pub fn render_player(direction: Direction, screen: Vec2, tilesheet: &MmoTilesheets, frame: u32) {
    let tile_to_render = match direction {
       Direction::North => 4 + frame,
       Direction::South => 1 + frame,
@@ -164,11 +243,86 @@ pub fn render_player(
       Direction::East => 7 + frame,
    };
 
-   tilesheet.render_tile_at("chars", tile_to_render, location);
+   tilesheet.render_tile_at_screen("chars", tile_to_render, screen);
 }
 
-pub fn render_entity_name(name: &str, screen_location: (f32, f32)) {
+pub fn render_entity_name(name: &str, skull: Option<Skull>, screen_location: (f32, f32)) {
    let (x, y) = screen_location;
    let y = y - 10.0; // move the text slightly above the entity 
    draw_text(name, x, y, 20.0 /* font size */, BLACK);
+
+   if let Some(skull) = skull {
+      render_skull(skull, (x - 9.0, y - 5.0));
+   }
+}
+
+/// A small skull drawn around `center`, left of the name it belongs to.
+fn render_skull(skull: Skull, (x, y): (f32, f32)) {
+   let color = match skull {
+      Skull::White => WHITE,
+      Skull::Red => RED,
+   };
+
+   draw_circle(x, y, 6.0, color);
+   draw_rectangle(x - 3.5, y + 2.0, 7.0, 5.0, color);
+   draw_circle_lines(x, y, 6.0, 1.0, BLACK);
+   // eyes
+   draw_circle(x - 2.5, y, 1.5, BLACK);
+   draw_circle(x + 2.5, y, 1.5, BLACK);
+}
+
+/// Like `render_entity_name`, with the guild name, if any, right under it.
+pub fn render_player_name(
+   name: &str,
+   guild: Option<&str>,
+   skull: Option<Skull>,
+   screen_location: (f32, f32),
+) {
+   let Some(guild) = guild else {
+      render_entity_name(name, skull, screen_location);
+      return;
+   };
+
+   let (x, y) = screen_location;
+   render_entity_name(name, skull, (x, y - 12.0));
+   draw_text(guild, x, y - 10.0, 15.0, DARKGRAY);
+}
+
+/// Draws the party shield to the top right of an entity, if it has one.
+pub fn render_party_shield(shield: Option<PartyShield>, screen_location: (f32, f32)) {
+   let Some(shield) = shield else {
+      return;
+   };
+
+   let (x, y) = screen_location;
+   let (x, y) = (x + TILE_WIDTH - 4.0, y - 4.0);
+   let (fill, border) = match shield {
+      PartyShield::Leader => (GOLD, BLACK),
+      PartyShield::Member => (BLUE, BLACK),
+      PartyShield::Invited => (WHITE, GOLD),
+      PartyShield::Inviter => (WHITE, BLUE),
+   };
+
+   draw_circle(x, y, 5.0, fill);
+   draw_circle_lines(x, y, 5.0, 1.5, border);
+}
+
+/// A level one player standing on `location`.
+#[cfg(test)]
+pub fn test_player(location: Location) -> Player {
+   Player::new(&InitPlayer {
+      id: Uuid::new_v4(),
+      username: "tester".to_string(),
+      location,
+      z_level: location.2,
+      hp: 100,
+      max_hp: 100,
+      mana: 50,
+      max_mana: 50,
+      level: 1,
+      direction: Direction::South,
+      experience: 0,
+      inventory: shared::Inventory::starter(),
+      tick_rate_ms: 50,
+   })
 }
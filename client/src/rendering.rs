@@ -1,118 +1,211 @@
-use crate::{MmoTilesheets, Player, player::render_entity_name};
+use crate::{
+   MmoTilesheets, Player,
+   camera::Camera,
+   interpolation::CreatureTimelines,
+   minimap::Minimap,
+   player::{render_entity_name, render_follow_frame},
+};
 use egui_macroquad::macroquad::prelude::*;
 use shared::{
-   Direction, GameObject, GameObjects,
+   AttackTarget, ConditionKind, Creatures, GameObject, GameObjects,
    constants::{CAMERA_HEIGHT, CAMERA_WIDTH, TILE_HEIGHT, TILE_WIDTH},
-   game_objects::ORC_MAX_HP,
+   zones::Fields,
 };
 use thin_logger::log::trace;
 use tiled::Map;
 
-pub fn render_view(player: &Player, map: &Map, tilesheets: &MmoTilesheets) {
-   for i in 0..CAMERA_HEIGHT {
-      for j in 0..CAMERA_WIDTH {
-         let x = player.curr_location.0 as i32 - CAMERA_WIDTH as i32 / 2 + j as i32;
-         let y = player.curr_location.1 as i32 - CAMERA_HEIGHT as i32 / 2 + i as i32;
-
-         let mut tile_drawn = false;
-
-         // Iterate through all layers to find the correct z-level
-         for (group_idx, layer) in map.layers().enumerate() {
-            // z_level 0 = base group (first group), z_level 1 = top group (second group)
-            let tile = match layer.layer_type() {
-               tiled::LayerType::Group(group_layer) if group_idx == player.z_level as usize => {
-                  // Look for tile layers inside the current z-level group
-                  group_layer.layers().find_map(|l| match l.layer_type() {
-                     tiled::LayerType::Tiles(tl) => tl.get_tile(x, y),
-                     _ => None,
-                  })
-               }
-               _ => None,
-            };
-
-            if let Some(t) = tile
-               && let Some(t_id) = t.id().into()
-            {
-               tilesheets.render_tile_at("grass-tileset", t_id, (j, i, 0));
-               tile_drawn = true;
-               break;
+/// The tileset the ground is drawn from.
+pub const TERRAIN_TILESET: &str = "grass-tileset";
+
+/// Draws the ground in view, and lets the minimap know it has been seen.
+pub fn render_view(
+   player: &Player,
+   camera: &Camera,
+   map: &Map,
+   tilesheets: &MmoTilesheets,
+   minimap: &mut Minimap,
+) {
+   for (x, y) in camera.visible_tiles() {
+      let screen = camera.to_screen(vec2(x as f32, y as f32));
+      let mut tile_drawn = false;
+
+      // Iterate through all layers to find the correct z-level
+      for (group_idx, layer) in map.layers().enumerate() {
+         // z_level 0 = base group (first group), z_level 1 = top group (second group)
+         let tile = match layer.layer_type() {
+            tiled::LayerType::Group(group_layer) if group_idx == player.z_level as usize => {
+               // Look for tile layers inside the current z-level group
+               group_layer.layers().find_map(|l| match l.layer_type() {
+                  tiled::LayerType::Tiles(tl) => tl.get_tile(x, y),
+                  _ => None,
+               })
             }
+            _ => None,
+         };
+
+         if let Some(t) = tile
+            && let Some(t_id) = t.id().into()
+         {
+            tilesheets.render_tile_at_screen(TERRAIN_TILESET, t_id, screen);
+            minimap.explore((x as u32, y as u32, player.z_level), t_id, tilesheets);
+            tile_drawn = true;
+            break;
          }
+      }
 
-         if !tile_drawn {
-            draw_rectangle(
-               j as f32 * TILE_HEIGHT,
-               i as f32 * TILE_WIDTH,
-               TILE_WIDTH,
-               TILE_HEIGHT,
-               BLACK,
-            );
-         }
+      if !tile_drawn {
+         draw_rectangle(screen.x, screen.y, TILE_WIDTH, TILE_HEIGHT, BLACK);
       }
    }
 }
 
-pub fn render_objects(player: &Player, tilesheets: &MmoTilesheets, game_objects: &GameObjects) {
-   for i in 0..CAMERA_HEIGHT {
-      for j in 0..CAMERA_WIDTH {
-         let x = player.curr_location.0 as i32 - CAMERA_WIDTH as i32 / 2 + j as i32;
-         let y = player.curr_location.1 as i32 - CAMERA_HEIGHT as i32 / 2 + i as i32;
+/// Tints the field tiles in view by the condition they give.
+pub fn render_fields(player: &Player, camera: &Camera, fields: &Fields) {
+   for (&location, spec) in &fields.0 {
+      if location.2 != player.z_level {
+         continue;
+      }
+      let screen = camera.tile_to_screen(location);
+      if !camera.shows(screen) {
+         continue;
+      }
 
-         if x.is_negative() || y.is_negative() {
-            continue;
-         }
+      let color = match spec.kind {
+         ConditionKind::Burning => Color::new(1.0, 0.3, 0.0, 0.35),
+         _ => Color::new(0.2, 0.8, 0.1, 0.35),
+      };
+      draw_rectangle(screen.x, screen.y, TILE_WIDTH, TILE_HEIGHT, color);
+   }
+}
 
-         let (x, y) = (x as u32, y as u32);
+/// Dots the way the player walks on their own, and frames where it ends.
+pub fn render_route(player: &Player, camera: &Camera) {
+   let center = vec2(TILE_WIDTH, TILE_HEIGHT) / 2.0;
+   for &location in &player.route {
+      let screen = camera.tile_to_screen(location) + center;
+      draw_circle(screen.x, screen.y, 3.0, Color::new(1.0, 1.0, 1.0, 0.6));
+   }
+   if let Some(destination) = player.destination {
+      let screen = camera.tile_to_screen(destination);
+      draw_rectangle_lines(
+         screen.x + 4.0,
+         screen.y + 4.0,
+         TILE_WIDTH - 8.0,
+         TILE_HEIGHT - 8.0,
+         2.0,
+         YELLOW,
+      );
+   }
+}
 
-         // Check if object exists at player's current z_level
-         let object_location = (x, y, player.z_level);
-         if !game_objects.0.contains_key(&object_location) {
-            continue;
-         }
+/// Shows the player's conditions as small lettered squares in the bottom-left
+/// corner of the view.
+pub fn render_condition_icons(conditions: &[ConditionKind]) {
+   let size = 16.0;
+   let y = CAMERA_HEIGHT as f32 * TILE_HEIGHT - size - 4.0;
+
+   for (n, kind) in conditions.iter().enumerate() {
+      let (letter, color) = match kind {
+         ConditionKind::Poison => ("P", DARKGREEN),
+         ConditionKind::Burning => ("B", ORANGE),
+         ConditionKind::Haste => ("H", SKYBLUE),
+         ConditionKind::Paralyze => ("Z", DARKPURPLE),
+         ConditionKind::Regeneration => ("R", PINK),
+      };
+      let x = 4.0 + n as f32 * (size + 2.0);
+      draw_rectangle(x, y, size, size, color);
+      draw_rectangle_lines(x, y, size, size, 1.0, BLACK);
+      draw_text(letter, x + 4.0, y + 13.0, 16.0, WHITE);
+   }
+}
+
+/// Shows a line of text along the bottom of the view, like what the player
+/// looked at.
+pub fn render_status_line(status: &str) {
+   let font_size = 20.0;
+   let size = measure_text(status, None, font_size as u16, 1.0);
+   let view_width = CAMERA_WIDTH as f32 * TILE_WIDTH;
+   let x = ((view_width - size.width) / 2.0).max(4.0);
+   let y = CAMERA_HEIGHT as f32 * TILE_HEIGHT - 32.0;
+
+   draw_rectangle(
+      x - 4.0,
+      y - size.offset_y - 2.0,
+      size.width + 8.0,
+      size.height + 6.0,
+      Color::new(0.0, 0.0, 0.0, 0.6),
+   );
+   draw_text(status, x, y, font_size, WHITE);
+}
 
-         let game_object = &game_objects.0[&object_location];
-
-         if let GameObject::Orc { hp, direction, .. } = game_object {
-            trace!("Orc direction is: {direction:?}",);
-
-            render_entity_name("Orc", (j as f32 * TILE_WIDTH, i as f32 * TILE_HEIGHT));
-
-            let healthbar_pct: f32 = *hp as f32 / ORC_MAX_HP as f32;
-
-            let bar_width = 32.0;
-            let bar_height = 4.0;
-            let offset_y = -6.0; // move the health bar slightly above the Orc tile
-
-            // background
-            draw_rectangle(
-               j as f32 * TILE_WIDTH,
-               i as f32 * TILE_HEIGHT + offset_y,
-               bar_width,
-               bar_height,
-               RED,
-            );
-
-            // fill
-            draw_rectangle(
-               j as f32 * TILE_WIDTH,
-               i as f32 * TILE_HEIGHT + offset_y,
-               bar_width * healthbar_pct,
-               bar_height,
-               GREEN,
-            );
-
-            let tile_id = match direction {
-               Direction::South => 63,
-               Direction::North => 66,
-               Direction::East => 69,
-               Direction::West => 72,
-            };
-
-            tilesheets.render_tile_at("tibia-sprites", tile_id, (j, i, 0));
-            continue;
+/// Draws the objects in view. Creatures are drawn where they were at server
+/// time `time`.
+pub fn render_objects(
+   player: &Player,
+   camera: &Camera,
+   tilesheets: &MmoTilesheets,
+   game_objects: &GameObjects,
+   creatures: &Creatures,
+   timelines: &CreatureTimelines,
+   time: f64,
+) {
+   for (x, y) in camera.visible_tiles() {
+      if x.is_negative() || y.is_negative() {
+         continue;
+      }
+
+      // Check if object exists at player's current z_level
+      let object_location = (x as u32, y as u32, player.z_level);
+      let Some(game_object) = game_objects.0.get(&object_location) else {
+         continue;
+      };
+
+      if let GameObject::Creature {
+         hp, direction, uid, ..
+      } = game_object
+         && let Some(creature) = creatures.of(game_object)
+      {
+         trace!("{} direction is: {direction:?}", creature.name);
+
+         let screen = match timelines.position(game_object, creatures, time) {
+            Some(position) => camera.to_screen(position),
+            None => camera.tile_to_screen(object_location),
+         };
+
+         if player.attack_target == Some(AttackTarget::Monster(*uid)) {
+            draw_rectangle_lines(screen.x, screen.y, TILE_WIDTH, TILE_HEIGHT, 2.0, RED);
+         }
+         if player.following == Some(AttackTarget::Monster(*uid)) {
+            render_follow_frame(screen);
          }
 
-         tilesheets.render_tile_at("props-tileset", game_object.id(), (j, i, 0));
+         render_entity_name(&creature.name, None, (screen.x, screen.y));
+
+         let healthbar_pct: f32 = *hp as f32 / creature.hp as f32;
+
+         let bar_width = 32.0;
+         let bar_height = 4.0;
+         let offset_y = -6.0; // move the health bar slightly above the creature tile
+
+         // background
+         draw_rectangle(screen.x, screen.y + offset_y, bar_width, bar_height, RED);
+
+         // fill
+         draw_rectangle(
+            screen.x,
+            screen.y + offset_y,
+            bar_width * healthbar_pct,
+            bar_height,
+            GREEN,
+         );
+
+         let tile_id = creature.sprites.facing(*direction);
+
+         tilesheets.render_tile_at_screen("tibia-sprites", tile_id, screen);
+         continue;
       }
+
+      let screen = camera.tile_to_screen(object_location);
+      tilesheets.render_tile_at_screen("props-tileset", game_object.id(), screen);
    }
 }
@@ -1,46 +1,54 @@
 use crate::{Cc, ClientChannel};
 use anyhow::Result;
-use shared::network::tcp::TcpServerMsg;
+use shared::network::tcp::{MsgReader, TcpServerMsg, read_msg};
 use thin_logger::log::{debug, info};
-use tokio::{
-   io::AsyncReadExt, net::tcp::OwnedReadHalf, sync::mpsc::UnboundedSender, task::JoinHandle,
-};
+use tokio::{net::tcp::OwnedReadHalf, sync::mpsc::UnboundedSender, task::JoinHandle};
 use uuid::Uuid;
 
 pub fn tcp_reader_task(
-   mut tcp_read: OwnedReadHalf,
+   mut tcp_read: MsgReader<OwnedReadHalf>,
    cc_tx: UnboundedSender<ClientChannel>,
    user_id: Uuid,
 ) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
-      let mut buf = [0; 1024];
       loop {
-         match tcp_read.read(&mut buf).await {
-            Ok(size) if size > 0 => {
+         match read_msg::<TcpServerMsg, _>(&mut tcp_read).await {
+            Ok(Some(server_msg)) => {
                debug!("received msg from server through the tcp reader");
 
-               let Ok(server_msg) = bincode::deserialize::<TcpServerMsg>(&buf[0..size]) else {
-                  debug!("could not deserialize message from server in tcp listener");
-                  continue;
-               };
-
                let cc = match server_msg {
                   TcpServerMsg::Pong(ping_id) => Cc::Pong(ping_id),
-                  TcpServerMsg::ChatMsg { username, msg } => Cc::ChatMsg {
+                  TcpServerMsg::ChatMsg {
+                     username,
+                     msg,
+                     channel,
+                  } => Cc::ChatMsg {
                      from: username,
                      msg,
+                     channel,
                   },
+                  TcpServerMsg::ServerMsg(msg) => Cc::ServerMsg(msg),
+                  TcpServerMsg::ChannelJoined(channel) => Cc::ChannelJoined(channel),
+                  TcpServerMsg::ChannelLeft(channel) => Cc::ChannelLeft(channel),
                   TcpServerMsg::ReconnectOk => Cc::ReconnectOk,
-                  TcpServerMsg::RespawnOk => {
-                     // Server should send the new health and location via UDP
-                     // For now, just reset to default values
-                     Cc::RespawnOk {
-                        hp: 100,
-                        location: (0, 0, 0),
-                     }
-                  }
+                  TcpServerMsg::RespawnOk { hp, location } => Cc::RespawnOk { hp, location },
+                  TcpServerMsg::Inventory(inventory) => Cc::Inventory(inventory),
+                  TcpServerMsg::TradeUpdate(window) => Cc::TradeUpdate(window),
+                  TcpServerMsg::TradeClosed(reason) => Cc::TradeClosed(reason),
+                  TcpServerMsg::Teleport(location) => Cc::Teleport(location),
+                  TcpServerMsg::VipList(entries) => Cc::VipList(entries),
+                  TcpServerMsg::VipStatus { name, online } => Cc::VipStatus { name, online },
+                  TcpServerMsg::Stats(stats) => Cc::Stats(stats),
+                  TcpServerMsg::Party(party) => Cc::Party(party),
+                  TcpServerMsg::Guild(guild) => Cc::Guild(guild),
+                  TcpServerMsg::Skull(skull) => Cc::Skull(skull),
+                  TcpServerMsg::Conditions(kinds) => Cc::Conditions(kinds),
+                  TcpServerMsg::PartyInvite(leader) => Cc::PartyInvite(leader),
+                  TcpServerMsg::Status(status) => Cc::Status(status),
+                  TcpServerMsg::Spells(spells) => Cc::Spells(spells),
                   TcpServerMsg::InitOk(_) => unreachable!(),
                   TcpServerMsg::InitErr(_) => unreachable!(),
+                  TcpServerMsg::Characters(_) => unreachable!(),
                };
 
                let msg = ClientChannel {
@@ -48,9 +56,13 @@ pub fn tcp_reader_task(
                   msg: cc,
                };
 
-               cc_tx.send(msg).unwrap();
+               cc_tx.send(msg)?;
+            }
+            Err(e) => {
+               info!("could not read message from server: {e}. exiting tcp reader task.");
+               break;
             }
-            _ => {
+            Ok(None) => {
                info!("exiting tcp reader task.");
                break;
             }
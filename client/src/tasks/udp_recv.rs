@@ -1,6 +1,9 @@
 use crate::{Cc, ClientChannel};
 use anyhow::Result;
-use shared::{OtherPlayer, network::udp::UdpServerMsg};
+use shared::{
+   OtherPlayer,
+   network::udp::{MAX_DATAGRAM_SIZE, UdpServerMsg},
+};
 use std::sync::Arc;
 use tokio::{net::UdpSocket, sync::mpsc::UnboundedSender, task::JoinHandle};
 use uuid::Uuid;
@@ -11,7 +14,7 @@ pub fn udp_recv_task(
    user_id: Uuid,
 ) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
-      let mut buf = [0; 1024];
+      let mut buf = vec![0; MAX_DATAGRAM_SIZE];
       while let Ok(size) = udp_socket.recv(&mut buf).await {
          if let Ok(ps) = bincode::deserialize::<UdpServerMsg>(&buf[..size]) {
             match ps {
@@ -28,26 +31,36 @@ pub fn udp_recv_task(
                   };
                   cc_tx.send(cc)?;
                }
-               UdpServerMsg::Objects(game_objects) => {
+               UdpServerMsg::Objects { tick, objects } => {
                   let cc = ClientChannel {
                      id: user_id,
-                     msg: Cc::Objects(game_objects),
+                     msg: Cc::Objects { tick, objects },
                   };
                   cc_tx.send(cc)?;
                }
                UdpServerMsg::Pong(_) => todo!(),
                UdpServerMsg::OtherPlayer {
+                  tick,
                   username,
                   location,
                   direction,
+                  guild,
+                  skull,
+                  hp_percent,
                } => {
                   let cc = ClientChannel {
                      id: user_id,
-                     msg: Cc::OtherPlayer(OtherPlayer {
-                        username,
-                        location,
-                        direction,
-                     }),
+                     msg: Cc::OtherPlayer {
+                        tick,
+                        player: OtherPlayer {
+                           username,
+                           location,
+                           direction,
+                           guild,
+                           skull,
+                           hp_percent,
+                        },
+                     },
                   };
                   cc_tx.send(cc)?;
                }
@@ -65,6 +78,20 @@ pub fn udp_recv_task(
                   };
                   cc_tx.send(cc)?;
                }
+               UdpServerMsg::MagicEffect { effect, tiles } => {
+                  let cc = ClientChannel {
+                     id: user_id,
+                     msg: Cc::MagicEffect { effect, tiles },
+                  };
+                  cc_tx.send(cc)?;
+               }
+               UdpServerMsg::Projectile(projectile) => {
+                  let cc = ClientChannel {
+                     id: user_id,
+                     msg: Cc::Projectile(projectile),
+                  };
+                  cc_tx.send(cc)?;
+               }
                UdpServerMsg::DamageNumber { damage } => {
                   let cc = ClientChannel {
                      id: user_id,
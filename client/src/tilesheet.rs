@@ -1,5 +1,8 @@
 use egui_macroquad::macroquad::prelude::*;
-use shared::Location;
+use shared::{
+   Location,
+   constants::{TILE_HEIGHT, TILE_WIDTH},
+};
 use std::{collections::HashMap, sync::Arc};
 use thin_logger::log::{error, info};
 use tiled::{Map, Tileset};
@@ -58,7 +61,7 @@ impl Tilesheet {
 // Experimental
 
 pub struct MmoTilesheets<'tileset> {
-   layers: HashMap<&'tileset str, (Arc<Tileset>, Texture2D)>,
+   layers: HashMap<&'tileset str, (Arc<Tileset>, Texture2D, Image)>,
 }
 
 impl MmoTilesheets<'_> {
@@ -67,8 +70,9 @@ impl MmoTilesheets<'_> {
 
       for tileset in map.tilesets() {
          info!("loading tileset: {:?}", tileset.name);
-         let texture = texture_from_tileset(tileset);
-         layers.insert(tileset.name.as_str(), (tileset.clone(), texture));
+         let image = image_from_tileset(tileset);
+         let texture = Texture2D::from_image(&image);
+         layers.insert(tileset.name.as_str(), (tileset.clone(), texture, image));
       }
 
       MmoTilesheets { layers }
@@ -76,15 +80,22 @@ impl MmoTilesheets<'_> {
 
    pub fn render_tile_at(&self, tileset_name: &str, tile_id: u32, location: Location) {
       let (x_coordinate, y_coordinate, _) = location;
-      let Some((tileset, texture)) = self.layers.get(tileset_name) else {
+      let screen = vec2(x_coordinate as f32, y_coordinate as f32) * vec2(TILE_WIDTH, TILE_HEIGHT);
+      self.render_tile_at_screen(tileset_name, tile_id, screen);
+   }
+
+   /// Like `render_tile_at`, at any point of the screen rather than on the
+   /// grid of the view.
+   pub fn render_tile_at_screen(&self, tileset_name: &str, tile_id: u32, screen: Vec2) {
+      let Some((tileset, texture, _)) = self.layers.get(tileset_name) else {
          error!("tileset not found: {:?}", tileset_name);
          return;
       };
       let (tile_x, tile_y, width, height) = Self::tile_rect(tileset, tile_id);
       draw_texture_ex(
          texture,
-         x_coordinate as f32 * width,
-         y_coordinate as f32 * height,
+         screen.x,
+         screen.y,
          WHITE,
          DrawTextureParams {
             source: Some(Rect::new(tile_x, tile_y, width, height)),
@@ -94,6 +105,31 @@ impl MmoTilesheets<'_> {
       );
    }
 
+   /// The average color of a tile, which stands for it where it is too small
+   /// to draw, like on the minimap.
+   pub fn tile_color(&self, tileset_name: &str, tile_id: u32) -> Option<Color> {
+      let (tileset, _, image) = self.layers.get(tileset_name)?;
+      let (tile_x, tile_y, width, height) = Self::tile_rect(tileset, tile_id);
+      let (tile_x, tile_y) = (tile_x as u32, tile_y as u32);
+      let (width, height) = (width as u32, height as u32);
+      if tile_x + width > image.width as u32 || tile_y + height > image.height as u32 {
+         return None;
+      }
+
+      let mut sum = [0.0; 3];
+      let mut opaque = 0.0;
+      for y in tile_y..tile_y + height {
+         for x in tile_x..tile_x + width {
+            let pixel = image.get_pixel(x, y);
+            sum[0] += pixel.r * pixel.a;
+            sum[1] += pixel.g * pixel.a;
+            sum[2] += pixel.b * pixel.a;
+            opaque += pixel.a;
+         }
+      }
+      (opaque > 0.0).then(|| Color::new(sum[0] / opaque, sum[1] / opaque, sum[2] / opaque, 1.0))
+   }
+
    fn tile_rect(tileset: &Tileset, id: u32) -> (f32, f32, f32, f32) {
       let tile_width = tileset.tile_width;
       let tile_height = tileset.tile_height;
@@ -109,11 +145,15 @@ impl MmoTilesheets<'_> {
 }
 
 pub fn texture_from_tileset(tileset: &Tileset) -> Texture2D {
+   Texture2D::from_image(&image_from_tileset(tileset))
+}
+
+fn image_from_tileset(tileset: &Tileset) -> Image {
    let image = tileset.image.as_ref().expect("tileset has no image");
 
    info!("loading image: {:?}", image.source);
 
    let bytes = std::fs::read(image.source.clone()).expect("Failed to read texture file");
 
-   Texture2D::from_file_with_format(&bytes, None)
+   Image::from_file_with_format(&bytes, None).expect("Failed to decode texture file")
 }
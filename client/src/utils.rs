@@ -1,5 +1,9 @@
 use egui_macroquad::macroquad::prelude::*;
-use shared::{Location, constants::*, network::tcp::TcpClientMsg};
+use shared::{
+   Location,
+   constants::*,
+   network::tcp::{TcpClientMsg, encode},
+};
 use std::{
    collections::HashMap,
    sync::{Arc, Mutex},
@@ -71,7 +75,7 @@ impl PingMonitor {
             self.ping_counter
          };
 
-         let serialized_ping = bincode::serialize(&TcpClientMsg::Ping(ping_id)).unwrap();
+         let serialized_ping = encode(&TcpClientMsg::Ping(ping_id)).unwrap();
          _ = socket.lock().unwrap().try_write(&serialized_ping);
 
          self.pings.insert(ping_id, curr_time);
@@ -0,0 +1,216 @@
+use crate::storage;
+use anyhow::{Result, anyhow};
+use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier, password_hash::SaltString};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thin_logger::log::{error, info};
+use tokio::{sync::Mutex, task::spawn_blocking};
+
+const ACCOUNTS_PATH: &str = "data/accounts.json";
+const MAX_CHARACTERS: usize = 10;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Account {
+   password_hash: String, // argon2, salt included
+   pub characters: Vec<String>,
+}
+
+/// Every account, saved to disk on every change. There is no sign up: an
+/// account is opened the first time someone logs in with its name.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Accounts(pub HashMap<String, Account>); // account name -> account
+
+impl Accounts {
+   pub fn load() -> Result<Accounts> {
+      storage::load_json(ACCOUNTS_PATH)
+   }
+
+   fn save(&self) {
+      // tests keep their accounts in memory
+      if cfg!(test) {
+         return;
+      }
+      if let Err(e) = storage::save_json(ACCOUNTS_PATH, self) {
+         error!("failed to save accounts: {e:#}");
+      }
+   }
+
+   /// Opens an account with a password hashed beforehand. Someone may have
+   /// opened it in the meantime.
+   fn open(&mut self, name: &str, password_hash: String) -> Result<Vec<String>, String> {
+      if self.0.contains_key(name) {
+         return Err("Could not open the account. Try again.".to_string());
+      }
+      info!("opened account {name}");
+      self.0.insert(
+         name.to_string(),
+         Account {
+            password_hash,
+            characters: vec![],
+         },
+      );
+      self.save();
+      Ok(vec![])
+   }
+
+   /// Adds a character to the account. Names are unique across accounts,
+   /// whatever their case.
+   pub fn create_character(&mut self, account: &str, name: &str) -> Result<Vec<String>, String> {
+      check_character_name(name)?;
+
+      let is_taken = (self.0.values())
+         .flat_map(|account| &account.characters)
+         .any(|character| character.eq_ignore_ascii_case(name));
+      if is_taken {
+         return Err(format!("{name} is already taken."));
+      }
+
+      let Some(account) = self.0.get_mut(account) else {
+         return Err("Log in first.".to_string());
+      };
+      if account.characters.len() >= MAX_CHARACTERS {
+         return Err(format!(
+            "An account has {MAX_CHARACTERS} characters at most."
+         ));
+      }
+
+      account.characters.push(name.to_string());
+      let characters = account.characters.clone();
+      self.save();
+      Ok(characters)
+   }
+
+   pub fn owns(&self, account: &str, character: &str) -> bool {
+      self
+         .0
+         .get(account)
+         .is_some_and(|account| account.characters.iter().any(|c| c == character))
+   }
+}
+
+/// Returns the characters of the account if the password is right, opening
+/// the account if there is none by that name yet. Argon2 is slow on purpose,
+/// so it runs on a blocking thread with the accounts unlocked.
+pub async fn log_in(
+   accounts: &Mutex<Accounts>,
+   name: &str,
+   password: &str,
+) -> Result<Vec<String>, String> {
+   if name.is_empty() || password.is_empty() {
+      return Err("Enter an account name and a password.".to_string());
+   }
+   let wrong = || "Wrong account name or password.".to_string();
+
+   let stored_hash = (accounts.lock().await.0.get(name)).map(|a| a.password_hash.clone());
+   let password = password.to_string();
+   match stored_hash {
+      Some(hash) => {
+         let is_right = spawn_blocking(move || verify_password(&hash, &password))
+            .await
+            .unwrap_or(false);
+         if !is_right {
+            return Err(wrong());
+         }
+         let accounts = accounts.lock().await;
+         accounts
+            .0
+            .get(name)
+            .map(|a| a.characters.clone())
+            .ok_or_else(wrong)
+      }
+      None => {
+         let hash = spawn_blocking(move || hash_password(&password))
+            .await
+            .map_err(anyhow::Error::from)
+            .flatten()
+            .map_err(|e| {
+               error!("failed to hash the password of {name}: {e:#}");
+               "Could not open the account. Try again later.".to_string()
+            })?;
+         accounts.lock().await.open(name, hash)
+      }
+   }
+}
+
+fn check_character_name(name: &str) -> Result<(), String> {
+   if !(4..=20).contains(&name.chars().count()) {
+      return Err("Character names are 4 to 20 letters long.".to_string());
+   }
+   if !name.chars().all(|c| c.is_alphabetic() || c == ' ') || name.trim() != name {
+      return Err("Character names are made of letters and spaces only.".to_string());
+   }
+   Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String> {
+   let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>()).map_err(|e| anyhow!("{e}"))?;
+   let hash = Argon2::default()
+      .hash_password(password.as_bytes(), &salt)
+      .map_err(|e| anyhow!("{e}"))?;
+   Ok(hash.to_string())
+}
+
+fn verify_password(hash: &str, password: &str) -> bool {
+   let Ok(hash) = PasswordHash::new(hash) else {
+      error!("unreadable password hash in {ACCOUNTS_PATH}");
+      return false;
+   };
+   Argon2::default()
+      .verify_password(password.as_bytes(), &hash)
+      .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[tokio::test]
+   async fn test_log_in() {
+      let accounts = Mutex::new(Accounts::default());
+      assert!(log_in(&accounts, "", "secret").await.is_err());
+
+      assert_eq!(log_in(&accounts, "alice", "secret").await, Ok(vec![]));
+      assert!(accounts.lock().await.0.contains_key("alice"));
+      assert_eq!(
+         log_in(&accounts, "alice", "wrong").await,
+         Err("Wrong account name or password.".to_string())
+      );
+
+      accounts
+         .lock()
+         .await
+         .create_character("alice", "Alice")
+         .unwrap();
+      assert_eq!(
+         log_in(&accounts, "alice", "secret").await,
+         Ok(vec!["Alice".to_string()])
+      );
+   }
+
+   #[test]
+   fn test_create_character() {
+      let account = |characters: &[&str]| Account {
+         password_hash: String::new(),
+         characters: characters.iter().map(|c| c.to_string()).collect(),
+      };
+      let full = ["Carol"; MAX_CHARACTERS];
+      let mut accounts = Accounts(HashMap::from([
+         ("alice".to_string(), account(&[])),
+         ("bob".to_string(), account(&["Bobby"])),
+         ("carol".to_string(), account(&full)),
+      ]));
+
+      assert_eq!(
+         accounts.create_character("alice", "Alice Smith"),
+         Ok(vec!["Alice Smith".to_string()])
+      );
+      assert!(accounts.owns("alice", "Alice Smith"));
+      assert!(accounts.create_character("alice", "BOBBY").is_err());
+      assert!(accounts.create_character("alice", "Al").is_err());
+      assert!(accounts.create_character("alice", "Alice2").is_err());
+      assert!(accounts.create_character("alice", " Alice").is_err());
+      assert!(accounts.create_character("nobody", "Nobody").is_err());
+      assert!(accounts.create_character("carol", "Caroline").is_err());
+      assert!(!accounts.owns("carol", "Caroline"));
+   }
+}
@@ -0,0 +1,224 @@
+use crate::Player;
+use futures::future::join_all;
+use shared::{
+   ChatChannel, Speech, SpeechMode, is_valid_channel_name, network::tcp::TcpServerMsg, parse_speech,
+};
+use std::collections::{HashMap, HashSet};
+use thin_logger::log::debug;
+use uuid::Uuid;
+
+/// Named chat channels and the players listening to them. A channel exists
+/// for as long as somebody is in it.
+#[derive(Debug, Default)]
+pub struct Channels(pub HashMap<String, HashSet<Uuid>>);
+
+impl Channels {
+   pub fn join(&mut self, channel: &str, player_id: Uuid) -> bool {
+      self
+         .0
+         .entry(channel.to_string())
+         .or_default()
+         .insert(player_id)
+   }
+
+   pub fn leave(&mut self, channel: &str, player_id: Uuid) -> bool {
+      let Some(members) = self.0.get_mut(channel) else {
+         return false;
+      };
+      let removed = members.remove(&player_id);
+      if members.is_empty() {
+         self.0.remove(channel);
+      }
+      removed
+   }
+
+   pub fn leave_all(&mut self, player_id: Uuid) {
+      for members in self.0.values_mut() {
+         members.remove(&player_id);
+      }
+      self.0.retain(|_, members| !members.is_empty());
+   }
+
+   pub fn is_member(&self, channel: &str, player_id: Uuid) -> bool {
+      self
+         .0
+         .get(channel)
+         .is_some_and(|members| members.contains(&player_id))
+   }
+}
+
+pub async fn send_server_msg(
+   player_id: Uuid,
+   msg: impl Into<String>,
+   players: &mut HashMap<Uuid, Player>,
+) {
+   if let Some(p) = players.get_mut(&player_id) {
+      p.send_tcp_msg(&TcpServerMsg::ServerMsg(msg.into())).await;
+   }
+}
+
+/// Tells muted players they cannot talk yet. Returns whether they are muted.
+pub async fn reject_if_muted(player_id: Uuid, players: &mut HashMap<Uuid, Player>) -> bool {
+   let Some(remaining) = players.get(&player_id).and_then(Player::mute_remaining) else {
+      return false;
+   };
+   let minutes = remaining.as_secs() / 60 + 1;
+   send_server_msg(
+      player_id,
+      format!("You are muted for another {minutes} minutes."),
+      players,
+   )
+   .await;
+   true
+}
+
+/// Local speech reaches whoever stands within range of the speaker. Private
+/// messages go straight to the recipient.
+pub async fn handle_chat_msg(player_id: Uuid, text: String, players: &mut HashMap<Uuid, Player>) {
+   if reject_if_muted(player_id, players).await {
+      return;
+   }
+
+   let Some(speaker) = players.get(&player_id) else {
+      return;
+   };
+   let username = speaker.username.clone();
+   let location = speaker.location;
+
+   match parse_speech(&text) {
+      Speech::Private { to, msg } => send_private_msg(player_id, to, msg, players).await,
+      Speech::Local { mode, msg } => {
+         let chat_msg = TcpServerMsg::ChatMsg {
+            username: username.clone(),
+            msg,
+            channel: ChatChannel::Local(mode),
+         };
+
+         let futures = players
+            .values_mut()
+            .filter(|p| p.id != player_id && mode.can_hear(location, p.location))
+            .filter(|p| !p.ignored.contains(&username))
+            .map(|p| p.send_tcp_msg(&chat_msg));
+         join_all(futures).await;
+      }
+   }
+}
+
+/// Says the words of a spell that was cast. Unlike other speech the caster
+/// hears them too, as their client leaves that up to the server.
+pub async fn say_spell_words(player_id: Uuid, words: String, players: &mut HashMap<Uuid, Player>) {
+   handle_chat_msg(player_id, words.clone(), players).await;
+
+   if let Some(caster) = players.get_mut(&player_id) {
+      let chat_msg = TcpServerMsg::ChatMsg {
+         username: caster.username.clone(),
+         msg: words,
+         channel: ChatChannel::Local(SpeechMode::Say),
+      };
+      caster.send_tcp_msg(&chat_msg).await;
+   }
+}
+
+pub async fn send_private_msg(
+   player_id: Uuid,
+   to: String,
+   msg: String,
+   players: &mut HashMap<Uuid, Player>,
+) {
+   if reject_if_muted(player_id, players).await {
+      return;
+   }
+
+   let Some(username) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+
+   let Some(recipient) = players.values_mut().find(|p| p.username == to) else {
+      send_server_msg(player_id, format!("{to} is not online."), players).await;
+      return;
+   };
+
+   if recipient.ignored.contains(&username) {
+      debug!("{to} ignores {username}. dropping private message.");
+      return;
+   }
+
+   let chat_msg = TcpServerMsg::ChatMsg {
+      username: username.clone(),
+      msg,
+      channel: ChatChannel::Private(username),
+   };
+   recipient.send_tcp_msg(&chat_msg).await;
+}
+
+pub async fn handle_channel_msg(
+   player_id: Uuid,
+   channel: String,
+   msg: String,
+   players: &mut HashMap<Uuid, Player>,
+   channels: &Channels,
+) {
+   if !channels.is_member(&channel, player_id) {
+      send_server_msg(player_id, format!("You are not in #{channel}."), players).await;
+      return;
+   }
+
+   if reject_if_muted(player_id, players).await {
+      return;
+   }
+
+   let Some(username) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+
+   let chat_msg = TcpServerMsg::ChatMsg {
+      username: username.clone(),
+      msg,
+      channel: ChatChannel::Named(channel.clone()),
+   };
+
+   let futures = players
+      .values_mut()
+      .filter(|p| p.id != player_id && channels.is_member(&channel, p.id))
+      .filter(|p| !p.ignored.contains(&username))
+      .map(|p| p.send_tcp_msg(&chat_msg));
+   join_all(futures).await;
+}
+
+pub async fn handle_join_channel(
+   player_id: Uuid,
+   channel: String,
+   players: &mut HashMap<Uuid, Player>,
+   channels: &mut Channels,
+) {
+   if !is_valid_channel_name(&channel) {
+      send_server_msg(
+         player_id,
+         "Channel names are up to 20 letters, digits or dashes.",
+         players,
+      )
+      .await;
+      return;
+   }
+
+   debug!("{player_id} joins #{channel}");
+   channels.join(&channel, player_id);
+
+   if let Some(p) = players.get_mut(&player_id) {
+      p.send_tcp_msg(&TcpServerMsg::ChannelJoined(channel)).await;
+   }
+}
+
+pub async fn handle_leave_channel(
+   player_id: Uuid,
+   channel: String,
+   players: &mut HashMap<Uuid, Player>,
+   channels: &mut Channels,
+) {
+   debug!("{player_id} leaves #{channel}");
+   channels.leave(&channel, player_id);
+
+   if let Some(p) = players.get_mut(&player_id) {
+      p.send_tcp_msg(&TcpServerMsg::ChannelLeft(channel)).await;
+   }
+}
@@ -0,0 +1,241 @@
+use crate::{
+   MapElement, MmoMap, Player, ServerState,
+   party::{Parties, share_experience},
+   player::DamageResult,
+   pvp,
+};
+use futures::future::join_all;
+use itertools::Itertools;
+use rand::Rng;
+use shared::{
+   CreatureType, GameObject, GameObjects, Item, ItemKind, Location, Projectile, Weapon, distance,
+   has_line_of_sight, is_on_screen,
+   network::{sendable::SendableAsync, tcp::TcpServerMsg, udp::UdpServerMsg},
+};
+use std::{collections::HashMap, net::SocketAddr};
+use thin_logger::log::{debug, info};
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+
+pub async fn handle_player_damage(
+   player: &mut Player,
+   damage: u32,
+   source: &str,
+   udp_socket: &UdpSocket,
+   player_udp: SocketAddr,
+) {
+   match player.take_damage(damage, source) {
+      DamageResult::AlreadyDead => {}
+      DamageResult::Damaged { damage, hp } => {
+         info!(
+            "Player {} took {} damage. HP: {}/{}",
+            player.username, damage, hp, player.max_hp
+         );
+
+         let health_msg = UdpServerMsg::PlayerHealthUpdate { hp };
+         udp_socket
+            .send_msg_and_log_(health_msg, Some(player_udp))
+            .await;
+
+         let damage_msg = UdpServerMsg::DamageNumber { damage };
+         udp_socket
+            .send_msg_and_log_(damage_msg, Some(player_udp))
+            .await;
+      }
+      DamageResult::Died {
+         damage,
+         death_message,
+      } => {
+         info!(
+            "Player {} has died from {} damage.",
+            player.username, damage
+         );
+
+         let death_msg = UdpServerMsg::PlayerDeath {
+            message: death_message,
+         };
+         udp_socket
+            .send_msg_and_log_(death_msg, Some(player_udp))
+            .await;
+      }
+   }
+}
+
+/// Damages the monster at `location`, less its armor. A kill removes it and
+/// hands out its experience, and its loot to the killer. Returns whether the
+/// monster died.
+pub async fn hit_monster(
+   killer: Uuid,
+   location: Location,
+   damage: u32,
+   players: &mut HashMap<Uuid, Player>,
+   game_objects: &mut GameObjects,
+   parties: &Parties,
+   state: &ServerState,
+) -> bool {
+   let Some(object) = game_objects.0.get_mut(&location) else {
+      return false;
+   };
+   let Some(creature) = state.creatures.of(object) else {
+      return false;
+   };
+   let GameObject::Creature { hp, uid, .. } = object else {
+      return false;
+   };
+   let damage = damage.saturating_sub(creature.armor);
+   *hp = hp.saturating_sub(damage);
+   debug!("{uid} takes {damage} damage. hp left: {hp}");
+   if let Some(p) = players.get_mut(&killer) {
+      p.enter_combat();
+   }
+
+   if *hp > 0 {
+      return false;
+   }
+
+   info!("{} {uid} was killed by {killer}", creature.name);
+   game_objects.0.remove(&location);
+   state.mmo_map.lock().await[location] = MapElement::Empty;
+
+   for (id, experience) in share_experience(killer, creature.experience, players, parties) {
+      if let Some(p) = players.get_mut(&id) {
+         p.gain_experience(experience).await;
+      }
+   }
+   if let Some(p) = players.get_mut(&killer) {
+      give_loot(p, creature).await;
+   }
+   true
+}
+
+async fn give_loot(player: &mut Player, creature: &CreatureType) {
+   let loot: Vec<Item> = {
+      let mut rng = rand::thread_rng();
+      creature
+         .loot
+         .iter()
+         .filter_map(|loot| {
+            let dropped = rng.gen_bool(loot.chance);
+            dropped.then(|| Item::new(loot.item, rng.gen_range(1..=loot.max)))
+         })
+         .collect()
+   };
+
+   let msg = match loot.is_empty() {
+      true => format!("Loot of {}: nothing.", creature.with_article()),
+      false => format!(
+         "Loot of {}: {}.",
+         creature.with_article(),
+         loot.iter().join(", ")
+      ),
+   };
+   if !loot.is_empty() {
+      for item in loot {
+         player.inventory.add(item);
+      }
+      player
+         .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+         .await;
+   }
+   player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+}
+
+/// Damages another player. Hitting an unmarked player earns the attacker a
+/// skull, killing one counts as an unjustified kill. Returns whether the
+/// target died.
+pub async fn hit_player(
+   attacker_id: Uuid,
+   target_id: Uuid,
+   damage: u32,
+   players: &mut HashMap<Uuid, Player>,
+   udp_socket: &UdpSocket,
+) -> bool {
+   let Some(target) = players.get(&target_id) else {
+      return false;
+   };
+   let (name, target_skull) = (target.username.clone(), target.skull);
+
+   let Some(attacker) = players.get_mut(&attacker_id) else {
+      return false;
+   };
+   pvp::mark_aggression(attacker, target_skull).await;
+   attacker.enter_combat();
+   let attacker_name = attacker.username.clone();
+
+   let Some(target) = players.get_mut(&target_id) else {
+      return false;
+   };
+   let Some(target_udp) = target.udp_socket else {
+      return false;
+   };
+   debug!("{attacker_name} hits {name} for {damage}");
+   let source = &attacker_name;
+   handle_player_damage(target, damage, source, udp_socket, target_udp).await;
+
+   if !target.is_dead {
+      return false;
+   }
+   info!("{attacker_name} killed {name}");
+
+   if let Some(attacker) = players.get_mut(&attacker_id) {
+      pvp::record_kill(attacker, &name, target_skull).await;
+   }
+   true
+}
+
+/// Whether an attack with the given range gets from `from` to `to`.
+pub fn can_reach(from: Location, to: Location, range: u32, mmo_map: &MmoMap) -> bool {
+   distance(from, to) <= range && has_line_of_sight(from, to, |tile| mmo_map.blocks_missiles(tile))
+}
+
+/// Takes one piece of ammunition for a shot with `weapon`, if it needs any,
+/// and warns the player when that was the last one.
+pub async fn use_ammunition(player: &mut Player, weapon: Weapon) {
+   let Some(ammunition) = weapon.ammunition else {
+      return;
+   };
+   player.inventory.remove_all(&[Item::new(ammunition, 1)]);
+   player
+      .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+      .await;
+   if player.inventory.count(ammunition) == 0 {
+      let msg = format!("You have no {}s left.", ammunition.name());
+      player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+   }
+}
+
+/// Wields a weapon from the inventory, or puts it away if it already was.
+pub async fn wield(player: &mut Player, kind: ItemKind) {
+   let msg = if kind.weapon().is_none() {
+      "You cannot wield this.".to_string()
+   } else if player.weapon == Some(kind) {
+      player.weapon = None;
+      format!("You put away the {}.", kind.name())
+   } else if player.inventory.count(kind) == 0 {
+      return;
+   } else {
+      player.weapon = Some(kind);
+      format!("You are now wielding a {}.", kind.name())
+   };
+   player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+}
+
+/// Shows the missiles shot this tick to everyone who can see them.
+pub async fn broadcast_projectiles(
+   projectiles: &[Projectile],
+   players: &HashMap<Uuid, Player>,
+   udp_socket: &UdpSocket,
+) {
+   let futures = projectiles.iter().flat_map(|projectile| {
+      players
+         .values()
+         .filter(|p| {
+            is_on_screen(projectile.from, p.location) || is_on_screen(projectile.to, p.location)
+         })
+         .filter_map(|p| p.udp_socket)
+         .map(|addr| {
+            udp_socket.send_msg_and_log_(UdpServerMsg::Projectile(*projectile), Some(addr))
+         })
+   });
+   join_all(futures).await;
+}
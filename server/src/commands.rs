@@ -0,0 +1,346 @@
+use crate::{
+   MapElement, Player, Sc, ServerChannel, ServerState,
+   chat::{send_private_msg, send_server_msg},
+   death, guild, moderation, movement, party,
+   spawn_manager::{free_tiles_around, place_creature},
+   trade,
+};
+use itertools::Itertools;
+use shared::{
+   COMMANDS, Command, GameObject, Location, Role, find_command, is_on_screen,
+   network::{sendable::SendableAsync, tcp::TcpServerMsg, udp::UdpServerMsg},
+};
+use std::{
+   collections::HashMap,
+   time::{Duration, Instant},
+};
+use thin_logger::log::{info, warn};
+use tokio::{net::UdpSocket, sync::mpsc::UnboundedSender};
+use uuid::Uuid;
+
+const MAX_SUMMONS: u32 = 10;
+
+/// Runs a command sent by `player_id`. Replies go to the player's server tab.
+pub async fn execute_command(
+   player_id: Uuid,
+   command: Command,
+   state: &ServerState,
+   udp_socket: &UdpSocket,
+   sc_tx: &UnboundedSender<ServerChannel>,
+) {
+   let mut players = state.players.lock().await;
+   let Some(player) = players.get_mut(&player_id) else {
+      return;
+   };
+
+   let Some(spec) = command.spec() else {
+      warn!("{} sent {command:?}, which has no spec", player.username);
+      return;
+   };
+   let role = player.role;
+   if role < spec.min_role {
+      let reply = format!("You may not use /{}.", spec.name);
+      send_server_msg(player_id, reply, &mut players).await;
+      return;
+   }
+   if spec.min_role > Role::Player {
+      moderation::audit(player, &command);
+   }
+
+   let replies = match command {
+      Command::PrivateMsg { to, msg } => {
+         send_private_msg(player_id, to, msg, &mut players).await;
+         return;
+      }
+      Command::Who => {
+         let viewer = &players[&player_id];
+         let around = players
+            .values()
+            .filter(|p| p.id != player_id && viewer.can_see(p))
+            .filter(|p| is_on_screen(viewer.location, p.location))
+            .map(|p| &p.username)
+            .sorted()
+            .join(", ");
+         match around.is_empty() {
+            true => vec!["Nobody is around.".to_string()],
+            false => vec![format!("Players around you: {around}.")],
+         }
+      }
+      Command::Online => {
+         let viewer = &players[&player_id];
+         let online = players
+            .values()
+            .filter(|p| viewer.can_see(p))
+            .map(|p| &p.username)
+            .sorted()
+            .collect_vec();
+         vec![format!(
+            "{} players online: {}.",
+            online.len(),
+            online.iter().join(", ")
+         )]
+      }
+      Command::Ignore(username) => {
+         if player.ignored.remove(&username) {
+            vec![format!("You no longer ignore {username}.")]
+         } else {
+            player.ignored.insert(username.clone());
+            vec![format!("You are now ignoring {username}.")]
+         }
+      }
+      Command::Help(None) => COMMANDS
+         .iter()
+         .filter(|spec| spec.min_role <= role)
+         .map(|spec| format!("{} - {}", spec.usage, spec.description))
+         .collect(),
+      Command::Help(Some(name)) => match find_command(&name) {
+         Some(spec) if spec.min_role <= role => {
+            vec![format!("{} - {}", spec.usage, spec.description)]
+         }
+         _ => vec![format!("There is no /{name} command.")],
+      },
+      Command::Mute { name, minutes } => {
+         let target_id = match outranked_target(&mut players, &name, role) {
+            Ok(target_id) => target_id,
+            Err(reply) => return send_server_msg(player_id, reply, &mut players).await,
+         };
+         let target = players.get_mut(&target_id).unwrap();
+         if minutes == 0 {
+            target.muted_until = None;
+            target
+               .send_tcp_msg(&TcpServerMsg::ServerMsg("You may talk again.".to_string()))
+               .await;
+            vec![format!("{name} may talk again.")]
+         } else {
+            let duration = Duration::from_secs(minutes as u64 * 60);
+            target.muted_until = Some(Instant::now() + duration);
+            let msg = format!("You have been muted for {minutes} minutes.");
+            target.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+            vec![format!("{name} is muted for {minutes} minutes.")]
+         }
+      }
+      Command::Goto(name) => {
+         let Some(target) = find_player(&mut players, &name) else {
+            return not_online(player_id, &name, &mut players).await;
+         };
+         let target_location = target.location;
+
+         let mmo_map = state.mmo_map.lock().await;
+         let destination =
+            free_tiles_around(target_location, &mmo_map, &player_locations(&players))
+               .into_iter()
+               .next();
+         drop(mmo_map);
+
+         match destination {
+            Some(location) => {
+               teleport(player_id, location, &mut players, state).await;
+               vec![format!("Teleported to {name}.")]
+            }
+            None => vec![format!("There is no room next to {name}.")],
+         }
+      }
+      Command::Teleport { x, y, z } => {
+         let location = (x, y, z.unwrap_or(player.location.2));
+         let is_ground = matches!(
+            state.mmo_map.lock().await.get(location),
+            Some(MapElement::Empty)
+         );
+         let is_closed_door = matches!(
+            state.game_objects.lock().await.0.get(&location),
+            Some(GameObject::Door { is_open: false, .. })
+         );
+         let is_free = is_ground
+            && !is_closed_door
+            && !movement::is_blocked(player_id, location, &players, state).await;
+         match is_free {
+            true => {
+               teleport(player_id, location, &mut players, state).await;
+               vec![format!("Teleported to {location:?}.")]
+            }
+            false => vec![format!("You cannot stand on {location:?}.")],
+         }
+      }
+      Command::Summon { monster, count } => match state.creatures.by_name(&monster) {
+         None => vec![format!("There is no monster called {monster}.")],
+         Some(creature) => {
+            let center = player.location;
+            let mut game_objects = state.game_objects.lock().await;
+            let mut mmo_map = state.mmo_map.lock().await;
+
+            let tiles = free_tiles_around(center, &mmo_map, &player_locations(&players));
+            let count = count.clamp(1, MAX_SUMMONS) as usize;
+            for &location in tiles.iter().take(count) {
+               place_creature(location, creature, &mut game_objects, &mut mmo_map);
+            }
+            vec![format!(
+               "Summoned {}x {}.",
+               count.min(tiles.len()),
+               creature.name
+            )]
+         }
+      },
+      Command::Kick(name) => match outranked_target(&mut players, &name, role) {
+         Err(reply) => vec![reply],
+         Ok(target) => {
+            kick(target, "You have been kicked.", &mut players, sc_tx).await;
+            vec![format!("{name} has been kicked.")]
+         }
+      },
+      Command::Ban(name) => {
+         let target_role = match players.values().find(|p| p.username == name) {
+            Some(target) => target.role,
+            None => state.moderation.lock().await.role_of(&name),
+         };
+         if target_role >= role {
+            vec![format!("You cannot ban {name}.")]
+         } else {
+            state.moderation.lock().await.ban(&name);
+            if let Some(target) = players.values().find(|p| p.username == name) {
+               kick(target.id, "You have been banned.", &mut players, sc_tx).await;
+            }
+            vec![format!("{name} has been banned.")]
+         }
+      }
+      Command::Unban(name) => match state.moderation.lock().await.unban(&name) {
+         true => vec![format!("{name} is no longer banned.")],
+         false => vec![format!("{name} is not banned.")],
+      },
+      Command::Broadcast(msg) => {
+         let msg = TcpServerMsg::ServerMsg(format!("{}: {msg}", player.username));
+         for p in players.values_mut() {
+            p.send_tcp_msg(&msg).await;
+         }
+         return;
+      }
+      Command::SetHp { name, hp } => {
+         let Some(target) = find_player(&mut players, &name) else {
+            return not_online(player_id, &name, &mut players).await;
+         };
+         if target.is_dead {
+            vec![format!("{name} is dead.")]
+         } else {
+            target.hp = hp.clamp(1, target.max_hp);
+            let msg = UdpServerMsg::PlayerHealthUpdate { hp: target.hp };
+            udp_socket.send_msg_and_log_(msg, target.udp_socket).await;
+            vec![format!(
+               "{name} now has {}/{} hp.",
+               target.hp, target.max_hp
+            )]
+         }
+      }
+      Command::SetLevel { name, level } => {
+         let Some(target) = find_player(&mut players, &name) else {
+            return not_online(player_id, &name, &mut players).await;
+         };
+         target.set_level(level).await;
+         vec![format!("{name} is now level {}.", target.level)]
+      }
+      Command::Ghost => {
+         player.is_ghost = !player.is_ghost;
+         match player.is_ghost {
+            true => vec!["You are now invisible.".to_string()],
+            false => vec!["You are visible again.".to_string()],
+         }
+      }
+      Command::SetRole {
+         name,
+         role: new_role,
+      } => {
+         if new_role >= role {
+            let reply = format!("You cannot make anyone a {new_role}.");
+            return send_server_msg(player_id, reply, &mut players).await;
+         }
+         let target_id = match outranked_target(&mut players, &name, role) {
+            Ok(target_id) => target_id,
+            Err(reply) => return send_server_msg(player_id, reply, &mut players).await,
+         };
+         state.moderation.lock().await.set_role(&name, new_role);
+         let target = players.get_mut(&target_id).unwrap();
+         target.role = new_role;
+         target.is_ghost &= new_role >= Role::GameMaster;
+         let msg = format!("You are now a {new_role}.");
+         target.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+         vec![format!("{name} is now a {new_role}.")]
+      }
+      Command::Party(action) => {
+         let mut parties = state.parties.lock().await;
+         party::handle_party_action(player_id, action, &mut players, &mut parties).await;
+         return;
+      }
+      Command::Guild(action) => {
+         let mut guilds = state.guilds.lock().await;
+         guild::handle_guild_action(player_id, action, &mut players, &mut guilds).await;
+         return;
+      }
+      Command::Bless => vec![death::bless(player).await],
+      Command::Loot => {
+         let mut corpses = state.corpses.lock().await;
+         vec![death::loot(player, &mut corpses).await]
+      }
+      Command::Deaths(name) => {
+         let name = name.unwrap_or_else(|| player.username.clone());
+         state.death_history.lock().await.describe(&name)
+      }
+   };
+
+   for reply in replies {
+      send_server_msg(player_id, reply, &mut players).await;
+   }
+}
+
+fn find_player<'a>(players: &'a mut HashMap<Uuid, Player>, name: &str) -> Option<&'a mut Player> {
+   players.values_mut().find(|p| p.username == name)
+}
+
+async fn not_online(player_id: Uuid, name: &str, players: &mut HashMap<Uuid, Player>) {
+   send_server_msg(player_id, format!("{name} is not online."), players).await;
+}
+
+/// Finds a player that `role` may act on, i.e. one with a lower role.
+fn outranked_target(
+   players: &mut HashMap<Uuid, Player>,
+   name: &str,
+   role: Role,
+) -> Result<Uuid, String> {
+   match find_player(players, name) {
+      None => Err(format!("{name} is not online.")),
+      Some(target) if target.role >= role => Err(format!("You cannot do that to {name}.")),
+      Some(target) => Ok(target.id),
+   }
+}
+
+fn player_locations(players: &HashMap<Uuid, Player>) -> Vec<Location> {
+   players.values().map(|p| p.location).collect()
+}
+
+pub async fn teleport(
+   player_id: Uuid,
+   location: Location,
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+) {
+   let Some(player) = players.get_mut(&player_id) else {
+      return;
+   };
+   info!("teleporting {} to {location:?}", player.username);
+   player.location = location;
+   player.send_tcp_msg(&TcpServerMsg::Teleport(location)).await;
+
+   let mut trades = state.trades.lock().await;
+   trade::cancel_trade_if_apart(player_id, players, &mut trades).await;
+}
+
+/// Tells the player why and then disconnects them like a logout would.
+async fn kick(
+   player_id: Uuid,
+   reason: &str,
+   players: &mut HashMap<Uuid, Player>,
+   sc_tx: &UnboundedSender<ServerChannel>,
+) {
+   send_server_msg(player_id, reason, players).await;
+   _ = sc_tx.send(ServerChannel {
+      id: player_id,
+      msg: Sc::Disconnect,
+   });
+}
@@ -0,0 +1,201 @@
+use crate::{Player, combat};
+use shared::{ConditionKind, ConditionSpec, network::tcp::TcpServerMsg};
+use std::time::{Duration, Instant};
+use thin_logger::log::debug;
+use tokio::net::UdpSocket;
+
+/// Poison piles up, but only this far.
+const MAX_POISON_TICKS: u32 = 20;
+
+#[derive(Debug, Clone)]
+pub struct Condition {
+   pub kind: ConditionKind,
+   pub ticks_left: u32,
+   pub amount: u32,
+   pub interval: Duration,
+   pub next_tick: Instant,
+}
+
+impl From<ConditionSpec> for Condition {
+   fn from(spec: ConditionSpec) -> Self {
+      let interval = Duration::from_millis(spec.interval_ms);
+      Condition {
+         kind: spec.kind,
+         ticks_left: spec.ticks,
+         amount: spec.amount,
+         interval,
+         next_tick: Instant::now() + interval,
+      }
+   }
+}
+
+async fn send_conditions(player: &mut Player) {
+   let kinds = player.condition_kinds();
+   player.send_tcp_msg(&TcpServerMsg::Conditions(kinds)).await;
+}
+
+/// Gives the player a condition. Conditions stack like this:
+/// - poison piles up: the ticks add up and the stronger damage stays,
+/// - haste and paralyze cancel each other out, the newer one wins,
+/// - anything else is replaced by a stronger one, or else lasts longer.
+pub async fn apply(player: &mut Player, spec: ConditionSpec) {
+   if player.is_dead || spec.ticks == 0 {
+      return;
+   }
+   debug!("{} gets {spec:?}", player.username);
+
+   let before = player.condition_kinds();
+   let opposite = match spec.kind {
+      ConditionKind::Haste => Some(ConditionKind::Paralyze),
+      ConditionKind::Paralyze => Some(ConditionKind::Haste),
+      _ => None,
+   };
+   player.conditions.retain(|c| Some(c.kind) != opposite);
+
+   let new = Condition::from(spec);
+   match player.conditions.iter_mut().find(|c| c.kind == spec.kind) {
+      None => player.conditions.push(new),
+      Some(existing) if spec.kind == ConditionKind::Poison => {
+         existing.ticks_left = (existing.ticks_left + new.ticks_left).min(MAX_POISON_TICKS);
+         existing.amount = existing.amount.max(new.amount);
+      }
+      Some(existing) if new.amount > existing.amount => *existing = new,
+      Some(existing) => existing.ticks_left = existing.ticks_left.max(new.ticks_left),
+   }
+
+   if player.condition_kinds() != before {
+      send_conditions(player).await;
+   }
+}
+
+/// Ends every condition, like death does.
+pub async fn clear(player: &mut Player) {
+   if !player.conditions.is_empty() {
+      player.conditions.clear();
+      send_conditions(player).await;
+   }
+}
+
+/// Runs the ticks that are due: damage over time hurts, regeneration heals,
+/// and whatever ran out goes away. Death ends every condition.
+pub async fn process_conditions(player: &mut Player, udp_socket: &UdpSocket) {
+   if player.conditions.is_empty() {
+      return;
+   }
+   let Some(player_udp) = player.udp_socket else {
+      return;
+   };
+   let before = player.condition_kinds();
+
+   let now = Instant::now();
+   let (mut damage, mut healing) = (vec![], 0);
+   for condition in player.conditions.iter_mut().filter(|c| c.next_tick <= now) {
+      condition.ticks_left -= 1;
+      condition.next_tick = now + condition.interval;
+      match condition.kind {
+         ConditionKind::Poison => damage.push(("poison", condition.amount)),
+         ConditionKind::Burning => damage.push(("fire", condition.amount)),
+         ConditionKind::Regeneration => healing += condition.amount,
+         ConditionKind::Haste | ConditionKind::Paralyze => {}
+      }
+   }
+   player.conditions.retain(|c| c.ticks_left > 0);
+
+   if healing > 0 && !player.is_dead && player.hp < player.max_hp {
+      player.hp = (player.hp + healing).min(player.max_hp);
+      player
+         .send_tcp_msg(&TcpServerMsg::Stats(player.stats()))
+         .await;
+   }
+   for (source, damage) in damage {
+      combat::handle_player_damage(player, damage, source, udp_socket, player_udp).await;
+   }
+   if player.is_dead {
+      player.conditions.clear();
+   }
+
+   if player.condition_kinds() != before {
+      send_conditions(player).await;
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::player::test_player;
+
+   fn spec(kind: ConditionKind, ticks: u32, amount: u32) -> ConditionSpec {
+      ConditionSpec {
+         kind,
+         ticks,
+         interval_ms: 1000,
+         amount,
+      }
+   }
+
+   fn ticks_and_amount(player: &Player, kind: ConditionKind) -> Option<(u32, u32)> {
+      player
+         .conditions
+         .iter()
+         .find(|c| c.kind == kind)
+         .map(|c| (c.ticks_left, c.amount))
+   }
+
+   #[tokio::test]
+   async fn test_poison_piles_up() {
+      let mut player = test_player("poisoned", (10, 10, 0)).await;
+      apply(&mut player, spec(ConditionKind::Poison, 5, 10)).await;
+      apply(&mut player, spec(ConditionKind::Poison, 8, 4)).await;
+      assert_eq!(
+         ticks_and_amount(&player, ConditionKind::Poison),
+         Some((13, 10))
+      );
+
+      apply(&mut player, spec(ConditionKind::Poison, 15, 4)).await;
+      assert_eq!(
+         ticks_and_amount(&player, ConditionKind::Poison),
+         Some((MAX_POISON_TICKS, 10))
+      );
+   }
+
+   #[tokio::test]
+   async fn test_haste_and_paralyze_cancel_out() {
+      let mut player = test_player("hasted", (10, 10, 0)).await;
+      apply(&mut player, spec(ConditionKind::Haste, 10, 0)).await;
+      apply(&mut player, spec(ConditionKind::Paralyze, 3, 0)).await;
+      assert_eq!(player.condition_kinds(), vec![ConditionKind::Paralyze]);
+
+      apply(&mut player, spec(ConditionKind::Haste, 2, 0)).await;
+      assert_eq!(player.condition_kinds(), vec![ConditionKind::Haste]);
+   }
+
+   #[tokio::test]
+   async fn test_stronger_replaces_weaker_lasts_longer() {
+      let mut player = test_player("burning", (10, 10, 0)).await;
+      apply(&mut player, spec(ConditionKind::Burning, 4, 5)).await;
+      apply(&mut player, spec(ConditionKind::Burning, 2, 8)).await;
+      assert_eq!(
+         ticks_and_amount(&player, ConditionKind::Burning),
+         Some((2, 8))
+      );
+
+      apply(&mut player, spec(ConditionKind::Burning, 6, 3)).await;
+      assert_eq!(
+         ticks_and_amount(&player, ConditionKind::Burning),
+         Some((6, 8))
+      );
+      apply(&mut player, spec(ConditionKind::Burning, 1, 3)).await;
+      assert_eq!(
+         ticks_and_amount(&player, ConditionKind::Burning),
+         Some((6, 8))
+      );
+   }
+
+   #[tokio::test]
+   async fn test_nothing_sticks_to_the_dead() {
+      let mut player = test_player("dead", (10, 10, 0)).await;
+      player.is_dead = true;
+      apply(&mut player, spec(ConditionKind::Poison, 5, 10)).await;
+      assert!(player.conditions.is_empty());
+   }
+}
@@ -0,0 +1,60 @@
+use anyhow::Result;
+use clap::Parser;
+use shared::{ServerConfig, load_toml};
+use std::{net::SocketAddr, path::PathBuf};
+
+const DEFAULT_CONFIG: &str = "server.toml";
+
+/// Overrides for the settings in the config file.
+#[derive(Debug, Parser)]
+#[command(about = "The game server")]
+struct Args {
+   /// TOML file with the settings. Missing settings keep their defaults, and
+   /// so does everything when there is no server.toml and no file was given.
+   #[arg(long, env = "MMO_SERVER_CONFIG")]
+   config: Option<PathBuf>,
+   #[arg(long, env = "MMO_SERVER_TCP_ADDR")]
+   tcp_addr: Option<SocketAddr>,
+   #[arg(long, env = "MMO_SERVER_UDP_ADDR")]
+   udp_addr: Option<SocketAddr>,
+   /// Milliseconds between game loop ticks.
+   #[arg(long, env = "MMO_SERVER_TICK_RATE_MS")]
+   tick_rate_ms: Option<u64>,
+   /// off, error, warn, info, debug or trace.
+   #[arg(long, env = "MMO_SERVER_LOG_LEVEL")]
+   log_level: Option<String>,
+   #[arg(long, env = "MMO_SERVER_MAP_PATH")]
+   map_path: Option<PathBuf>,
+   /// Whether players can attack each other.
+   #[arg(long, env = "MMO_SERVER_PVP")]
+   pvp: Option<bool>,
+}
+
+/// Resolves the settings from the command line, the environment and the
+/// config file, in that order, falling back to the defaults.
+pub fn load() -> Result<ServerConfig> {
+   let args = Args::parse();
+   let mut config: ServerConfig = load_toml(args.config.as_deref(), DEFAULT_CONFIG)?;
+
+   if let Some(tcp_addr) = args.tcp_addr {
+      config.tcp_addr = tcp_addr;
+   }
+   if let Some(udp_addr) = args.udp_addr {
+      config.udp_addr = udp_addr;
+   }
+   if let Some(tick_rate_ms) = args.tick_rate_ms {
+      config.tick_rate_ms = tick_rate_ms;
+   }
+   if let Some(log_level) = args.log_level {
+      config.log_level = log_level;
+   }
+   if let Some(map_path) = args.map_path {
+      config.map_path = map_path;
+   }
+   if let Some(pvp) = args.pvp {
+      config.pvp = pvp;
+   }
+
+   config.check()?;
+   Ok(config)
+}
@@ -0,0 +1,353 @@
+use crate::{Player, conditions, progress::SavedProgress, storage};
+use anyhow::Result;
+use itertools::Itertools;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use shared::{
+   GameObject, GameObjects, Inventory, Item, ItemKind, Location, is_adjacent,
+   network::tcp::TcpServerMsg,
+};
+use std::{
+   collections::{BTreeMap, HashMap},
+   time::{Duration, Instant},
+};
+use thin_logger::log::{error, info};
+
+const DEATHS_PATH: &str = "data/deaths.json";
+/// Deaths kept per character, older ones are forgotten.
+const MAX_DEATHS: usize = 10;
+/// Only those who did damage this recently share the kill.
+pub const KILLER_WINDOW: Duration = Duration::from_secs(60);
+const MAX_KILLERS: usize = 4;
+
+/// The share of their experience players lose when they die.
+const EXPERIENCE_LOSS: f64 = 0.1;
+/// The chance of every stack in the inventory to end up in the corpse.
+const ITEM_LOSS_CHANCE: f64 = 0.1;
+const MAX_BLESSINGS: u32 = 5;
+/// How much of the losses every blessing spares.
+const BLESSING_PROTECTION: f64 = 0.16;
+/// How long a corpse lies around before it rots away with what it holds.
+const CORPSE_DURATION: Duration = Duration::from_secs(300);
+
+fn blessing_price(level: u32) -> u32 {
+   20 * level
+}
+
+/// Damage taken by a player, remembered to name their killers.
+#[derive(Debug, Clone)]
+pub struct Hit {
+   pub source: String, // "an orc", a player name, "poison"...
+   pub damage: u32,
+   pub time: Instant,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Death {
+   pub time: String,
+   pub level: u32,
+   pub killers: Vec<String>,
+}
+
+impl Death {
+   /// Names the last hit first and then whoever did the most damage, out of
+   /// the hits taken lately.
+   pub fn new(level: u32, hits: &[Hit]) -> Death {
+      let recent: Vec<&Hit> = hits
+         .iter()
+         .filter(|hit| hit.time.elapsed() < KILLER_WINDOW)
+         .collect();
+      let last_hit = recent.last().map(|hit| hit.source.clone());
+
+      let mut damage_by_source: HashMap<&str, u32> = HashMap::new();
+      for hit in &recent {
+         *damage_by_source.entry(&hit.source).or_default() += hit.damage;
+      }
+      let by_damage = damage_by_source
+         .into_iter()
+         .sorted_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)))
+         .map(|(source, _)| source.to_string());
+
+      Death {
+         time: chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+         level,
+         killers: last_hit
+            .into_iter()
+            .chain(by_damage)
+            .unique()
+            .take(MAX_KILLERS)
+            .collect(),
+      }
+   }
+
+   /// E.g. "Killed at level 5 by an orc and Bob."
+   pub fn message(&self) -> String {
+      match self.killers.as_slice() {
+         [] => format!("Died at level {}.", self.level),
+         [killer] => format!("Killed at level {} by {killer}.", self.level),
+         [others @ .., last] => {
+            format!(
+               "Killed at level {} by {} and {last}.",
+               self.level,
+               others.join(", ")
+            )
+         }
+      }
+   }
+}
+
+/// The latest deaths of every character, saved to disk on every change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeathHistory(pub BTreeMap<String, Vec<Death>>); // username -> deaths, oldest first
+
+impl DeathHistory {
+   pub fn load() -> Result<DeathHistory> {
+      storage::load_json(DEATHS_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(DEATHS_PATH, self) {
+         error!("failed to save the death history: {e:#}");
+      }
+   }
+
+   fn record(&mut self, username: &str, death: Death) {
+      let deaths = self.0.entry(username.to_string()).or_default();
+      deaths.push(death);
+      if deaths.len() > MAX_DEATHS {
+         deaths.remove(0);
+      }
+      self.save();
+   }
+
+   /// The replies to `/deaths`, newest first.
+   pub fn describe(&self, username: &str) -> Vec<String> {
+      match self.0.get(username) {
+         None => vec![format!("{username} has never died.")],
+         Some(deaths) => deaths
+            .iter()
+            .rev()
+            .map(|death| format!("{} {}", death.time, death.message()))
+            .collect(),
+      }
+   }
+}
+
+#[derive(Debug)]
+struct Corpse {
+   items: Inventory,
+   rots_at: Instant,
+}
+
+/// What dead players dropped, by where they died.
+#[derive(Debug, Default)]
+pub struct Corpses(HashMap<Location, Corpse>);
+
+impl Corpses {
+   /// Lets the corpses that lay around for long enough rot away.
+   pub fn rot(&mut self) {
+      let now = Instant::now();
+      self.0.retain(|_, corpse| corpse.rots_at > now);
+   }
+
+   pub fn contains(&self, location: Location) -> bool {
+      self.0.contains_key(&location)
+   }
+
+   /// Adds the corpses to the objects players see, except where something
+   /// else lies, like a ladder or a door.
+   pub fn show_in(&self, objects: &mut GameObjects) {
+      for &location in self.0.keys() {
+         objects.0.entry(location).or_insert(GameObject::Corpse);
+      }
+   }
+}
+
+/// Records the death of a player that just died and takes its penalties:
+/// some experience and maybe some items, which are left in the corpse.
+/// Blessings soften the losses and are used up. Death also ends every
+/// condition, and the losses are saved right away so logging out does not
+/// undo them.
+pub async fn handle_death(
+   player: &mut Player,
+   corpses: &mut Corpses,
+   history: &mut DeathHistory,
+   progress: &mut SavedProgress,
+) {
+   let Some(death) = player.pending_death.take() else {
+      return;
+   };
+   history.record(&player.username, death);
+   conditions::clear(player).await;
+
+   let losses = 1.0 - player.blessings as f64 * BLESSING_PROTECTION;
+   player.blessings = 0;
+
+   let lost_experience = (player.experience as f64 * EXPERIENCE_LOSS * losses) as u64;
+   player.lose_experience(lost_experience).await;
+
+   let dropped: Vec<Item> = player
+      .inventory
+      .0
+      .iter()
+      .copied()
+      .filter(|_| rand::thread_rng().gen_bool(ITEM_LOSS_CHANCE * losses))
+      .collect();
+   info!(
+      "{} died, losing {lost_experience} experience and {dropped:?}",
+      player.username
+   );
+
+   let mut msg = format!("You lost {lost_experience} experience.");
+   if !dropped.is_empty() {
+      player.inventory.remove_all(&dropped);
+      let corpse = corpses.0.entry(player.location).or_insert_with(|| Corpse {
+         items: Inventory::default(),
+         rots_at: Instant::now(),
+      });
+      corpse.rots_at = Instant::now() + CORPSE_DURATION;
+      for item in &dropped {
+         corpse.items.add(*item);
+      }
+      msg += &format!(" You dropped {}.", dropped.iter().join(", "));
+      player
+         .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+         .await;
+   }
+   progress.record(player);
+   player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+}
+
+/// Buys the player another blessing with gold. Returns the reply.
+pub async fn bless(player: &mut Player) -> String {
+   if player.blessings >= MAX_BLESSINGS {
+      return "You already have every blessing.".to_string();
+   }
+
+   let price = Item::new(ItemKind::GoldCoin, blessing_price(player.level));
+   if !player.inventory.remove_all(&[price]) {
+      return format!("A blessing costs {price}.");
+   }
+   player.blessings += 1;
+   player
+      .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+      .await;
+
+   format!(
+      "You have been blessed for {price}. You now have {}/{MAX_BLESSINGS} blessings.",
+      player.blessings
+   )
+}
+
+/// Moves what the corpses next to the player hold into their inventory.
+/// Returns the reply.
+pub async fn loot(player: &mut Player, corpses: &mut Corpses) -> String {
+   if player.is_dead {
+      return "You are dead.".to_string();
+   }
+
+   let nearby: Vec<Location> = corpses
+      .0
+      .keys()
+      .filter(|&&location| is_adjacent(player.location, location))
+      .copied()
+      .collect();
+   let looted: Vec<Item> = nearby
+      .iter()
+      .filter_map(|location| corpses.0.remove(location))
+      .flat_map(|corpse| corpse.items.0)
+      .collect();
+   if looted.is_empty() {
+      return "There is nothing to loot here.".to_string();
+   }
+
+   for item in &looted {
+      player.inventory.add(*item);
+   }
+   player
+      .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+      .await;
+
+   format!("You looted {}.", looted.iter().join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   fn corpse(rots_at: Instant) -> Corpse {
+      Corpse {
+         items: Inventory::default(),
+         rots_at,
+      }
+   }
+
+   fn hit(source: &str, damage: u32, ago: Duration) -> Hit {
+      Hit {
+         source: source.to_string(),
+         damage,
+         time: Instant::now() - ago,
+      }
+   }
+
+   #[test]
+   fn test_death_killers() {
+      let hits = [
+         hit("Bob", 50, KILLER_WINDOW * 2),
+         hit("an orc", 10, Duration::ZERO),
+         hit("Alice", 30, Duration::ZERO),
+         hit("an orc", 15, Duration::ZERO),
+         hit("poison", 20, Duration::ZERO),
+         hit("a wolf", 5, Duration::ZERO),
+         hit("Carol", 3, Duration::ZERO),
+      ];
+      let death = Death::new(7, &hits);
+      // the last hit first, then the most damage, and Bob hit too long ago
+      assert_eq!(death.killers, ["Carol", "Alice", "an orc", "poison"]);
+      assert_eq!(death.level, 7);
+
+      assert!(Death::new(7, &hits[..1]).killers.is_empty());
+   }
+
+   #[test]
+   fn test_death_message() {
+      let death = |killers: &[&str]| Death {
+         time: String::new(),
+         level: 5,
+         killers: killers.iter().map(|k| k.to_string()).collect(),
+      };
+      assert_eq!(death(&[]).message(), "Died at level 5.");
+      assert_eq!(death(&["an orc"]).message(), "Killed at level 5 by an orc.");
+      assert_eq!(
+         death(&["an orc", "Bob"]).message(),
+         "Killed at level 5 by an orc and Bob."
+      );
+      assert_eq!(
+         death(&["an orc", "Bob", "poison"]).message(),
+         "Killed at level 5 by an orc, Bob and poison."
+      );
+   }
+
+   #[test]
+   fn test_corpses() {
+      let now = Instant::now();
+      let mut corpses = Corpses(HashMap::from([
+         ((1, 1, 0), corpse(now + CORPSE_DURATION)),
+         ((2, 1, 0), corpse(now + CORPSE_DURATION)),
+         ((3, 1, 0), corpse(now)),
+      ]));
+      corpses.rot();
+      assert!(!corpses.contains((3, 1, 0)));
+
+      let ladder = GameObject::Ladder {
+         id: 83,
+         tileset_location: 1,
+         target_z: 1,
+      };
+      let mut objects = GameObjects(HashMap::from([((2, 1, 0), ladder)]));
+      corpses.show_in(&mut objects);
+      assert_eq!(objects.0[&(1, 1, 0)], GameObject::Corpse);
+      assert_eq!(objects.0[&(2, 1, 0)], ladder);
+      assert_eq!(objects.0.len(), 2);
+   }
+}
@@ -0,0 +1,399 @@
+use crate::{
+   Player,
+   chat::{reject_if_muted, send_server_msg},
+   storage,
+};
+use anyhow::Result;
+use futures::future::join_all;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use shared::{
+   ChatChannel, GuildAction, GuildRank, is_valid_guild_name, network::tcp::TcpServerMsg,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use thin_logger::log::{error, info};
+use uuid::Uuid;
+
+const GUILDS_PATH: &str = "data/guilds.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Guild {
+   pub name: String,
+   pub members: BTreeMap<String, GuildRank>, // username -> rank
+   /// Invitations are not worth keeping across restarts.
+   #[serde(skip)]
+   pub invited: HashSet<String>,
+}
+
+impl Guild {
+   fn rank_of(&self, username: &str) -> Option<GuildRank> {
+      self.members.get(username).copied()
+   }
+}
+
+/// Every guild, saved to disk on every change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Guilds(pub Vec<Guild>);
+
+impl Guilds {
+   pub fn load() -> Result<Guilds> {
+      storage::load_json(GUILDS_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(GUILDS_PATH, self) {
+         error!("failed to save guilds: {e:#}");
+      }
+   }
+
+   pub fn of(&self, username: &str) -> Option<&Guild> {
+      self.0.iter().find(|g| g.members.contains_key(username))
+   }
+
+   fn index_of(&self, username: &str) -> Option<usize> {
+      self.0.iter().position(|g| g.members.contains_key(username))
+   }
+
+   fn by_name(&self, name: &str) -> Option<usize> {
+      self
+         .0
+         .iter()
+         .position(|g| g.name.eq_ignore_ascii_case(name))
+   }
+}
+
+/// Updates the guild shown under the player's name, if they are online.
+async fn set_guild(username: &str, guild: Option<String>, players: &mut HashMap<Uuid, Player>) {
+   if let Some(p) = players.values_mut().find(|p| p.username == username) {
+      p.guild = guild.clone();
+      p.send_tcp_msg(&TcpServerMsg::Guild(guild)).await;
+   }
+}
+
+async fn send_to_guild(guild: &Guild, msg: String, players: &mut HashMap<Uuid, Player>) {
+   let msg = TcpServerMsg::ServerMsg(msg);
+   let futures = players
+      .values_mut()
+      .filter(|p| guild.members.contains_key(&p.username))
+      .map(|p| p.send_tcp_msg(&msg));
+   join_all(futures).await;
+}
+
+pub async fn handle_login(player_id: Uuid, players: &mut HashMap<Uuid, Player>, guilds: &Guilds) {
+   let Some(username) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+   let guild = guilds.of(&username).map(|g| g.name.clone());
+   if guild.is_some() {
+      set_guild(&username, guild, players).await;
+   }
+}
+
+pub async fn handle_guild_action(
+   player_id: Uuid,
+   action: GuildAction,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) {
+   let Some(username) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+
+   let result = match action {
+      GuildAction::Info => info(&username, players, guilds),
+      GuildAction::Create(name) => create(&username, name, players, guilds).await,
+      GuildAction::Invite(name) => invite(&username, &name, players, guilds).await,
+      GuildAction::Join(name) => join(&username, &name, players, guilds).await,
+      GuildAction::Leave => leave(&username, players, guilds).await,
+      GuildAction::Kick(name) => kick(&username, &name, players, guilds).await,
+      GuildAction::Promote(name) => promote(&username, &name, players, guilds).await,
+      GuildAction::Demote(name) => demote(&username, &name, players, guilds).await,
+   };
+
+   let replies = match result {
+      Ok(replies) => replies,
+      Err(reply) => vec![reply],
+   };
+   for reply in replies {
+      send_server_msg(player_id, reply, players).await;
+   }
+}
+
+type Replies = Result<Vec<String>, String>;
+
+fn info(username: &str, players: &HashMap<Uuid, Player>, guilds: &Guilds) -> Replies {
+   let Some(guild) = guilds.of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+
+   let members = guild
+      .members
+      .iter()
+      .sorted_by_key(|(_, rank)| std::cmp::Reverse(**rank))
+      .map(|(name, rank)| {
+         let online = players.values().any(|p| p.username == *name);
+         match online {
+            true => format!("{name} - {rank} (online)"),
+            false => format!("{name} - {rank}"),
+         }
+      });
+
+   Ok(std::iter::once(format!("{}:", guild.name))
+      .chain(members)
+      .collect())
+}
+
+async fn create(
+   username: &str,
+   name: String,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   if guilds.of(username).is_some() {
+      return Err("Leave your guild first.".to_string());
+   }
+   if !is_valid_guild_name(&name) {
+      return Err("Guild names are 3 to 20 letters and spaces.".to_string());
+   }
+   if guilds.by_name(&name).is_some() {
+      return Err(format!("There already is a guild called {name}."));
+   }
+
+   info!("{username} founds {name}");
+   guilds.0.push(Guild {
+      name: name.clone(),
+      members: BTreeMap::from([(username.to_string(), GuildRank::Leader)]),
+      invited: HashSet::new(),
+   });
+   guilds.save();
+
+   set_guild(username, Some(name.clone()), players).await;
+   Ok(vec![format!("You founded {name}.")])
+}
+
+async fn invite(
+   username: &str,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   let Some(idx) = guilds.index_of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+   if guilds.0[idx].rank_of(username) < Some(GuildRank::ViceLeader) {
+      return Err("Only leaders and vice-leaders can invite players.".to_string());
+   }
+   let Some(target) = players.values().find(|p| p.username == name).map(|p| p.id) else {
+      return Err(format!("{name} is not online."));
+   };
+   if guilds.of(name).is_some() {
+      return Err(format!("{name} is already in a guild."));
+   }
+
+   let guild = &mut guilds.0[idx];
+   guild.invited.insert(name.to_string());
+   let msg = format!(
+      "{username} invited you to {0}. Type /guild join \"{0}\" to accept.",
+      guild.name
+   );
+   send_server_msg(target, msg, players).await;
+   Ok(vec![format!("You invited {name} to {}.", guild.name)])
+}
+
+async fn join(
+   username: &str,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   if guilds.of(username).is_some() {
+      return Err("Leave your guild first.".to_string());
+   }
+   let Some(idx) = guilds.by_name(name) else {
+      return Err(format!("There is no guild called {name}."));
+   };
+
+   let guild = &mut guilds.0[idx];
+   if !guild.invited.remove(username) {
+      return Err(format!("You have not been invited to {}.", guild.name));
+   }
+   guild
+      .members
+      .insert(username.to_string(), GuildRank::Member);
+   let guild_name = guild.name.clone();
+   guilds.save();
+
+   send_to_guild(
+      &guilds.0[idx],
+      format!("{username} has joined the guild."),
+      players,
+   )
+   .await;
+   set_guild(username, Some(guild_name), players).await;
+   Ok(vec![])
+}
+
+/// Removes a member. When the leader goes, the highest ranked member left
+/// takes over, and a guild with nobody left is disbanded.
+async fn remove_member(
+   idx: usize,
+   username: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) {
+   let guild = &mut guilds.0[idx];
+   let rank = guild.members.remove(username);
+   set_guild(username, None, players).await;
+
+   if guild.members.is_empty() {
+      info!("{} is disbanded", guild.name);
+      guilds.0.remove(idx);
+      guilds.save();
+      return;
+   }
+
+   if rank == Some(GuildRank::Leader)
+      && let Some((successor, _)) = guild.members.iter().max_by_key(|(_, rank)| **rank)
+   {
+      let successor = successor.clone();
+      guild.members.insert(successor.clone(), GuildRank::Leader);
+      send_to_guild(guild, format!("{successor} now leads the guild."), players).await;
+   }
+   guilds.save();
+}
+
+async fn leave(
+   username: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   let Some(idx) = guilds.index_of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+   let guild_name = guilds.0[idx].name.clone();
+
+   remove_member(idx, username, players, guilds).await;
+   if let Some(guild) = guilds.0.get(idx).filter(|g| g.name == guild_name) {
+      send_to_guild(guild, format!("{username} has left the guild."), players).await;
+   }
+   Ok(vec![format!("You left {guild_name}.")])
+}
+
+async fn kick(
+   username: &str,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   let Some(idx) = guilds.index_of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+   let guild = &guilds.0[idx];
+   let rank = guild.rank_of(username);
+   let Some(target_rank) = guild.rank_of(name) else {
+      return Err(format!("{name} is not in your guild."));
+   };
+   if rank < Some(GuildRank::ViceLeader) || Some(target_rank) >= rank {
+      return Err(format!("You cannot kick {name}."));
+   }
+
+   remove_member(idx, name, players, guilds).await;
+   if let Some(target) = players.values().find(|p| p.username == name).map(|p| p.id) {
+      send_server_msg(target, "You have been kicked from your guild.", players).await;
+   }
+   send_to_guild(&guilds.0[idx], format!("{name} has been kicked."), players).await;
+   Ok(vec![])
+}
+
+async fn promote(
+   username: &str,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   let Some(idx) = guilds.index_of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+   let guild = &mut guilds.0[idx];
+   if guild.rank_of(username) != Some(GuildRank::Leader) {
+      return Err("Only the leader can promote members.".to_string());
+   }
+
+   let msg = match guild.rank_of(name) {
+      None => return Err(format!("{name} is not in your guild.")),
+      Some(GuildRank::Leader) => return Err("You already lead the guild.".to_string()),
+      Some(GuildRank::Member) => {
+         guild
+            .members
+            .insert(name.to_string(), GuildRank::ViceLeader);
+         format!("{name} is now a Vice-Leader.")
+      }
+      // promoting a vice-leader hands over the guild
+      Some(GuildRank::ViceLeader) => {
+         guild.members.insert(name.to_string(), GuildRank::Leader);
+         guild
+            .members
+            .insert(username.to_string(), GuildRank::ViceLeader);
+         format!("{name} now leads the guild.")
+      }
+   };
+   guilds.save();
+
+   send_to_guild(&guilds.0[idx], msg, players).await;
+   Ok(vec![])
+}
+
+async fn demote(
+   username: &str,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &mut Guilds,
+) -> Replies {
+   let Some(idx) = guilds.index_of(username) else {
+      return Err("You are not in a guild.".to_string());
+   };
+   let guild = &mut guilds.0[idx];
+   if guild.rank_of(username) != Some(GuildRank::Leader) {
+      return Err("Only the leader can demote members.".to_string());
+   }
+   if guild.rank_of(name) != Some(GuildRank::ViceLeader) {
+      return Err(format!("{name} is not a Vice-Leader."));
+   }
+
+   guild.members.insert(name.to_string(), GuildRank::Member);
+   guilds.save();
+
+   send_to_guild(&guilds.0[idx], format!("{name} is now a Member."), players).await;
+   Ok(vec![])
+}
+
+pub async fn handle_guild_msg(
+   player_id: Uuid,
+   msg: String,
+   players: &mut HashMap<Uuid, Player>,
+   guilds: &Guilds,
+) {
+   let Some(username) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+   let Some(guild) = guilds.of(&username) else {
+      send_server_msg(player_id, "You are not in a guild.", players).await;
+      return;
+   };
+
+   if reject_if_muted(player_id, players).await {
+      return;
+   }
+
+   let chat_msg = TcpServerMsg::ChatMsg {
+      username,
+      msg,
+      channel: ChatChannel::Guild,
+   };
+
+   let futures = players
+      .values_mut()
+      .filter(|p| p.id != player_id && guild.members.contains_key(&p.username))
+      .map(|p| p.send_tcp_msg(&chat_msg));
+   join_all(futures).await;
+}
@@ -0,0 +1,133 @@
+use crate::{Player, ServerState, commands, movement};
+use shared::{
+   ConditionKind, GameObject, Location, is_adjacent, is_on_screen, network::tcp::TcpServerMsg,
+};
+use std::collections::HashMap;
+use thin_logger::log::debug;
+use uuid::Uuid;
+
+/// Tells the player what is on a tile they can see.
+pub async fn handle_look(
+   player_id: Uuid,
+   location: Location,
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   let description = match is_on_screen(player.location, location) {
+      true => describe(player, location, players, state).await,
+      false => "You cannot see that far.".to_string(),
+   };
+   send_status(player_id, description, players).await;
+}
+
+async fn describe(
+   looker: &Player,
+   location: Location,
+   players: &HashMap<Uuid, Player>,
+   state: &ServerState,
+) -> String {
+   if location == looker.location {
+      return "You see yourself.".to_string();
+   }
+   let other = players
+      .values()
+      .find(|p| p.location == location && !p.is_dead && looker.can_see(p));
+   if let Some(other) = other {
+      return match &other.guild {
+         Some(guild) => format!(
+            "You see {} (level {}), a member of {guild}.",
+            other.username, other.level
+         ),
+         None => format!("You see {} (level {}).", other.username, other.level),
+      };
+   }
+
+   let object = state.game_objects.lock().await.0.get(&location).copied();
+   let object = match object {
+      None if state.corpses.lock().await.contains(location) => Some(GameObject::Corpse),
+      object => object,
+   };
+   match object {
+      Some(object @ GameObject::Creature { .. }) => match state.creatures.of(&object) {
+         Some(creature) => format!("You see {}.", creature.with_article()),
+         None => "You see a creature.".to_string(),
+      },
+      Some(GameObject::Ladder { target_z, .. }) if target_z > location.2 => {
+         "You see a ladder going up.".to_string()
+      }
+      Some(GameObject::Ladder { .. }) => "You see a ladder going down.".to_string(),
+      Some(GameObject::Door { is_open: true, .. }) => "You see an open door.".to_string(),
+      Some(GameObject::Door { is_open: false, .. }) => "You see a closed door.".to_string(),
+      Some(GameObject::FlowerPot { .. }) => "You see a flower pot.".to_string(),
+      Some(GameObject::Corpse) => "You see a corpse.".to_string(),
+      None => match state.fields.condition_at(location).map(|spec| spec.kind) {
+         Some(ConditionKind::Burning) => "You see a fire field.".to_string(),
+         Some(_) => "You see a poison field.".to_string(),
+         None => "You see the ground.".to_string(),
+      },
+   }
+}
+
+/// Uses what is on a tile next to the player: climbs ladders, opens and
+/// closes doors.
+pub async fn handle_use(
+   player_id: Uuid,
+   location: Location,
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   if player.is_dead {
+      return;
+   }
+   if !is_adjacent(player.location, location) {
+      send_status(player_id, "You are too far away.".to_string(), players).await;
+      return;
+   }
+
+   let object = state.game_objects.lock().await.0.get(&location).copied();
+   match object {
+      Some(GameObject::Ladder { target_z, .. }) => {
+         let destination = (location.0, location.1, target_z);
+         if movement::is_blocked(player_id, destination, players, state).await {
+            send_status(player_id, "There is no room.".to_string(), players).await;
+            return;
+         }
+         debug!("{} climbs to {destination:?}", player.username);
+         commands::teleport(player_id, destination, players, state).await;
+      }
+      Some(GameObject::Door { is_open, .. }) => {
+         // nobody gets shut in the doorway
+         if is_open
+            && players
+               .values()
+               .any(|p| p.location == location && !p.is_dead)
+         {
+            send_status(
+               player_id,
+               "There is someone in the way.".to_string(),
+               players,
+            )
+            .await;
+            return;
+         }
+         if let Some(GameObject::Door { is_open, .. }) =
+            state.game_objects.lock().await.0.get_mut(&location)
+         {
+            *is_open = !*is_open;
+         }
+      }
+      _ => send_status(player_id, "You cannot use this.".to_string(), players).await,
+   }
+}
+
+async fn send_status(player_id: Uuid, status: String, players: &mut HashMap<Uuid, Player>) {
+   if let Some(player) = players.get_mut(&player_id) {
+      player.send_tcp_msg(&TcpServerMsg::Status(status)).await;
+   }
+}
@@ -1,16 +1,80 @@
+pub mod account;
+pub mod chat;
+pub mod combat;
+pub mod commands;
+pub mod conditions;
+pub mod config;
+pub mod death;
+pub mod guild;
+pub mod interaction;
+pub mod moderation;
+pub mod movement;
+pub mod party;
 pub mod player;
+pub mod progress;
+pub mod pvp;
+pub mod regeneration;
 pub mod spawn_manager;
+pub mod spells;
+pub mod storage;
 pub mod tasks;
+pub mod temple;
+pub mod trade;
+pub mod vip;
 
+use account::Accounts;
+use chat::Channels;
+use death::{Corpses, DeathHistory};
+use guild::Guilds;
+use moderation::Moderation;
+use party::Parties;
 pub use player::*;
-use shared::{GameObject, GameObjects, Location, constants::*};
+use progress::SavedProgress;
+use shared::{
+   AttackTarget, Command, Creatures, Fields, GameObject, GameObjects, Item, ItemKind, Location,
+   ProtectionZones, ServerConfig, Temples, constants::*,
+};
+use spells::Spells;
 use std::{
    collections::{HashMap, HashSet, VecDeque},
+   net::SocketAddr,
    ops::{Index, IndexMut},
+   sync::Arc,
    time::Instant,
 };
+use tasks::Players;
+use temple::HomeTowns;
 use thin_logger::log::debug;
+use tokio::sync::Mutex;
+use trade::Trades;
 use uuid::Uuid;
+use vip::VipLists;
+
+/// Handles to the state shared between tasks. Cloning it only clones the handles.
+#[derive(Clone)]
+pub struct ServerState {
+   pub players: Players,
+   pub address_mapping: Arc<Mutex<HashMap<SocketAddr, Uuid>>>, // tcp or udp addr -> player_id
+   pub game_objects: Arc<Mutex<GameObjects>>,
+   pub mmo_map: Arc<Mutex<MmoMap>>,
+   pub trades: Arc<Mutex<Trades>>,
+   pub channels: Arc<Mutex<Channels>>,
+   pub moderation: Arc<Mutex<Moderation>>,
+   pub vip: Arc<Mutex<VipLists>>,
+   pub parties: Arc<Mutex<Parties>>,
+   pub guilds: Arc<Mutex<Guilds>>,
+   pub protection_zones: Arc<ProtectionZones>,
+   pub fields: Arc<Fields>,
+   pub spells: Arc<Spells>,
+   pub creatures: Arc<Creatures>,
+   pub temples: Arc<Temples>,
+   pub home_towns: Arc<Mutex<HomeTowns>>,
+   pub corpses: Arc<Mutex<Corpses>>,
+   pub death_history: Arc<Mutex<DeathHistory>>,
+   pub progress: Arc<Mutex<SavedProgress>>,
+   pub accounts: Arc<Mutex<Accounts>>,
+   pub config: Arc<ServerConfig>,
+}
 
 pub struct ServerChannel {
    pub id: Uuid,
@@ -20,16 +84,39 @@ pub struct ServerChannel {
 pub enum Sc {
    PlayerMove {
       client_request_id: u32,
-      location: Location,
+      step: (i32, i32),
    },
+   /// Sent once a new player is in the players map.
+   Login,
    Disconnect,
    MoveObject {
       from: Location,
       to: Location,
    },
    ChatMsg(String), // message
-   Ping(u32),       // ping_id
+   ChannelMsg {
+      channel: String,
+      msg: String,
+   },
+   JoinChannel(String),
+   LeaveChannel(String),
+   Command(Command),
+   Ping(u32), // ping_id
    Respawn,
+   TradeOffer {
+      to: String,
+      items: Vec<Item>,
+   },
+   TradeAccept,
+   TradeCancel,
+   VipAdd(String),
+   VipRemove(String),
+   Attack(Option<AttackTarget>),
+   PartyMsg(String),
+   GuildMsg(String),
+   UseItem(ItemKind),
+   Look(Location),
+   Use(Location),
 }
 
 #[derive(Debug, Default)]
@@ -51,7 +138,7 @@ impl MmoMap {
             } => MapElement::Object(Object {
                id: (id, tileset_location),
             }),
-            GameObject::Orc {
+            GameObject::Creature {
                id,
                tileset_location,
                ..
@@ -67,6 +154,13 @@ impl MmoMap {
             } => MapElement::Object(Object {
                id: (id, tileset_location),
             }),
+            // monsters do not go through doors, open or not
+            GameObject::Door {
+               tileset_location, ..
+            } => MapElement::Object(Object {
+               id: (game_object.id(), tileset_location),
+            }),
+            GameObject::Corpse => MapElement::Empty,
          };
          map[location] = map_element;
       }
@@ -80,6 +174,11 @@ impl MmoMap {
          .and_then(|row| row.get(x as usize))
    }
 
+   /// Objects stop missiles, and so does the edge of the map.
+   pub fn blocks_missiles(&self, location: Location) -> bool {
+      matches!(self.get(location), None | Some(MapElement::Object(_)))
+   }
+
    pub fn move_monster(&mut self, from: Location, to: Location) -> Option<()> {
       if from == to {
          debug!("Cannot move monster to the same location");
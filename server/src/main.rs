@@ -1,11 +1,23 @@
 use anyhow::Result;
 use server::{
-   MmoMap, Player, ServerChannel,
+   MmoMap, Player, ServerChannel, ServerState,
+   account::Accounts,
+   chat::Channels,
+   config,
+   death::{Corpses, DeathHistory},
+   guild::Guilds,
+   moderation::Moderation,
+   party::Parties,
+   progress::SavedProgress,
+   spells::Spells,
    tasks::{game_loop_task, sc_rx_task, tcp_listener_task, udp_recv_task},
+   temple::HomeTowns,
+   trade::Trades,
+   vip::VipLists,
 };
-use shared::{GameObjects, constants::*};
+use shared::{Creatures, Fields, GameObjects, ProtectionZones, Temples, load_map, to_toml};
 use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use thin_logger::log::{LevelFilter, info};
+use thin_logger::log::info;
 use tokio::{
    net::{TcpListener, UdpSocket},
    sync::{Mutex, mpsc},
@@ -14,13 +26,15 @@ use uuid::Uuid;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-   thin_logger::build(LevelFilter::Info.into()).init();
+   let config = Arc::new(config::load()?);
+   thin_logger::build(config.log_level().into()).init();
+   eprintln!("config:\n{}", to_toml(&*config));
 
-   let udp_socket = Arc::new(UdpSocket::bind(SERVER_UDP_ADDR).await?);
-   info!("Server listening on UDP: {}", SERVER_UDP_ADDR);
+   let udp_socket = Arc::new(UdpSocket::bind(config.udp_addr).await?);
+   info!("Server listening on UDP: {}", config.udp_addr);
 
-   let tcp_listener = TcpListener::bind(SERVER_TCP_ADDR).await?;
-   info!("Server listening on TCP: {}", SERVER_TCP_ADDR);
+   let tcp_listener = TcpListener::bind(config.tcp_addr).await?;
+   info!("Server listening on TCP: {}", config.tcp_addr);
 
    // let _player = true;
 
@@ -31,42 +45,73 @@ async fn main() -> Result<()> {
    let players = HashMap::<Uuid, Player>::new();
    let players = Arc::new(Mutex::new(players));
 
-   let game_objects = GameObjects::new();
-   let game_objects_2 = GameObjects::new();
+   let trades = Arc::new(Mutex::new(Trades::default()));
+   let channels = Arc::new(Mutex::new(Channels::default()));
+   let moderation = Arc::new(Mutex::new(Moderation::load()?));
+   let accounts = Arc::new(Mutex::new(Accounts::load()?));
+   let vip = Arc::new(Mutex::new(VipLists::load()?));
+   let parties = Arc::new(Mutex::new(Parties::default()));
+   let guilds = Arc::new(Mutex::new(Guilds::load()?));
+   let map = load_map(&config.map_path)?;
+   let protection_zones = Arc::new(ProtectionZones::from_map(&map));
+   let fields = Arc::new(Fields::from_map(&map));
+   let spells = Arc::new(Spells::load()?);
+   let temples = Arc::new(Temples::from_map(&map));
+   let home_towns = Arc::new(Mutex::new(HomeTowns::load()?));
+   let corpses = Arc::new(Mutex::new(Corpses::default()));
+   let death_history = Arc::new(Mutex::new(DeathHistory::load()?));
+   let progress = Arc::new(Mutex::new(SavedProgress::load()?));
+
+   let creatures = Arc::new(Creatures::load()?);
+   let game_objects = GameObjects::new(&map, &creatures);
+   let game_objects_2 = game_objects.clone();
    let game_objects = Arc::new(Mutex::new(game_objects));
 
    // mmo map setup
    let mmo_map = MmoMap::from_game_objects(game_objects_2);
    let mmo_map = Arc::new(Mutex::new(mmo_map));
 
+   let state = ServerState {
+      players: players.clone(),
+      address_mapping: address_mapping.clone(),
+      game_objects,
+      mmo_map,
+      trades,
+      channels,
+      moderation,
+      vip,
+      parties,
+      guilds,
+      protection_zones,
+      fields,
+      spells,
+      creatures,
+      temples,
+      home_towns,
+      corpses,
+      death_history,
+      progress,
+      accounts,
+      config,
+   };
+
    let (sc_tx, sc_rx) = mpsc::unbounded_channel::<ServerChannel>();
 
-   let task1_handle = tcp_listener_task(
-      tcp_listener,
-      players.clone(),
-      address_mapping.clone(),
-      sc_tx.clone(),
-      game_objects.clone(),
-   );
+   let task1_handle = tcp_listener_task(tcp_listener, sc_tx.clone(), state.clone());
 
    // Game loop task
-   let task2_handle = game_loop_task(
-      udp_socket.clone(),
-      players.clone(),
-      game_objects.clone(),
-      mmo_map.clone(),
-   );
+   let task2_handle = game_loop_task(udp_socket.clone(), state.clone());
 
    // Receives UDP msgs from clients.
    let task3_handle = udp_recv_task(
       udp_socket.clone(),
       sc_tx.clone(),
-      address_mapping.clone(),
+      address_mapping,
       players.clone(),
    );
 
    // Handler/processor of server channel messages
-   let task4_handle = sc_rx_task(sc_rx, udp_socket, address_mapping, players, game_objects);
+   let task4_handle = sc_rx_task(sc_rx, sc_tx, udp_socket, state);
 
    // not a fan of how this looks but it works ok.
    // it bubbles up to main on the first error and
@@ -0,0 +1,71 @@
+use crate::{Player, storage};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shared::{Command, Role};
+use std::collections::{HashMap, HashSet};
+use thin_logger::log::error;
+
+const MODERATION_PATH: &str = "data/moderation.json";
+const AUDIT_LOG_PATH: &str = "data/gm_audit.log";
+
+/// Roles and bans, saved to disk on every change. Nobody starts out as an
+/// admin: the first one is added to `data/moderation.json` by hand, e.g.
+/// `{ "roles": { "Alice": "Admin" }, "bans": [] }`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Moderation {
+   pub roles: HashMap<String, Role>, // username -> role, plain players are left out
+   pub bans: HashSet<String>,        // usernames
+}
+
+impl Moderation {
+   pub fn load() -> Result<Moderation> {
+      storage::load_json(MODERATION_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(MODERATION_PATH, self) {
+         error!("failed to save moderation data: {e:#}");
+      }
+   }
+
+   pub fn role_of(&self, username: &str) -> Role {
+      self.roles.get(username).copied().unwrap_or_default()
+   }
+
+   pub fn set_role(&mut self, username: &str, role: Role) {
+      match role {
+         Role::Player => self.roles.remove(username),
+         _ => self.roles.insert(username.to_string(), role),
+      };
+      self.save();
+   }
+
+   pub fn is_banned(&self, username: &str) -> bool {
+      self.bans.contains(username)
+   }
+
+   pub fn ban(&mut self, username: &str) -> bool {
+      let banned = self.bans.insert(username.to_string());
+      self.save();
+      banned
+   }
+
+   pub fn unban(&mut self, username: &str) -> bool {
+      let unbanned = self.bans.remove(username);
+      self.save();
+      unbanned
+   }
+}
+
+/// Appends a privileged command to the audit log.
+pub fn audit(gm: &Player, command: &Command) {
+   let line = format!(
+      "{} [{}] {}: {command:?}",
+      chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+      gm.role,
+      gm.username
+   );
+   if let Err(e) = storage::append_line(AUDIT_LOG_PATH, &line) {
+      error!("failed to write to the audit log: {e:#}");
+   }
+}
@@ -0,0 +1,124 @@
+use crate::{MapElement, Player, ServerState, conditions, temple};
+use shared::{Direction, Location, constants::BASE_MOVE_DELAY, move_delay_factor};
+use std::{
+   collections::HashMap,
+   time::{Duration, Instant},
+};
+use thin_logger::log::{debug, trace};
+use uuid::Uuid;
+
+/// How much sooner than its pace a step may come in, for the network
+/// bunching up steps the client sent in time.
+const STEP_LENIENCY: Duration = Duration::from_millis(50);
+
+/// Takes the step the player asked for if it is a valid one and comes no
+/// sooner than the player can walk. Steps are acknowledged whether they are
+/// taken or not, so the client knows which of the steps it predicted to roll
+/// back.
+pub async fn handle_step(
+   player_id: Uuid,
+   client_request_id: u32,
+   step: (i32, i32),
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   if client_request_id <= player.client_request_id {
+      trace!("received outdated player move from {}", player.username);
+      return;
+   }
+   if player.is_dead {
+      trace!("{} cannot walk while dead", player.username);
+      return;
+   }
+
+   let destination = state.game_objects.lock().await.step(player.location, step);
+   let destination = match destination {
+      Some(location) if !is_blocked(player_id, location, players, state).await => Some(location),
+      _ => None,
+   };
+
+   let Some(player) = players.get_mut(&player_id) else {
+      return;
+   };
+   player.client_request_id = client_request_id;
+   let destination = destination.filter(|_| keep_pace(player, step, Instant::now()));
+   let Some(location) = destination else {
+      debug!(
+         "{} cannot step {step:?} from {:?}",
+         player.username, player.location
+      );
+      return;
+   };
+
+   player.direction = match step {
+      (1, _) => Direction::East,
+      (-1, _) => Direction::West,
+      (_, 1) => Direction::South,
+      _ => Direction::North,
+   };
+   player.location = location;
+   debug!("player {} moved to {location:?}", player.username);
+
+   if let Some(spec) = state.fields.condition_at(location) {
+      conditions::apply(player, spec).await;
+   }
+   let mut home_towns = state.home_towns.lock().await;
+   temple::visit(player, &state.temples, &mut home_towns).await;
+}
+
+/// Whether a step at `now` keeps to the pace the client walks at, which is
+/// how long the step before it took. Diagonal steps take twice as long.
+fn keep_pace(player: &mut Player, step: (i32, i32), now: Instant) -> bool {
+   if now + STEP_LENIENCY < player.next_step_at {
+      return false;
+   }
+   let delay = match step {
+      (0, _) | (_, 0) => BASE_MOVE_DELAY,
+      _ => BASE_MOVE_DELAY * 2.0,
+   };
+   let delay = delay * move_delay_factor(&player.condition_kinds());
+   player.next_step_at = player.next_step_at.max(now) + Duration::from_secs_f32(delay);
+   true
+}
+
+/// Monsters stand in the way, and so do the other players the player can see.
+pub async fn is_blocked(
+   player_id: Uuid,
+   location: Location,
+   players: &HashMap<Uuid, Player>,
+   state: &ServerState,
+) -> bool {
+   let player = &players[&player_id];
+   let by_player = players
+      .values()
+      .any(|p| p.id != player_id && p.location == location && !p.is_dead && player.can_see(p));
+   by_player
+      || matches!(
+         state.mmo_map.lock().await.get(location),
+         Some(MapElement::Monster(_))
+      )
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::player::test_player;
+
+   #[tokio::test]
+   async fn test_keep_pace() {
+      let mut player = test_player("walker", (10, 10, 0)).await;
+      let start = Instant::now();
+      let step = Duration::from_secs_f32(BASE_MOVE_DELAY);
+
+      assert!(keep_pace(&mut player, (1, 0), start));
+      assert!(!keep_pace(&mut player, (1, 0), start + step / 2));
+      // a step bunched up behind the one before it still counts
+      assert!(keep_pace(&mut player, (1, 0), start + step - STEP_LENIENCY));
+      assert!(keep_pace(&mut player, (1, 1), start + step * 2));
+      assert!(!keep_pace(&mut player, (1, 0), start + step * 3));
+      assert!(keep_pace(&mut player, (1, 0), start + step * 4));
+   }
+}
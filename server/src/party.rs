@@ -0,0 +1,411 @@
+use crate::{
+   Player,
+   chat::{reject_if_muted, send_server_msg},
+};
+use futures::future::join_all;
+use shared::{
+   ChatChannel, PartyAction, PartyInfo, constants::SHARED_EXP_RANGE, network::tcp::TcpServerMsg,
+};
+use std::collections::{HashMap, HashSet};
+use thin_logger::log::debug;
+use uuid::Uuid;
+
+#[derive(Debug)]
+pub struct Party {
+   pub leader: Uuid,
+   pub members: Vec<Uuid>, // leader included
+   pub invited: HashSet<Uuid>,
+}
+
+impl Party {
+   fn info(&self, players: &HashMap<Uuid, Player>) -> PartyInfo {
+      let username = |id: &Uuid| players.get(id).map(|p| p.username.clone());
+      PartyInfo {
+         leader: username(&self.leader).unwrap_or_default(),
+         members: self.members.iter().filter_map(username).collect(),
+         invited: self.invited.iter().filter_map(username).collect(),
+      }
+   }
+
+   /// A party needs somebody besides the leader, even if only invited.
+   fn is_over(&self) -> bool {
+      self.members.len() <= 1 && self.invited.is_empty()
+   }
+}
+
+#[derive(Debug, Default)]
+pub struct Parties(pub Vec<Party>);
+
+impl Parties {
+   pub fn of(&self, player_id: Uuid) -> Option<&Party> {
+      self
+         .0
+         .iter()
+         .find(|party| party.members.contains(&player_id))
+   }
+
+   fn index_of(&self, player_id: Uuid) -> Option<usize> {
+      self
+         .0
+         .iter()
+         .position(|party| party.members.contains(&player_id))
+   }
+}
+
+/// Sends every member the current state of the party at `idx`.
+async fn send_party_update(idx: usize, players: &mut HashMap<Uuid, Player>, parties: &Parties) {
+   let party = &parties.0[idx];
+   let msg = TcpServerMsg::Party(Some(party.info(players)));
+
+   let futures = players
+      .values_mut()
+      .filter(|p| party.members.contains(&p.id))
+      .map(|p| p.send_tcp_msg(&msg));
+   join_all(futures).await;
+}
+
+async fn send_to_members(party: &Party, msg: String, players: &mut HashMap<Uuid, Player>) {
+   let msg = TcpServerMsg::ServerMsg(msg);
+   let futures = players
+      .values_mut()
+      .filter(|p| party.members.contains(&p.id))
+      .map(|p| p.send_tcp_msg(&msg));
+   join_all(futures).await;
+}
+
+pub async fn handle_party_action(
+   player_id: Uuid,
+   action: PartyAction,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &mut Parties,
+) {
+   let result = match action {
+      PartyAction::Invite(name) => invite(player_id, &name, players, parties).await,
+      PartyAction::Accept(leader) => accept(player_id, &leader, players, parties).await,
+      PartyAction::Leave => match parties.of(player_id) {
+         Some(_) => {
+            leave_party(player_id, players, parties).await;
+            Ok(())
+         }
+         None => Err("You are not in a party.".to_string()),
+      },
+      PartyAction::Kick(name) => kick(player_id, &name, players, parties).await,
+   };
+
+   if let Err(reply) = result {
+      send_server_msg(player_id, reply, players).await;
+   }
+}
+
+async fn invite(
+   player_id: Uuid,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &mut Parties,
+) -> Result<(), String> {
+   let Some(target) = players
+      .values()
+      .find(|p| p.username == name && p.id != player_id)
+   else {
+      return Err(format!("{name} is not online."));
+   };
+   let target_id = target.id;
+
+   let idx = match parties.index_of(player_id) {
+      Some(idx) if parties.0[idx].leader != player_id => {
+         return Err("Only the leader can invite players.".to_string());
+      }
+      Some(idx) => idx,
+      None => {
+         parties.0.push(Party {
+            leader: player_id,
+            members: vec![player_id],
+            invited: HashSet::new(),
+         });
+         parties.0.len() - 1
+      }
+   };
+
+   if parties.of(target_id).is_some() {
+      if parties.0[idx].is_over() {
+         parties.0.remove(idx);
+      }
+      return Err(format!("{name} is already in a party."));
+   }
+
+   let leader = players[&player_id].username.clone();
+   let party = &mut parties.0[idx];
+   if party.invited.remove(&target_id) {
+      send_server_msg(
+         target_id,
+         format!("{leader} took back the party invitation."),
+         players,
+      )
+      .await;
+   } else {
+      party.invited.insert(target_id);
+      if let Some(target) = players.get_mut(&target_id) {
+         target
+            .send_tcp_msg(&TcpServerMsg::PartyInvite(leader.clone()))
+            .await;
+      }
+      send_server_msg(
+         target_id,
+         format!("{leader} invited you to a party. Type /party accept {leader} to join."),
+         players,
+      )
+      .await;
+   }
+
+   if parties.0[idx].is_over() {
+      let party = parties.0.remove(idx);
+      disband(party, players).await;
+   } else {
+      send_party_update(idx, players, parties).await;
+   }
+   Ok(())
+}
+
+async fn accept(
+   player_id: Uuid,
+   leader: &str,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &mut Parties,
+) -> Result<(), String> {
+   if parties.of(player_id).is_some() {
+      return Err("Leave your party first.".to_string());
+   }
+
+   let idx = parties.0.iter().position(|party| {
+      party.invited.contains(&player_id)
+         && players
+            .get(&party.leader)
+            .is_some_and(|p| p.username == leader)
+   });
+   let Some(idx) = idx else {
+      return Err(format!("{leader} has not invited you to a party."));
+   };
+
+   let party = &mut parties.0[idx];
+   party.invited.remove(&player_id);
+   party.members.push(player_id);
+
+   let username = players[&player_id].username.clone();
+   debug!("{username} joins the party of {leader}");
+   send_to_members(
+      &parties.0[idx],
+      format!("{username} has joined the party."),
+      players,
+   )
+   .await;
+   send_party_update(idx, players, parties).await;
+   Ok(())
+}
+
+async fn kick(
+   player_id: Uuid,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &mut Parties,
+) -> Result<(), String> {
+   let Some(party) = parties.of(player_id) else {
+      return Err("You are not in a party.".to_string());
+   };
+   if party.leader != player_id {
+      return Err("Only the leader can kick members.".to_string());
+   }
+
+   let target = party
+      .members
+      .iter()
+      .copied()
+      .find(|id| *id != player_id && players.get(id).is_some_and(|p| p.username == name));
+   let Some(target_id) = target else {
+      return Err(format!("{name} is not in your party."));
+   };
+
+   send_server_msg(target_id, "You have been removed from the party.", players).await;
+   leave_party(target_id, players, parties).await;
+   Ok(())
+}
+
+/// Takes the player out of their party and any invitations, handing the
+/// lead to the next member when the leader goes.
+pub async fn leave_party(
+   player_id: Uuid,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &mut Parties,
+) {
+   let mut touched: Vec<bool> = parties
+      .0
+      .iter_mut()
+      .map(|party| party.invited.remove(&player_id) | party.members.contains(&player_id))
+      .collect();
+
+   if let Some(idx) = parties.index_of(player_id) {
+      let party = &mut parties.0[idx];
+      party.members.retain(|id| *id != player_id);
+      if party.leader == player_id
+         && let Some(&next) = party.members.first()
+      {
+         party.leader = next;
+      }
+
+      if let Some(p) = players.get_mut(&player_id) {
+         p.send_tcp_msg(&TcpServerMsg::Party(None)).await;
+      }
+      let username = players.get(&player_id).map(|p| p.username.clone());
+      if let Some(username) = username {
+         let msg = format!("{username} has left the party.");
+         send_to_members(&parties.0[idx], msg, players).await;
+      }
+   }
+
+   // leaving can end this party or one the player was only invited to
+   let mut idx = 0;
+   while idx < parties.0.len() {
+      if !touched[idx] {
+         idx += 1;
+      } else if parties.0[idx].is_over() {
+         touched.remove(idx);
+         let party = parties.0.remove(idx);
+         disband(party, players).await;
+      } else {
+         send_party_update(idx, players, parties).await;
+         idx += 1;
+      }
+   }
+}
+
+async fn disband(party: Party, players: &mut HashMap<Uuid, Player>) {
+   for id in party.members {
+      if let Some(p) = players.get_mut(&id) {
+         p.send_tcp_msg(&TcpServerMsg::Party(None)).await;
+         p.send_tcp_msg(&TcpServerMsg::ServerMsg(
+            "Your party has been disbanded.".to_string(),
+         ))
+         .await;
+      }
+   }
+}
+
+pub async fn handle_party_msg(
+   player_id: Uuid,
+   msg: String,
+   players: &mut HashMap<Uuid, Player>,
+   parties: &Parties,
+) {
+   let Some(party) = parties.of(player_id) else {
+      send_server_msg(player_id, "You are not in a party.", players).await;
+      return;
+   };
+
+   if reject_if_muted(player_id, players).await {
+      return;
+   }
+
+   let chat_msg = TcpServerMsg::ChatMsg {
+      username: players[&player_id].username.clone(),
+      msg,
+      channel: ChatChannel::Party,
+   };
+
+   let futures = players
+      .values_mut()
+      .filter(|p| p.id != player_id && party.members.contains(&p.id))
+      .map(|p| p.send_tcp_msg(&chat_msg));
+   join_all(futures).await;
+}
+
+/// Splits the experience for a kill evenly between the killer and the party
+/// members around them. The killer gets what does not split evenly, or the
+/// first member to share if the killer is dead. Without a party the killer
+/// keeps all of it.
+pub fn share_experience(
+   killer: Uuid,
+   experience: u64,
+   players: &HashMap<Uuid, Player>,
+   parties: &Parties,
+) -> Vec<(Uuid, u64)> {
+   let Some(location) = players.get(&killer).map(|p| p.location) else {
+      return vec![];
+   };
+
+   let receivers: Vec<Uuid> = match parties.of(killer) {
+      None => vec![killer],
+      Some(party) => party
+         .members
+         .iter()
+         .copied()
+         .filter(|id| {
+            players.get(id).is_some_and(|p| {
+               !p.is_dead
+                  && p.location.0.abs_diff(location.0) <= SHARED_EXP_RANGE
+                  && p.location.1.abs_diff(location.1) <= SHARED_EXP_RANGE
+                  && p.location.2.abs_diff(location.2) <= 1
+            })
+         })
+         .collect(),
+   };
+
+   if receivers.is_empty() {
+      return vec![];
+   }
+
+   let share = experience / receivers.len() as u64;
+   let remainder = experience % receivers.len() as u64;
+   // a killer who died in the meantime gets nothing, so the first one does
+   let gets_remainder = match receivers.contains(&killer) {
+      true => killer,
+      false => receivers[0],
+   };
+   receivers
+      .into_iter()
+      .map(|id| match id == gets_remainder {
+         true => (id, share + remainder),
+         false => (id, share),
+      })
+      .collect()
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::player::test_player;
+
+   #[tokio::test]
+   async fn test_share_experience() {
+      let mut players = HashMap::new();
+      let mut ids = vec![];
+      for (name, location) in [("a", (5, 5, 0)), ("b", (6, 5, 0)), ("c", (5, 7, 1))] {
+         let p = test_player(name, location).await;
+         ids.push(p.id);
+         players.insert(p.id, p);
+      }
+      let [a, b, c] = ids[..] else { unreachable!() };
+      let mut parties = Parties::default();
+      assert_eq!(share_experience(b, 25, &players, &parties), [(b, 25)]);
+
+      parties.0.push(Party {
+         leader: a,
+         members: vec![a, b, c],
+         invited: HashSet::new(),
+      });
+      let shares = share_experience(b, 25, &players, &parties);
+      assert_eq!(shares, [(a, 8), (b, 9), (c, 8)]);
+
+      // only members around the killer that are alive get a share
+      players.get_mut(&a).unwrap().is_dead = true;
+      players.get_mut(&c).unwrap().location = (5, 5 + SHARED_EXP_RANGE + 1, 0);
+      assert_eq!(share_experience(b, 25, &players, &parties), [(b, 25)]);
+
+      // a dead killer's remainder is not lost
+      players.get_mut(&a).unwrap().is_dead = false;
+      players.get_mut(&b).unwrap().is_dead = true;
+      players.get_mut(&c).unwrap().location = (5, 6, 0);
+      assert_eq!(
+         share_experience(b, 25, &players, &parties),
+         [(a, 13), (c, 12)]
+      );
+   }
+}
@@ -1,20 +1,65 @@
-use shared::{Direction, Location};
-use std::net::SocketAddr;
-use tokio::net::tcp::OwnedWriteHalf;
+use crate::{
+   conditions::Condition,
+   death::{Death, Hit, KILLER_WINDOW},
+};
+use itertools::Itertools;
+use shared::{
+   AttackTarget, ConditionKind, Direction, FISTS, Inventory, ItemKind, Location, PlayerStats, Role,
+   Skull, Weapon, experience_for_level, level_for_experience, max_hp_for_level, max_mana_for_level,
+   network::tcp::{TcpServerMsg, encode},
+};
+use std::{
+   collections::{HashMap, HashSet},
+   net::SocketAddr,
+   time::{Duration, Instant},
+};
+use thin_logger::log::error;
+use tokio::{io::AsyncWriteExt, net::tcp::OwnedWriteHalf};
 use uuid::Uuid;
 
+/// How long fighting or being hurt keeps a player in combat.
+const IN_COMBAT_DURATION: Duration = Duration::from_secs(6);
+
 #[derive(Debug)]
 pub struct Player {
    pub id: Uuid,
    pub username: String,
    pub client_request_id: u32,
+   pub next_step_at: Instant, // steps that come in sooner are refused
    pub location: Location,
 
    pub hp: u32,
    pub max_hp: u32,
+   pub mana: u32,
+   pub max_mana: u32,
    pub level: u32,
+   pub experience: u64,
    pub direction: Direction,
    pub is_dead: bool,
+   pub recent_damage: Vec<Hit>,
+   pub pending_death: Option<Death>, // recorded and penalized on the next tick
+   pub blessings: u32,
+   pub home_town: Option<String>,
+   pub inventory: Inventory,
+   pub ignored: HashSet<String>, // usernames
+
+   pub role: Role,
+   pub is_ghost: bool,
+   pub muted_until: Option<Instant>,
+
+   pub guild: Option<String>,
+
+   pub attack_target: Option<AttackTarget>,
+   pub weapon: Option<ItemKind>, // wielded
+   pub last_attack: Instant,
+   pub skull: Option<Skull>,
+   pub skull_until: Option<Instant>,
+   pub frags: Vec<Instant>,                       // unjustified kills
+   pub spell_cooldowns: HashMap<String, Instant>, // spell name -> ready again at
+   pub conditions: Vec<Condition>,
+   pub in_combat_until: Option<Instant>,
+   pub fed_until: Option<Instant>,
+   pub next_regen: Instant,
 
    pub tcp_tx: OwnedWriteHalf,
    pub tcp_socket: SocketAddr,
@@ -28,18 +73,149 @@ pub enum DamageResult {
 }
 
 impl Player {
-   pub fn take_damage(&mut self, damage: u32) -> DamageResult {
+   pub async fn send_tcp_msg(&mut self, msg: &TcpServerMsg) {
+      let Ok(serialized) = encode(msg) else {
+         error!("failed to serialize TCP message for {}", self.username);
+         return;
+      };
+      if self.tcp_tx.write_all(&serialized).await.is_err() {
+         error!("failed to send TCP message to {}", self.username);
+      }
+   }
+
+   pub fn stats(&self) -> PlayerStats {
+      PlayerStats {
+         hp: self.hp,
+         max_hp: self.max_hp,
+         mana: self.mana,
+         max_mana: self.max_mana,
+         level: self.level,
+         experience: self.experience,
+      }
+   }
+
+   /// Adds experience and levels up as many times as it is enough for. Every
+   /// level gained raises max health and mana and restores the same amount.
+   pub async fn gain_experience(&mut self, experience: u64) {
+      let old_level = self.level;
+      self.experience += experience;
+      self.level = level_for_experience(self.experience);
+
+      if self.level > old_level {
+         let old_max_hp = self.max_hp;
+         self.max_hp = max_hp_for_level(self.level);
+         self.hp += self.max_hp.saturating_sub(old_max_hp);
+         let old_max_mana = self.max_mana;
+         self.max_mana = max_mana_for_level(self.level);
+         self.mana += self.max_mana.saturating_sub(old_max_mana);
+
+         let msg = format!(
+            "You advanced from Level {old_level} to Level {}.",
+            self.level
+         );
+         self.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+      }
+
+      self.send_tcp_msg(&TcpServerMsg::Stats(self.stats())).await;
+   }
+
+   /// Takes experience away, which can cost levels. Health and mana are capped
+   /// at the new maximums.
+   pub async fn lose_experience(&mut self, experience: u64) {
+      let old_level = self.level;
+      self.experience = self.experience.saturating_sub(experience);
+      self.level = level_for_experience(self.experience);
+      self.max_hp = max_hp_for_level(self.level);
+      self.hp = self.hp.min(self.max_hp);
+      self.max_mana = max_mana_for_level(self.level);
+      self.mana = self.mana.min(self.max_mana);
+
+      if self.level < old_level {
+         let msg = format!(
+            "You were downgraded from Level {old_level} to Level {}.",
+            self.level
+         );
+         self.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+      }
+
+      self.send_tcp_msg(&TcpServerMsg::Stats(self.stats())).await;
+   }
+
+   pub async fn set_level(&mut self, level: u32) {
+      self.level = level.max(1);
+      self.experience = experience_for_level(self.level);
+      self.max_hp = max_hp_for_level(self.level);
+      self.hp = self.hp.min(self.max_hp);
+      self.max_mana = max_mana_for_level(self.level);
+      self.mana = self.mana.min(self.max_mana);
+      self.send_tcp_msg(&TcpServerMsg::Stats(self.stats())).await;
+   }
+
+   pub fn condition_kinds(&self) -> Vec<ConditionKind> {
+      self.conditions.iter().map(|c| c.kind).sorted().collect()
+   }
+
+   /// Ghosts are only visible to game masters and above.
+   pub fn can_see(&self, other: &Player) -> bool {
+      !other.is_ghost || self.role >= Role::GameMaster
+   }
+
+   pub fn mute_remaining(&self) -> Option<Duration> {
+      self
+         .muted_until
+         .and_then(|until| until.checked_duration_since(Instant::now()))
+   }
+
+   /// The wielded weapon, or fists once it is gone or out of ammunition.
+   pub fn weapon(&self) -> Weapon {
+      self
+         .weapon
+         .filter(|&kind| self.inventory.count(kind) > 0)
+         .and_then(|kind| kind.weapon())
+         .filter(|weapon| {
+            weapon
+               .ammunition
+               .is_none_or(|ammo| self.inventory.count(ammo) > 0)
+         })
+         .unwrap_or(FISTS)
+   }
+
+   pub fn enter_combat(&mut self) {
+      self.in_combat_until = Some(Instant::now() + IN_COMBAT_DURATION);
+   }
+
+   pub fn is_in_combat(&self) -> bool {
+      self
+         .in_combat_until
+         .is_some_and(|until| Instant::now() < until)
+   }
+
+   /// `source` names what did the damage, in case it turns out to be deadly.
+   pub fn take_damage(&mut self, damage: u32, source: &str) -> DamageResult {
       if self.is_dead {
          return DamageResult::AlreadyDead;
       }
+      self.enter_combat();
 
       self.hp = self.hp.saturating_sub(damage);
+      self
+         .recent_damage
+         .retain(|hit| hit.time.elapsed() < KILLER_WINDOW);
+      self.recent_damage.push(Hit {
+         source: source.to_string(),
+         damage,
+         time: Instant::now(),
+      });
 
       if self.hp == 0 {
          self.is_dead = true;
+         let death = Death::new(self.level, &self.recent_damage);
+         self.recent_damage.clear();
+         let death_message = death.message();
+         self.pending_death = Some(death);
          DamageResult::Died {
             damage,
-            death_message: "You have been slain!".to_string(),
+            death_message,
          }
       } else {
          DamageResult::Damaged {
@@ -49,3 +225,57 @@ impl Player {
       }
    }
 }
+
+/// A level one player standing on `location`, with a connection nobody
+/// reads from.
+#[cfg(test)]
+pub async fn test_player(username: &str, location: Location) -> Player {
+   use tokio::net::{TcpListener, TcpStream};
+
+   let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+   let stream = TcpStream::connect(listener.local_addr().unwrap())
+      .await
+      .unwrap();
+   let tcp_socket = stream.local_addr().unwrap();
+   let (_, tcp_tx) = stream.into_split();
+
+   Player {
+      id: Uuid::new_v4(),
+      username: username.to_string(),
+      client_request_id: 0,
+      next_step_at: Instant::now(),
+      location,
+      hp: max_hp_for_level(1),
+      max_hp: max_hp_for_level(1),
+      mana: max_mana_for_level(1),
+      max_mana: max_mana_for_level(1),
+      level: 1,
+      experience: 0,
+      direction: Direction::South,
+      is_dead: false,
+      recent_damage: vec![],
+      pending_death: None,
+      blessings: 0,
+      home_town: None,
+      inventory: Inventory::starter(),
+      ignored: HashSet::new(),
+      role: Role::Player,
+      is_ghost: false,
+      muted_until: None,
+      guild: None,
+      attack_target: None,
+      weapon: None,
+      last_attack: Instant::now(),
+      skull: None,
+      skull_until: None,
+      frags: vec![],
+      spell_cooldowns: HashMap::new(),
+      conditions: vec![],
+      in_combat_until: None,
+      fed_until: None,
+      next_regen: Instant::now(),
+      tcp_tx,
+      tcp_socket,
+      udp_socket: None,
+   }
+}
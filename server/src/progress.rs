@@ -0,0 +1,67 @@
+use crate::{Player, storage};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shared::Inventory;
+use std::collections::BTreeMap;
+use thin_logger::log::error;
+
+const PROGRESS_PATH: &str = "data/progress.json";
+
+/// What a character keeps between logins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+   pub level: u32,
+   pub experience: u64,
+   pub inventory: Inventory,
+   pub blessings: u32,
+}
+
+impl Default for Progress {
+   /// Where new characters start.
+   fn default() -> Self {
+      Progress {
+         level: 1,
+         experience: 0,
+         inventory: Inventory::starter(),
+         blessings: 0,
+      }
+   }
+}
+
+impl Progress {
+   fn of(player: &Player) -> Progress {
+      Progress {
+         level: player.level,
+         experience: player.experience,
+         inventory: player.inventory.clone(),
+         blessings: player.blessings,
+      }
+   }
+}
+
+/// The progress of every character, saved to disk when they log out and
+/// when they die.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedProgress(BTreeMap<String, Progress>); // username -> progress
+
+impl SavedProgress {
+   pub fn load() -> Result<SavedProgress> {
+      storage::load_json(PROGRESS_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(PROGRESS_PATH, self) {
+         error!("failed to save progress: {e:#}");
+      }
+   }
+
+   /// What `username` logs in with, a new character's start if nothing.
+   pub fn get(&self, username: &str) -> Progress {
+      self.0.get(username).cloned().unwrap_or_default()
+   }
+
+   pub fn record(&mut self, player: &Player) {
+      self.0.insert(player.username.clone(), Progress::of(player));
+      self.save();
+   }
+}
@@ -0,0 +1,59 @@
+use crate::Player;
+use shared::{Skull, network::tcp::TcpServerMsg};
+use std::time::{Duration, Instant};
+use thin_logger::log::info;
+
+const WHITE_SKULL_DURATION: Duration = Duration::from_secs(15 * 60);
+const RED_SKULL_DURATION: Duration = Duration::from_secs(2 * 60 * 60);
+/// Unjustified kills older than this no longer count towards a red skull.
+const FRAG_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+const RED_SKULL_FRAGS: usize = 3;
+
+async fn set_skull(player: &mut Player, skull: Skull, duration: Duration) {
+   let changed = player.skull != Some(skull);
+   player.skull = Some(skull);
+   player.skull_until = Some(Instant::now() + duration);
+
+   if changed {
+      info!("{} gets a {skull:?} skull", player.username);
+      player.send_tcp_msg(&TcpServerMsg::Skull(Some(skull))).await;
+   }
+}
+
+/// Attacking somebody without a skull marks the attacker, or keeps them
+/// marked for longer. A red skull is never traded down for a white one.
+pub async fn mark_aggression(attacker: &mut Player, target_skull: Option<Skull>) {
+   if target_skull.is_some() || attacker.skull == Some(Skull::Red) {
+      return;
+   }
+   set_skull(attacker, Skull::White, WHITE_SKULL_DURATION).await;
+}
+
+/// Counts the kill against the killer unless the victim was marked.
+pub async fn record_kill(killer: &mut Player, victim: &str, victim_skull: Option<Skull>) {
+   if victim_skull.is_some() {
+      return;
+   }
+
+   killer.frags.retain(|at| at.elapsed() < FRAG_DURATION);
+   killer.frags.push(Instant::now());
+
+   let msg = format!("Warning! The murder of {victim} was not justified.");
+   killer.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+
+   if killer.frags.len() >= RED_SKULL_FRAGS {
+      set_skull(killer, Skull::Red, RED_SKULL_DURATION).await;
+   }
+}
+
+/// Takes the skull off once its time is up.
+pub async fn expire_skull(player: &mut Player) {
+   if player
+      .skull_until
+      .is_some_and(|until| Instant::now() >= until)
+   {
+      player.skull = None;
+      player.skull_until = None;
+      player.send_tcp_msg(&TcpServerMsg::Skull(None)).await;
+   }
+}
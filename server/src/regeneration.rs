@@ -0,0 +1,68 @@
+use crate::Player;
+use shared::{Item, ItemKind, network::tcp::TcpServerMsg};
+use std::time::{Duration, Instant};
+use thin_logger::log::debug;
+
+const REGEN_INTERVAL: Duration = Duration::from_secs(3);
+/// Eating more than this much food at once is refused.
+const MAX_FED: Duration = Duration::from_secs(20 * 60);
+
+/// Health and mana recovered every [`REGEN_INTERVAL`].
+fn regen_amounts(fed: bool) -> (u32, u32) {
+   if fed { (3, 4) } else { (1, 1) }
+}
+
+/// Recovers some health and mana every few seconds, faster while fed. The
+/// dead and those in combat do not regenerate.
+pub async fn regenerate(player: &mut Player) {
+   let now = Instant::now();
+   if now < player.next_regen {
+      return;
+   }
+   player.next_regen = now + REGEN_INTERVAL;
+
+   if player.is_dead || player.is_in_combat() {
+      return;
+   }
+   if player.hp == player.max_hp && player.mana == player.max_mana {
+      return;
+   }
+
+   let fed = player.fed_until.is_some_and(|until| now < until);
+   let (hp, mana) = regen_amounts(fed);
+   player.hp = (player.hp + hp).min(player.max_hp);
+   player.mana = (player.mana + mana).min(player.max_mana);
+   player
+      .send_tcp_msg(&TcpServerMsg::Stats(player.stats()))
+      .await;
+}
+
+/// Eats one item of `kind`, which keeps the player fed for a while longer.
+pub async fn eat(player: &mut Player, kind: ItemKind) {
+   let Some(duration) = kind.food_duration() else {
+      let msg = "You cannot eat this.".to_string();
+      player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+      return;
+   };
+   if player.is_dead || player.inventory.count(kind) == 0 {
+      return;
+   }
+
+   let now = Instant::now();
+   let fed_left = player
+      .fed_until
+      .map_or(Duration::ZERO, |until| until.saturating_duration_since(now));
+   let fed = fed_left + duration;
+   if fed > MAX_FED {
+      let msg = "You are full.".to_string();
+      player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+      return;
+   }
+
+   player.inventory.remove_all(&[Item::new(kind, 1)]);
+   player.fed_until = Some(now + fed);
+   debug!("{} eats {}, fed for {fed:?}", player.username, kind.name());
+   player
+      .send_tcp_msg(&TcpServerMsg::Inventory(player.inventory.clone()))
+      .await;
+}
@@ -1,30 +1,175 @@
-use crate::Player;
+use crate::{MapElement, MmoMap, Monster, Player};
+use itertools::Itertools;
 use shared::{
-   GameObjects, Location,
+   CREATURE_TILESET, CreatureType, Creatures, Direction, GameObject, GameObjects, Location, Temple,
    constants::{MAP_HEIGHT, MAP_WIDTH},
 };
-use std::{collections::HashMap, sync::Arc};
-use thin_logger::log::{info, warn};
+use std::{
+   collections::HashMap,
+   sync::Arc,
+   time::{Duration, Instant},
+};
+use thin_logger::log::{debug, info, warn};
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-pub async fn generate_spawn_location(
-   players: Arc<Mutex<HashMap<Uuid, Player>>>,
-   game_objects: Arc<Mutex<GameObjects>>,
-) -> Location {
+/// How long a creature placed by the map stays dead.
+const RESPAWN_DELAY: Duration = Duration::from_secs(60);
+
+/// Puts a new creature of the given kind at `location`. Returns its uid.
+pub fn place_creature(
+   location: Location,
+   creature: &CreatureType,
+   game_objects: &mut GameObjects,
+   mmo_map: &mut MmoMap,
+) -> Uuid {
+   let id = creature.sprites.south;
+   let uid = Uuid::new_v4();
+   game_objects.0.insert(
+      location,
+      GameObject::Creature {
+         id,
+         tileset_location: CREATURE_TILESET,
+         uid,
+         hp: creature.hp,
+         direction: Direction::South,
+      },
+   );
+   mmo_map[location] = MapElement::Monster(Monster {
+      id: (id, CREATURE_TILESET),
+      last_movement: Instant::now(),
+      last_attack: Instant::now(),
+   });
+   uid
+}
+
+/// Tiles within two steps of `center` where nothing stands, closest first.
+pub fn free_tiles_around(
+   center: Location,
+   mmo_map: &MmoMap,
+   occupied: &[Location],
+) -> Vec<Location> {
+   let (x, y, z) = center;
+   (1..=2i32)
+      .flat_map(|r| {
+         (-r..=r)
+            .cartesian_product(-r..=r)
+            .filter(move |(dx, dy)| dx.abs() == r || dy.abs() == r)
+      })
+      .filter_map(|(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?, z)))
+      .filter(|&location| matches!(mmo_map.get(location), Some(MapElement::Empty)))
+      .filter(|location| !occupied.contains(location))
+      .collect()
+}
+
+#[derive(Debug)]
+struct Spawn {
+   location: Location,
+   creature_id: u32,
+   uid: Option<Uuid>, // None while dead
+   killed_at: Instant,
+}
+
+/// The creatures placed by the map. Killed ones come back where they were
+/// placed after [`RESPAWN_DELAY`].
+#[derive(Debug)]
+pub struct Spawns(Vec<Spawn>);
+
+impl Spawns {
+   pub fn from_game_objects(game_objects: &GameObjects) -> Spawns {
+      let spawns = game_objects
+         .0
+         .iter()
+         .filter_map(|(location, object)| match object {
+            GameObject::Creature { id, uid, .. } => Some(Spawn {
+               location: *location,
+               creature_id: *id,
+               uid: Some(*uid),
+               killed_at: Instant::now(),
+            }),
+            _ => None,
+         })
+         .collect();
+      Spawns(spawns)
+   }
+
+   pub fn respawn_creatures(
+      &mut self,
+      creatures: &Creatures,
+      game_objects: &mut GameObjects,
+      mmo_map: &mut MmoMap,
+      players: &HashMap<Uuid, Player>,
+   ) {
+      for spawn in &mut self.0 {
+         if let Some(uid) = spawn.uid {
+            if game_objects.find_monster(uid).is_none() {
+               spawn.uid = None;
+               spawn.killed_at = Instant::now();
+            }
+            continue;
+         }
+
+         let is_free = mmo_map[spawn.location] == MapElement::Empty
+            && !game_objects.0.contains_key(&spawn.location)
+            && players.values().all(|p| p.location != spawn.location);
+         if spawn.killed_at.elapsed() < RESPAWN_DELAY || !is_free {
+            continue;
+         }
+         let Some(creature) = creatures.by_id(spawn.creature_id) else {
+            continue;
+         };
+         debug!("respawning {} at {:?}", creature.name, spawn.location);
+         spawn.uid = Some(place_creature(
+            spawn.location,
+            creature,
+            game_objects,
+            mmo_map,
+         ));
+      }
+   }
+}
+
+async fn taken_locations(
+   players: &Mutex<HashMap<Uuid, Player>>,
+   game_objects: &Mutex<GameObjects>,
+) -> Vec<Location> {
    let players = players.lock().await;
    let mut taken_locations = players.values().map(|p| p.location).collect::<Vec<_>>();
 
    // Add monster locations
    let game_objs = game_objects.lock().await;
    for (location, obj) in game_objs.0.iter() {
-      if obj.id() == 63
-      /* orc */
-      {
+      if obj.is_monster() {
          taken_locations.push(*location);
       }
    }
-   drop(game_objs);
+
+   taken_locations
+}
+
+/// The first free tile of the temple. Anywhere on the map will do if the
+/// temple is full.
+pub async fn temple_spawn_location(
+   temple: &Temple,
+   players: Arc<Mutex<HashMap<Uuid, Player>>>,
+   game_objects: Arc<Mutex<GameObjects>>,
+) -> Location {
+   let taken_locations = taken_locations(&players, &game_objects).await;
+   match temple
+      .tiles
+      .iter()
+      .find(|tile| !taken_locations.contains(tile))
+   {
+      Some(&location) => location,
+      None => generate_spawn_location(players, game_objects).await,
+   }
+}
+
+pub async fn generate_spawn_location(
+   players: Arc<Mutex<HashMap<Uuid, Player>>>,
+   game_objects: Arc<Mutex<GameObjects>>,
+) -> Location {
+   let taken_locations = taken_locations(&players, &game_objects).await;
 
    // Find first available location starting from (0,0)
    let mut y = 0;
@@ -0,0 +1,244 @@
+use crate::{Player, ServerState, chat::send_server_msg, combat, conditions, pvp};
+use anyhow::{Context, Result, bail};
+use futures::future::join_all;
+use rand::Rng;
+use shared::{
+   Location, Spell, SpellKind, has_line_of_sight, is_on_screen,
+   network::{sendable::SendableAsync, tcp::TcpServerMsg, udp::UdpServerMsg},
+};
+use std::{
+   collections::HashMap,
+   fs,
+   time::{Duration, Instant},
+};
+use thin_logger::log::{debug, info};
+use tokio::net::UdpSocket;
+use uuid::Uuid;
+
+const SPELLS_PATH: &str = "assets/spells.json";
+
+/// Every spell players can cast, read once at startup.
+#[derive(Debug, Default)]
+pub struct Spells(Vec<Spell>);
+
+impl Spells {
+   pub fn load() -> Result<Spells> {
+      let json =
+         fs::read_to_string(SPELLS_PATH).with_context(|| format!("reading {SPELLS_PATH}"))?;
+      let spells: Vec<Spell> =
+         serde_json::from_str(&json).with_context(|| format!("parsing {SPELLS_PATH}"))?;
+      for spell in &spells {
+         check(spell).with_context(|| format!("checking {} in {SPELLS_PATH}", spell.name))?;
+      }
+      info!("loaded {} spells", spells.len());
+      Ok(Spells(spells))
+   }
+
+   pub fn all(&self) -> &[Spell] {
+      &self.0
+   }
+
+   /// The spell whose words were just said, if any.
+   pub fn find(&self, text: &str) -> Option<&Spell> {
+      let text = text.trim();
+      self
+         .0
+         .iter()
+         .find(|spell| spell.words.eq_ignore_ascii_case(text))
+   }
+}
+
+/// Catches the ranges the game would otherwise panic on once it rolls them.
+fn check(spell: &Spell) -> Result<()> {
+   match spell.kind {
+      SpellKind::Heal { min, max } | SpellKind::Damage { min, max } if min > max => {
+         bail!("min {min} is above its max {max}")
+      }
+      _ => Ok(()),
+   }
+}
+
+/// Lets the client know which spells its hotkeys cast.
+pub async fn handle_login(player_id: Uuid, players: &mut HashMap<Uuid, Player>, spells: &Spells) {
+   if let Some(player) = players.get_mut(&player_id) {
+      let msg = TcpServerMsg::Spells(spells.all().to_vec());
+      player.send_tcp_msg(&msg).await;
+   }
+}
+
+/// Checks the caster can cast `spell` right now, telling them why not.
+async fn can_cast(
+   player_id: Uuid,
+   spell: &Spell,
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+) -> bool {
+   let Some(caster) = players.get(&player_id) else {
+      return false;
+   };
+
+   let reply = if caster.is_dead {
+      return false;
+   } else if caster.level < spell.level {
+      format!("You need level {} to cast {}.", spell.level, spell.name)
+   } else if caster
+      .spell_cooldowns
+      .get(&spell.name)
+      .is_some_and(|ready_at| Instant::now() < *ready_at)
+   {
+      "You are exhausted.".to_string()
+   } else if caster.mana < spell.mana {
+      "You do not have enough mana.".to_string()
+   } else if spell.kind.is_hostile() && state.protection_zones.contains(caster.location) {
+      "This action is not permitted in a protection zone.".to_string()
+   } else {
+      return true;
+   };
+
+   send_server_msg(player_id, reply, players).await;
+   false
+}
+
+/// Casts `spell` for the player, if they can. Returns whether the spell went
+/// off.
+pub async fn cast(
+   player_id: Uuid,
+   spell: &Spell,
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+   udp_socket: &UdpSocket,
+) -> bool {
+   if !can_cast(player_id, spell, players, state).await {
+      return false;
+   }
+
+   let Some(caster) = players.get_mut(&player_id) else {
+      return false;
+   };
+   caster.mana -= spell.mana;
+   let ready_at = Instant::now() + Duration::from_millis(spell.cooldown_ms);
+   caster.spell_cooldowns.insert(spell.name.clone(), ready_at);
+   caster
+      .send_tcp_msg(&TcpServerMsg::Stats(caster.stats()))
+      .await;
+
+   let caster_location = caster.location;
+   let mut tiles = spell.area.tiles(caster_location, caster.direction);
+   // spells stop at walls like missiles do, and are not seen behind them
+   let mmo_map = state.mmo_map.lock().await;
+   tiles.retain(|&tile| has_line_of_sight(caster_location, tile, |t| mmo_map.blocks_missiles(t)));
+   drop(mmo_map);
+   debug!("{} casts {} on {tiles:?}", caster.username, spell.name);
+
+   match spell.kind.is_hostile() {
+      false => cast_friendly(player_id, spell, &tiles, players).await,
+      true => cast_hostile(player_id, spell, &tiles, players, state, udp_socket).await,
+   }
+
+   let effect = UdpServerMsg::MagicEffect {
+      effect: spell.effect,
+      tiles,
+   };
+   let futures = players
+      .values()
+      .filter(|p| is_on_screen(caster_location, p.location))
+      .filter_map(|p| p.udp_socket)
+      .map(|addr| udp_socket.send_msg_and_log_(&effect, Some(addr)));
+   join_all(futures).await;
+
+   true
+}
+
+/// Heals and buffs the players on `tiles`, the caster included.
+async fn cast_friendly(
+   player_id: Uuid,
+   spell: &Spell,
+   tiles: &[Location],
+   players: &mut HashMap<Uuid, Player>,
+) {
+   let caster = &players[&player_id];
+   let targets: Vec<Uuid> = players
+      .values()
+      .filter(|p| tiles.contains(&p.location) && !p.is_dead && caster.can_see(p))
+      .map(|p| p.id)
+      .collect();
+
+   for id in targets {
+      let Some(p) = players.get_mut(&id) else {
+         continue;
+      };
+      if let SpellKind::Heal { min, max } = spell.kind {
+         p.hp = (p.hp + rand::thread_rng().gen_range(min..=max)).min(p.max_hp);
+         p.send_tcp_msg(&TcpServerMsg::Stats(p.stats())).await;
+      }
+      if let Some(condition) = spell.condition {
+         conditions::apply(p, condition).await;
+      }
+   }
+}
+
+/// Damages the monsters and players on `tiles` and curses the players. Other
+/// players are only reached outside protection zones, and only if the server
+/// allows PvP.
+async fn cast_hostile(
+   player_id: Uuid,
+   spell: &Spell,
+   tiles: &[Location],
+   players: &mut HashMap<Uuid, Player>,
+   state: &ServerState,
+   udp_socket: &UdpSocket,
+) {
+   let mut game_objects = state.game_objects.lock().await;
+   let parties = state.parties.lock().await;
+
+   for &tile in tiles {
+      let damage = match spell.kind {
+         SpellKind::Damage { min, max } => rand::thread_rng().gen_range(min..=max),
+         _ => 0,
+      };
+
+      if damage > 0
+         && game_objects
+            .0
+            .get(&tile)
+            .and_then(|obj| obj.uid())
+            .is_some()
+      {
+         combat::hit_monster(
+            player_id,
+            tile,
+            damage,
+            players,
+            &mut game_objects,
+            &parties,
+            state,
+         )
+         .await;
+      }
+
+      let caster = &players[&player_id];
+      let target = players
+         .values()
+         .find(|p| p.id != player_id && p.location == tile && !p.is_dead && caster.can_see(p))
+         .map(|p| (p.id, p.skull));
+      let Some((target_id, target_skull)) = target else {
+         continue;
+      };
+      if !state.config.pvp || state.protection_zones.contains(tile) {
+         continue;
+      }
+
+      if damage > 0 {
+         combat::hit_player(player_id, target_id, damage, players, udp_socket).await;
+      } else if let Some(caster) = players.get_mut(&player_id) {
+         pvp::mark_aggression(caster, target_skull).await;
+         caster.enter_combat();
+      }
+
+      if let Some(condition) = spell.condition
+         && let Some(target) = players.get_mut(&target_id)
+      {
+         conditions::apply(target, condition).await;
+      }
+   }
+}
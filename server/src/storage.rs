@@ -0,0 +1,43 @@
+use anyhow::{Context, Result};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+   fs::{self, OpenOptions},
+   io::{ErrorKind, Write},
+   path::Path,
+};
+
+/// Reads `path` as JSON. A file that does not exist yet reads as the default value.
+pub fn load_json<T: DeserializeOwned + Default>(path: impl AsRef<Path>) -> Result<T> {
+   let path = path.as_ref();
+   match fs::read_to_string(path) {
+      Ok(json) => {
+         serde_json::from_str(&json).with_context(|| format!("failed to parse {}", path.display()))
+      }
+      Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+      Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+   }
+}
+
+/// Writes `value` to `path` as JSON. It goes to a temporary file first so a
+/// crash halfway through never leaves a truncated file behind.
+pub fn save_json<T: Serialize>(path: impl AsRef<Path>, value: &T) -> Result<()> {
+   let path = path.as_ref();
+   if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+   }
+
+   let tmp = path.with_extension("tmp");
+   fs::write(&tmp, serde_json::to_vec_pretty(value)?)?;
+   fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub fn append_line(path: impl AsRef<Path>, line: &str) -> Result<()> {
+   let path = path.as_ref();
+   if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+   }
+
+   let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+   writeln!(file, "{line}")?;
+   Ok(())
+}
@@ -1,28 +1,33 @@
-use crate::{MapElement, MmoMap, Player, player::DamageResult};
+use crate::{
+   MapElement, MmoMap, Player, ServerState,
+   chat::send_server_msg,
+   combat, conditions,
+   death::{self, Corpses},
+   party::Parties,
+   pvp, regeneration,
+   spawn_manager::{Spawns, free_tiles_around, place_creature},
+};
 use anyhow::Result;
 use futures::future::join_all;
+use rand::Rng;
 use shared::{
-   GameObject, GameObjects, Location,
+   AttackTarget, Behavior, CreatureType, Creatures, GameObject, GameObjects, Location, Projectile,
    constants::*,
+   distance, is_on_screen,
    network::{sendable::SendableAsync, udp::*},
 };
 use std::{
    collections::HashMap,
-   net::SocketAddr,
    sync::Arc,
    time::{Duration, Instant},
 };
-use thin_logger::log::{debug, error, info, trace};
+use thin_logger::log::{debug, error, trace};
 use tokio::{net::UdpSocket, sync::Mutex, task::JoinHandle};
 use uuid::Uuid;
 
-// ================ Helper Functions ================
+const PLAYER_ATTACK_COOLDOWN: Duration = Duration::from_secs(2);
 
-fn is_adjacent(a: Location, b: Location) -> bool {
-   let dx = (a.0 as i32 - b.0 as i32).abs();
-   let dy = (a.1 as i32 - b.1 as i32).abs();
-   a.2 == b.2 && dx <= 1 && dy <= 1
-}
+// ================ Helper Functions ================
 
 fn is_within_view(monster_pos: Location, player_pos: Location) -> bool {
    let min_x = (monster_pos.0 as i32) - ((CAMERA_WIDTH / 2) as i32);
@@ -40,129 +45,196 @@ fn get_active_monsters(game_objects: &GameObjects) -> Vec<(Location, GameObject)
       .0
       .clone()
       .into_iter()
-      .filter(|(_, obj)| obj.id() == 63) // orc
+      .filter(|(_, obj)| obj.is_monster())
       .collect()
 }
 
-// ================ Player Damage Handling ================
-
-async fn handle_player_damage(
-   player: &mut Player,
-   damage: u32,
-   game_objects: &mut GameObjects,
-   udp_socket: &UdpSocket,
-   player_udp: SocketAddr,
-) {
-   match player.take_damage(damage) {
-      DamageResult::AlreadyDead => {}
-      DamageResult::Damaged { damage, hp } => {
-         info!(
-            "Player {} took {} damage. HP: {}/{}",
-            player.username, damage, hp, player.max_hp
-         );
-
-         let health_msg = UdpServerMsg::PlayerHealthUpdate { hp };
-         udp_socket
-            .send_msg_and_log_(health_msg, Some(player_udp))
-            .await;
-
-         let damage_msg = UdpServerMsg::DamageNumber { damage };
-         udp_socket
-            .send_msg_and_log_(damage_msg, Some(player_udp))
-            .await;
-      }
-      DamageResult::Died {
-         damage,
-         death_message,
-      } => {
-         info!(
-            "Player {} has died from {} damage.",
-            player.username, damage
-         );
-
-         // Place a flowerpot at the player's death location (simulating a corpse)
-         game_objects.0.insert(
-            player.location,
-            GameObject::FlowerPot {
-               id: 149,
-               tileset_location: 0,
-            },
-         );
-
-         let death_msg = UdpServerMsg::PlayerDeath {
-            message: death_message,
-         };
-         udp_socket
-            .send_msg_and_log_(death_msg, Some(player_udp))
-            .await;
-      }
-   }
-}
-
 // ================ Monster AI ================
 
+/// Attacks the player with the strongest attack that reaches them, once the
+/// monster is ready to attack again. Summoners may call for help as well.
+/// Returns the missile of a ranged attack.
 async fn process_monster_attack(
    monster_location: Location,
+   creature: &CreatureType,
    player: &mut Player,
    game_objects: &mut GameObjects,
-   mmo_map: &Arc<Mutex<MmoMap>>,
+   state: &ServerState,
    udp_socket: &UdpSocket,
-   player_udp: SocketAddr,
-) -> bool {
-   let mut mmo_map = mmo_map.lock().await;
+   player_locations: &[Location],
+) -> Option<Projectile> {
+   let player_udp = player.udp_socket?;
+   let mut mmo_map = state.mmo_map.lock().await;
 
    let MapElement::Monster(mut monster) = mmo_map[monster_location] else {
       debug!("Invalid monster location for attack");
-      return false;
+      return None;
    };
 
-   if monster.last_attack.elapsed() < Duration::from_secs(2) {
+   let attack = creature
+      .attacks
+      .iter()
+      .filter(|attack| combat::can_reach(monster_location, player.location, attack.range, &mmo_map))
+      .max_by_key(|attack| attack.max)?;
+
+   if monster.last_attack.elapsed() < Duration::from_millis(attack.cooldown_ms) {
       trace!("Monster can't attack yet (cooldown)");
-      return false;
+      return None;
    }
 
-   trace!("Monster is adjacent to player. Attacking!");
+   trace!("Monster can reach player. Attacking!");
 
    // Update monster's last attack time
    monster.last_attack = Instant::now();
    mmo_map[monster_location] = MapElement::Monster(monster);
 
+   summon(
+      monster_location,
+      creature,
+      &state.creatures,
+      game_objects,
+      &mut mmo_map,
+      player_locations,
+   );
+
    // Release the lock before handling damage
    drop(mmo_map);
 
-   // hardcoded damage for now
-   handle_player_damage(player, 10, game_objects, udp_socket, player_udp).await;
+   let damage = rand::thread_rng().gen_range(attack.min..=attack.max);
+   let source = creature.with_article();
+   combat::handle_player_damage(player, damage, &source, udp_socket, player_udp).await;
+   if let Some(condition) = attack.condition
+      && rand::thread_rng().gen_bool(attack.condition_chance)
+   {
+      conditions::apply(player, condition).await;
+   }
+
+   attack.missile.map(|missile| Projectile {
+      missile,
+      from: monster_location,
+      to: player.location,
+   })
+}
+
+/// Calls more creatures next to a summoner, as long as there are not too many
+/// of them around already.
+fn summon(
+   location: Location,
+   creature: &CreatureType,
+   creatures: &Creatures,
+   game_objects: &mut GameObjects,
+   mmo_map: &mut MmoMap,
+   player_locations: &[Location],
+) {
+   let Some((summoned, chance, max)) = creature.behavior(|b| match b {
+      Behavior::Summon {
+         creature,
+         chance,
+         max,
+      } => Some((creature, *chance, *max)),
+      _ => None,
+   }) else {
+      return;
+   };
+   let Some(summoned) = creatures.by_name(summoned) else {
+      return;
+   };
+   if !rand::thread_rng().gen_bool(chance) {
+      return;
+   }
+
+   let around = game_objects
+      .0
+      .iter()
+      .filter(|(l, obj)| obj.id() == summoned.sprites.south && is_within_view(location, **l))
+      .count();
+   if around >= max as usize {
+      return;
+   }
+
+   if let Some(&tile) = free_tiles_around(location, mmo_map, player_locations).first() {
+      debug!("{} summons {} at {tile:?}", creature.name, summoned.name);
+      place_creature(tile, summoned, game_objects, mmo_map);
+   }
+}
+
+/// Where the monster wants to go next: away when it flees or is too close
+/// for its liking, closer when it is too far, nowhere otherwise.
+fn next_step(
+   monster_location: Location,
+   hp: u32,
+   creature: &CreatureType,
+   player_location: Location,
+   mmo_map: &MmoMap,
+) -> Option<Location> {
+   let fleeing = creature
+      .behavior(|b| match b {
+         Behavior::Flee { below } => Some(*below),
+         _ => None,
+      })
+      .is_some_and(|below| (hp as f32) < creature.hp as f32 * below);
+   let keep_distance = creature.behavior(|b| match b {
+      Behavior::KeepDistance { distance } => Some(*distance),
+      _ => None,
+   });
+   let current = distance(monster_location, player_location);
+
+   let wanted = match keep_distance {
+      _ if fleeing => u32::MAX,
+      Some(keep_distance) => keep_distance,
+      None => 1,
+   };
 
-   true
+   if current < wanted {
+      // the free neighbor farthest from the player, if it is any farther
+      let (x, y, z) = monster_location;
+      [(0, -1), (0, 1), (-1, 0), (1, 0)]
+         .into_iter()
+         .filter_map(|(dx, dy)| Some((x.checked_add_signed(dx)?, y.checked_add_signed(dy)?, z)))
+         .filter(|&tile| matches!(mmo_map.get(tile), Some(MapElement::Empty)))
+         .filter(|&tile| tile != player_location)
+         .max_by_key(|&tile| distance(tile, player_location))
+         .filter(|&tile| distance(tile, player_location) > current)
+   } else if current > wanted {
+      let shortest_path = mmo_map.shortest_path(monster_location, player_location);
+      // the last tile is the player's own
+      (shortest_path.len() > 2).then(|| shortest_path[1])
+   } else {
+      None
+   }
 }
 
 async fn process_monster_movement(
    monster_location: Location,
+   monster_hp: u32,
+   creature: &CreatureType,
    player_location: Location,
    game_objects: &mut GameObjects,
    mmo_map: &Arc<Mutex<MmoMap>>,
 ) -> Result<()> {
    let mut mmo_map = mmo_map.lock().await;
 
-   let shortest_path = mmo_map.shortest_path(monster_location, player_location);
-
-   if shortest_path.len() <= 2 {
-      trace!("No valid path to player or already adjacent");
-      return Ok(());
-   }
-
-   let next_position = shortest_path[1];
-
    let MapElement::Monster(monster) = &mmo_map[monster_location] else {
       debug!("Invalid monster location");
       return Ok(());
    };
 
-   if monster.last_movement.elapsed() < Duration::from_millis(200) {
+   if monster.last_movement.elapsed() < Duration::from_millis(creature.move_cooldown_ms) {
       trace!("Monster cant move yet (cooldown)");
       return Ok(());
    }
 
+   let Some(next_position) = next_step(
+      monster_location,
+      monster_hp,
+      creature,
+      player_location,
+      &mmo_map,
+   ) else {
+      trace!("Monster is where it wants to be, or has no way to get there");
+      return Ok(());
+   };
+
    if game_objects
       .move_object(monster_location, next_position)
       .is_none()
@@ -177,48 +249,221 @@ async fn process_monster_movement(
 async fn process_monster_ai(
    player: &mut Player,
    game_objects: &mut GameObjects,
-   mmo_map: &Arc<Mutex<MmoMap>>,
+   state: &ServerState,
    udp_socket: &UdpSocket,
-   player_udp: SocketAddr,
+   player_locations: &[Location],
+   projectiles: &mut Vec<Projectile>,
 ) -> Result<()> {
-   if player.is_dead {
+   if player.is_dead || player.is_ghost {
       return Ok(());
    }
 
    let monsters = get_active_monsters(game_objects);
 
-   for (monster_location, _) in monsters {
+   for (monster_location, monster) in monsters {
       if !is_within_view(monster_location, player.location) {
          continue;
       }
+      let Some(creature) = state.creatures.of(&monster) else {
+         continue;
+      };
+      let GameObject::Creature { hp, .. } = monster else {
+         continue;
+      };
 
       trace!("Monster can see player {}", player.username);
 
-      if is_adjacent(monster_location, player.location) {
-         process_monster_attack(
+      if !state.protection_zones.contains(player.location) {
+         let projectile = process_monster_attack(
             monster_location,
+            creature,
             player,
             game_objects,
-            mmo_map,
+            state,
             udp_socket,
-            player_udp,
+            player_locations,
          )
          .await;
-      } else {
-         process_monster_movement(monster_location, player.location, game_objects, mmo_map).await?;
+         projectiles.extend(projectile);
       }
+      process_monster_movement(
+         monster_location,
+         hp,
+         creature,
+         player.location,
+         game_objects,
+         &state.mmo_map,
+      )
+      .await?;
    }
 
    Ok(())
 }
 
+// ================ Player Attacks ================
+
+/// Hits the player's target if their weapon reaches it and the attack is off
+/// cooldown. Nobody fights inside a protection zone.
+async fn process_player_attack(
+   player_id: Uuid,
+   players: &mut HashMap<Uuid, Player>,
+   game_objects: &mut GameObjects,
+   udp_socket: &UdpSocket,
+   state: &ServerState,
+   parties: &Parties,
+   projectiles: &mut Vec<Projectile>,
+) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   let Some(target) = player.attack_target.clone() else {
+      return;
+   };
+   if player.is_dead || player.last_attack.elapsed() < PLAYER_ATTACK_COOLDOWN {
+      return;
+   }
+
+   match target {
+      AttackTarget::Monster(uid) => {
+         if state.protection_zones.contains(player.location) {
+            return;
+         }
+         let projectile =
+            attack_monster(player_id, uid, players, game_objects, parties, state).await;
+         projectiles.extend(projectile);
+      }
+      AttackTarget::Player(name) => {
+         let projectile = attack_player(player_id, &name, players, udp_socket, state).await;
+         projectiles.extend(projectile);
+      }
+   }
+}
+
+/// Hits the monster if the player's weapon reaches it. Returns the missile
+/// of a ranged weapon.
+async fn attack_monster(
+   player_id: Uuid,
+   target: Uuid,
+   players: &mut HashMap<Uuid, Player>,
+   game_objects: &mut GameObjects,
+   parties: &Parties,
+   state: &ServerState,
+) -> Option<Projectile> {
+   let player = players.get_mut(&player_id)?;
+
+   let Some((location, _)) = game_objects.find_monster(target) else {
+      trace!("target of {} is gone", player.username);
+      player.attack_target = None;
+      return None;
+   };
+
+   let weapon = player.weapon();
+   let from = player.location;
+   if !combat::can_reach(from, location, weapon.range, &*state.mmo_map.lock().await) {
+      return None;
+   }
+   player.last_attack = Instant::now();
+   combat::use_ammunition(player, weapon).await;
+
+   let damage = rand::thread_rng().gen_range(weapon.min..=weapon.max);
+   let killed = combat::hit_monster(
+      player_id,
+      location,
+      damage,
+      players,
+      game_objects,
+      parties,
+      state,
+   )
+   .await;
+
+   if killed && let Some(player) = players.get_mut(&player_id) {
+      player.attack_target = None;
+   }
+
+   weapon.missile.map(|missile| Projectile {
+      missile,
+      from,
+      to: location,
+   })
+}
+
+/// Hits the target player if the server allows PvP, the attacker's weapon
+/// reaches them and neither of them stands in a protection zone. Returns the
+/// missile of a ranged weapon.
+async fn attack_player(
+   player_id: Uuid,
+   name: &str,
+   players: &mut HashMap<Uuid, Player>,
+   udp_socket: &UdpSocket,
+   state: &ServerState,
+) -> Option<Projectile> {
+   let attacker = &players[&player_id];
+   let target = players
+      .values()
+      .find(|p| p.id != player_id && p.username == name && !p.is_dead && attacker.can_see(p))
+      .map(|p| (p.id, p.location));
+   let Some((target_id, target_location)) = target else {
+      if let Some(attacker) = players.get_mut(&player_id) {
+         attacker.attack_target = None;
+      }
+      return None;
+   };
+
+   let zones = &state.protection_zones;
+   let refusal = if !state.config.pvp {
+      Some("You may not attack other players on this server.")
+   } else if zones.contains(attacker.location) || zones.contains(target_location) {
+      Some("This action is not permitted in a protection zone.")
+   } else {
+      None
+   };
+   if let Some(msg) = refusal {
+      if let Some(attacker) = players.get_mut(&player_id) {
+         attacker.attack_target = None;
+      }
+      send_server_msg(player_id, msg, players).await;
+      return None;
+   }
+
+   let weapon = attacker.weapon();
+   let from = attacker.location;
+   if !combat::can_reach(
+      from,
+      target_location,
+      weapon.range,
+      &*state.mmo_map.lock().await,
+   ) {
+      return None;
+   }
+
+   let attacker = players.get_mut(&player_id)?;
+   attacker.last_attack = Instant::now();
+   combat::use_ammunition(attacker, weapon).await;
+
+   let damage = rand::thread_rng().gen_range(weapon.min..=weapon.max);
+   let killed = combat::hit_player(player_id, target_id, damage, players, udp_socket).await;
+
+   if killed && let Some(attacker) = players.get_mut(&player_id) {
+      attacker.attack_target = None;
+   }
+
+   weapon.missile.map(|missile| Projectile {
+      missile,
+      from,
+      to: target_location,
+   })
+}
+
 // ================ Player Updates ================
 
 async fn send_player_updates(
+   tick: u64,
    player_id: Uuid,
    player: &Player,
    all_players: &HashMap<Uuid, Player>,
    game_objects: &GameObjects,
+   corpses: &Corpses,
    udp_socket: &UdpSocket,
 ) {
    let Some(player_udp) = player.udp_socket else {
@@ -237,21 +482,31 @@ async fn send_player_updates(
    // Send other players' positions
    let other_players_futures = all_players
       .values()
-      .filter(|&ps| ps.id != player_id && !ps.is_dead)
+      .filter(|&ps| ps.id != player_id && !ps.is_dead && player.can_see(ps))
       .map(|ps| {
          udp_socket.send_msg_and_log_(
             UdpServerMsg::OtherPlayer {
+               tick,
                username: ps.username.clone(),
                location: ps.location,
                direction: ps.direction,
+               guild: ps.guild.clone(),
+               skull: ps.skull,
+               hp_percent: (ps.hp * 100 / ps.max_hp.max(1)).min(100) as u8,
             },
             Some(player_udp),
          )
       });
    join_all(other_players_futures).await;
 
-   // Send game objects
-   let objects = UdpServerMsg::Objects(game_objects.clone());
+   // Send the game objects on the player's screen, which keeps the snapshot
+   // well within a datagram however big the map grows
+   let mut objects = game_objects.clone();
+   corpses.show_in(&mut objects);
+   objects
+      .0
+      .retain(|&location, _| is_on_screen(player.location, location));
+   let objects = UdpServerMsg::Objects { tick, objects };
    udp_socket
       .send_msg_and_log_(objects, Some(player_udp))
       .await;
@@ -260,17 +515,42 @@ async fn send_player_updates(
 // ================ Main Game Loop ================
 
 async fn process_game_tick(
+   tick: u64,
    udp_socket: &Arc<UdpSocket>,
-   players: &Arc<Mutex<HashMap<Uuid, Player>>>,
-   game_objects: &Arc<Mutex<GameObjects>>,
-   mmo_map: &Arc<Mutex<MmoMap>>,
+   state: &ServerState,
+   spawns: &mut Spawns,
 ) -> Result<()> {
-   let mut players_guard = players.lock().await;
-   let mut game_objects = game_objects.lock().await;
+   let mut players_guard = state.players.lock().await;
+   let mut game_objects = state.game_objects.lock().await;
+   let parties = state.parties.lock().await;
+   let mut corpses = state.corpses.lock().await;
+   let mut death_history = state.death_history.lock().await;
+   let mut progress = state.progress.lock().await;
+
+   corpses.rot();
+   spawns.respawn_creatures(
+      &state.creatures,
+      &mut game_objects,
+      &mut *state.mmo_map.lock().await,
+      &players_guard,
+   );
 
    let player_ids: Vec<Uuid> = players_guard.keys().copied().collect();
+   let player_locations: Vec<Location> = players_guard.values().map(|p| p.location).collect();
+   let mut projectiles = vec![];
 
    for player_id in player_ids {
+      process_player_attack(
+         player_id,
+         &mut players_guard,
+         &mut game_objects,
+         udp_socket,
+         state,
+         &parties,
+         &mut projectiles,
+      )
+      .await;
+
       // Process monster AI for this player
       {
          let player = match players_guard.get_mut(&player_id) {
@@ -278,11 +558,24 @@ async fn process_game_tick(
             None => continue,
          };
 
-         let Some(player_udp) = player.udp_socket else {
+         if player.udp_socket.is_none() {
             continue;
-         };
+         }
+
+         death::handle_death(player, &mut corpses, &mut death_history, &mut progress).await;
+         pvp::expire_skull(player).await;
+         conditions::process_conditions(player, udp_socket).await;
+         regeneration::regenerate(player).await;
 
-         process_monster_ai(player, &mut game_objects, mmo_map, udp_socket, player_udp).await?;
+         process_monster_ai(
+            player,
+            &mut game_objects,
+            state,
+            udp_socket,
+            &player_locations,
+            &mut projectiles,
+         )
+         .await?;
       }
 
       // Send updates to this player
@@ -292,30 +585,40 @@ async fn process_game_tick(
             None => continue,
          };
 
-         send_player_updates(player_id, player, &players_guard, &game_objects, udp_socket).await;
+         send_player_updates(
+            tick,
+            player_id,
+            player,
+            &players_guard,
+            &game_objects,
+            &corpses,
+            udp_socket,
+         )
+         .await;
       }
    }
 
+   combat::broadcast_projectiles(&projectiles, &players_guard, udp_socket).await;
+
    Ok(())
 }
 
 /// This task should never finish. If it does, it must be an error.
-pub fn game_loop_task(
-   udp_socket: Arc<UdpSocket>,
-   players: Arc<Mutex<HashMap<Uuid, Player>>>,
-   game_objects: Arc<Mutex<GameObjects>>,
-   mmo_map: Arc<Mutex<MmoMap>>,
-) -> JoinHandle<Result<()>> {
+pub fn game_loop_task(udp_socket: Arc<UdpSocket>, state: ServerState) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
-      let mut interval = tokio::time::interval(Duration::from_millis(SERVER_TICK_RATE));
+      let mut interval = tokio::time::interval(Duration::from_millis(state.config.tick_rate_ms));
+      let mut spawns = Spawns::from_game_objects(&*state.game_objects.lock().await);
+
+      let mut tick: u64 = 0;
 
       loop {
          interval.tick().await;
 
-         if let Err(e) = process_game_tick(&udp_socket, &players, &game_objects, &mmo_map).await {
+         if let Err(e) = process_game_tick(tick, &udp_socket, &state, &mut spawns).await {
             error!("Game tick failed: {}", e);
             return Err(e);
          }
+         tick += 1;
       }
    })
 }
@@ -1,69 +1,52 @@
-use crate::{Player, Sc, ServerChannel, spawn_manager::generate_spawn_location};
-use anyhow::Result;
-use futures::future::join_all;
-use shared::{
-   Direction, GameObjects,
-   network::{tcp::*, udp::*},
+use crate::{
+   Player, Sc, ServerChannel, ServerState, chat, combat, commands, guild, interaction, movement,
+   party, regeneration,
+   spawn_manager::{generate_spawn_location, temple_spawn_location},
+   spells, temple, trade, vip,
 };
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
-use thin_logger::log::{debug, error, info, trace};
+use anyhow::Result;
+use shared::network::{tcp::*, udp::*};
+use std::sync::Arc;
+use thin_logger::log::{debug, error, info};
 use tokio::{
    io::AsyncWriteExt,
    net::UdpSocket,
-   sync::{Mutex, mpsc::UnboundedReceiver},
+   sync::mpsc::{UnboundedReceiver, UnboundedSender},
    task::JoinHandle,
 };
-use uuid::Uuid;
 
 pub fn sc_rx_task(
    mut sc_rx: UnboundedReceiver<ServerChannel>,
+   sc_tx: UnboundedSender<ServerChannel>,
    udp_socket: Arc<UdpSocket>,
-   address_mapping: Arc<Mutex<HashMap<SocketAddr, Uuid>>>,
-   players: Arc<Mutex<HashMap<Uuid, Player>>>,
-   game_objects: Arc<Mutex<GameObjects>>,
+   state: ServerState,
 ) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
+      let ServerState {
+         players,
+         address_mapping,
+         game_objects,
+         trades,
+         channels,
+         vip,
+         parties,
+         guilds,
+         ..
+      } = state.clone();
+
       while let Some(ps) = sc_rx.recv().await {
          let player_id = ps.id;
          match ps.msg {
             Sc::PlayerMove {
                client_request_id,
-               location,
+               step,
             } => {
                let mut players = players.lock().await;
-               let player = players.get_mut(&player_id).unwrap();
-
-               if client_request_id <= player.client_request_id {
-                  trace!("received outdated player move from {}", player.username);
-                  continue;
-               }
-
-               if location == player.location {
-                  trace!("player {} did not move. skipping.", player.username);
-                  continue;
-               }
-
-               let (old_x, old_y, _) = player.location;
-               let (new_x, new_y, _) = location;
-               if new_x > old_x {
-                  player.direction = Direction::East;
-               } else if new_x < old_x {
-                  player.direction = Direction::West;
-               } else if new_y > old_y {
-                  player.direction = Direction::South;
-               } else if new_y < old_y {
-                  player.direction = Direction::North;
-               }
-
-               debug!(
-                  "received player move from {} at {:?}",
-                  player.username, location
-               );
+               movement::handle_step(player_id, client_request_id, step, &mut players, &state)
+                  .await;
 
-               debug!("player direction is: {:?}", player.direction);
-
-               player.client_request_id = client_request_id;
-               player.location = location;
+               let mut trades = trades.lock().await;
+               trade::cancel_trade_if_apart(player_id, &mut players, &mut trades).await;
             }
             Sc::MoveObject { from, to } => {
                let mut game_objects = game_objects.lock().await;
@@ -72,10 +55,28 @@ pub fn sc_rx_task(
                   game_objects.0.insert(to, obj);
                }
             }
+            Sc::Login => {
+               let mut players = players.lock().await;
+               let vip = vip.lock().await;
+               vip::handle_login(player_id, &mut players, &vip).await;
+               drop(vip);
+               let guilds = guilds.lock().await;
+               guild::handle_login(player_id, &mut players, &guilds).await;
+               drop(guilds);
+               let home_towns = state.home_towns.lock().await;
+               temple::handle_login(player_id, &mut players, &home_towns);
+               spells::handle_login(player_id, &mut players, &state.spells).await;
+            }
             Sc::Disconnect => {
                info!("{player_id} disconnected");
 
                let mut players = players.lock().await;
+
+               // done first so the party can still be told who left
+               let mut parties = parties.lock().await;
+               party::leave_party(player_id, &mut players, &mut parties).await;
+               drop(parties);
+
                let player = players.get(&player_id);
 
                let Some(player) = player else {
@@ -90,34 +91,56 @@ pub fn sc_rx_task(
                let _maybe_uuid = player
                   .udp_socket
                   .and_then(|udp_socket| address_mapping.remove(&udp_socket));
-               let _maybe_player = players.remove(&player_id);
+               let maybe_player = players.remove(&player_id);
+
+               if let Some(player) = maybe_player {
+                  state.progress.lock().await.record(&player);
+
+                  let reason = format!("{} logged out", player.username);
+                  let mut trades = trades.lock().await;
+                  trade::cancel_trade(player_id, &reason, &mut players, &mut trades).await;
+
+                  let vip = vip.lock().await;
+                  let watchers = vip::watchers_of(&player, &players, &vip);
+                  vip::notify_watchers(&player.username, false, &watchers, &mut players).await;
+               }
+
+               channels.lock().await.leave_all(player_id);
             }
             Sc::ChatMsg(msg) => {
                debug!("received chat msg: \"{msg}\" from: {player_id}");
 
                let mut players = players.lock().await;
-               let username = players.get(&player_id).unwrap().username.clone();
-
-               // construct the message for everyone
-               let chat_msg = TcpServerMsg::ChatMsg {
-                  username: username.clone(),
-                  msg,
-               };
-               let s_chat_msg = bincode::serialize(&chat_msg)?;
-
-               let futures = players.values_mut().filter_map(|p| {
-                  if p.username == username {
-                     None
-                  } else {
-                     Some(p.tcp_tx.write_all(&s_chat_msg))
-                  }
-               });
-
-               for res in join_all(futures).await {
-                  if let Err(e) = res {
-                     error!("failed to send chat message: {}", e);
+               if let Some(spell) = state.spells.find(&msg) {
+                  let cast =
+                     spells::cast(player_id, spell, &mut players, &state, &udp_socket).await;
+                  // the words are still said out loud, unless the caster is muted
+                  let muted = players.get(&player_id).and_then(Player::mute_remaining);
+                  if cast && muted.is_none() {
+                     chat::say_spell_words(player_id, msg, &mut players).await;
                   }
+                  continue;
                }
+               chat::handle_chat_msg(player_id, msg, &mut players).await;
+            }
+            Sc::ChannelMsg { channel, msg } => {
+               let mut players = players.lock().await;
+               let channels = channels.lock().await;
+               chat::handle_channel_msg(player_id, channel, msg, &mut players, &channels).await;
+            }
+            Sc::JoinChannel(channel) => {
+               let mut players = players.lock().await;
+               let mut channels = channels.lock().await;
+               chat::handle_join_channel(player_id, channel, &mut players, &mut channels).await;
+            }
+            Sc::LeaveChannel(channel) => {
+               let mut players = players.lock().await;
+               let mut channels = channels.lock().await;
+               chat::handle_leave_channel(player_id, channel, &mut players, &mut channels).await;
+            }
+            Sc::Command(command) => {
+               debug!("{player_id} runs {command:?}");
+               commands::execute_command(player_id, command, &state, &udp_socket, &sc_tx).await;
             }
             Sc::Ping(ping_id) => {
                let tcp_socket_addr = (players.lock().await).get(&player_id).unwrap().tcp_socket;
@@ -129,11 +152,20 @@ pub fn sc_rx_task(
                }
             }
             Sc::Respawn => {
+               // only the dead come back, or it would be a free escape, and only
+               // once their death is dealt with so the corpse lies where they fell
+               let home_town = match (players.lock().await).get(&player_id) {
+                  Some(p) if p.is_dead && p.pending_death.is_none() => p.home_town.clone(),
+                  _ => continue,
+               };
                info!("Player {} is respawning", player_id);
 
-               // Use the shared spawn location generation logic
-               let spawn_location =
-                  generate_spawn_location(players.clone(), game_objects.clone()).await;
+               let spawn_location = match state.temples.of_town(home_town.as_deref()) {
+                  Some(temple) => {
+                     temple_spawn_location(temple, players.clone(), game_objects.clone()).await
+                  }
+                  None => generate_spawn_location(players.clone(), game_objects.clone()).await,
+               };
 
                let mut players = players.lock().await;
                if let Some(player) = players.get_mut(&player_id) {
@@ -143,12 +175,19 @@ pub fn sc_rx_task(
                   );
 
                   player.hp = player.max_hp;
+                  player.mana = player.max_mana;
                   player.location = spawn_location;
                   player.is_dead = false;
+                  player
+                     .send_tcp_msg(&TcpServerMsg::Stats(player.stats()))
+                     .await;
 
                   // Send respawn confirmation via TCP
-                  let respawn_ok = TcpServerMsg::RespawnOk;
-                  if let Ok(serialized) = bincode::serialize(&respawn_ok)
+                  let respawn_ok = TcpServerMsg::RespawnOk {
+                     hp: player.hp,
+                     location: player.location,
+                  };
+                  if let Ok(serialized) = encode(&respawn_ok)
                      && player.tcp_tx.write_all(&serialized).await.is_err()
                   {
                      error!(
@@ -175,6 +214,69 @@ pub fn sc_rx_task(
                   error!("Player {} not found when attempting to respawn", player_id);
                }
             }
+            Sc::TradeOffer { to, items } => {
+               let mut players = players.lock().await;
+               let mut trades = trades.lock().await;
+               trade::handle_trade_offer(player_id, to, items, &mut players, &mut trades).await;
+            }
+            Sc::TradeAccept => {
+               let mut players = players.lock().await;
+               let mut trades = trades.lock().await;
+               trade::handle_trade_accept(player_id, &mut players, &mut trades).await;
+            }
+            Sc::TradeCancel => {
+               let mut players = players.lock().await;
+               let mut trades = trades.lock().await;
+               trade::cancel_trade(
+                  player_id,
+                  "the trade was declined",
+                  &mut players,
+                  &mut trades,
+               )
+               .await;
+            }
+            Sc::Attack(target) => {
+               if let Some(player) = players.lock().await.get_mut(&player_id) {
+                  debug!("{} attacks {target:?}", player.username);
+                  player.attack_target = target;
+               }
+            }
+            Sc::PartyMsg(msg) => {
+               let mut players = players.lock().await;
+               let parties = parties.lock().await;
+               party::handle_party_msg(player_id, msg, &mut players, &parties).await;
+            }
+            Sc::GuildMsg(msg) => {
+               let mut players = players.lock().await;
+               let guilds = guilds.lock().await;
+               guild::handle_guild_msg(player_id, msg, &mut players, &guilds).await;
+            }
+            Sc::UseItem(kind) => {
+               if let Some(player) = players.lock().await.get_mut(&player_id) {
+                  match kind.weapon() {
+                     Some(_) => combat::wield(player, kind).await,
+                     None => regeneration::eat(player, kind).await,
+                  }
+               }
+            }
+            Sc::Look(location) => {
+               let mut players = players.lock().await;
+               interaction::handle_look(player_id, location, &mut players, &state).await;
+            }
+            Sc::Use(location) => {
+               let mut players = players.lock().await;
+               interaction::handle_use(player_id, location, &mut players, &state).await;
+            }
+            Sc::VipAdd(name) => {
+               let mut players = players.lock().await;
+               let mut vip = vip.lock().await;
+               vip::handle_vip_add(player_id, name, &mut players, &mut vip).await;
+            }
+            Sc::VipRemove(name) => {
+               let mut players = players.lock().await;
+               let mut vip = vip.lock().await;
+               vip::handle_vip_remove(player_id, name, &mut players, &mut vip).await;
+            }
          }
       }
       Ok(())
@@ -1,12 +1,25 @@
 use super::Players;
-use crate::{Player, Sc, ServerChannel, spawn_manager::generate_spawn_location};
+use crate::{
+   Player, Sc, ServerChannel, ServerState,
+   account::{self, Accounts},
+   moderation::Moderation,
+   spawn_manager::generate_spawn_location,
+};
 use anyhow::{Context, Result, bail};
-use shared::{Direction, GameObjects, InitPlayer, network::tcp::*};
-use std::{collections::HashMap, net::SocketAddr, sync::Arc};
+use shared::{Direction, InitPlayer, max_hp_for_level, max_mana_for_level, network::tcp::*};
+use std::{
+   collections::{HashMap, HashSet},
+   net::SocketAddr,
+   sync::Arc,
+   time::Instant,
+};
 use thin_logger::log::{debug, error, info, trace, warn};
 use tokio::{
-   io::{AsyncReadExt, AsyncWriteExt},
-   net::{TcpListener, TcpStream, tcp::OwnedReadHalf},
+   io::AsyncWriteExt,
+   net::{
+      TcpListener, TcpStream,
+      tcp::{OwnedReadHalf, OwnedWriteHalf},
+   },
    sync::{Mutex, mpsc::UnboundedSender},
    task::JoinHandle,
 };
@@ -15,10 +28,8 @@ use uuid::Uuid;
 
 pub fn tcp_listener_task(
    tcp_listener: TcpListener,
-   players: Players,
-   address_mapping: Arc<Mutex<HashMap<SocketAddr, Uuid>>>,
    sc_tx: UnboundedSender<ServerChannel>,
-   game_objects: Arc<Mutex<GameObjects>>,
+   state: ServerState,
 ) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
       let mut iter = TcpListenerStream::new(tcp_listener);
@@ -29,33 +40,42 @@ pub fn tcp_listener_task(
             tcp_stream.peer_addr()
          );
 
-         handle_tcp_stream(
-            tcp_stream,
-            players.clone(),
-            address_mapping.clone(),
-            sc_tx.clone(),
-            game_objects.clone(),
-         );
+         handle_tcp_stream(tcp_stream, sc_tx.clone(), state.clone());
       }
 
       Ok(())
    })
 }
 
-fn handle_tcp_stream(
-   mut stream: TcpStream,
-   players: Players,
-   address_mapping: Arc<Mutex<HashMap<SocketAddr, Uuid>>>,
-   sc_tx: UnboundedSender<ServerChannel>,
-   game_objects: Arc<Mutex<GameObjects>>,
-) {
+fn handle_tcp_stream(stream: TcpStream, sc_tx: UnboundedSender<ServerChannel>, state: ServerState) {
    // this task does not block the server and it can continue
    // accepting new connections.
    tokio::spawn(async move {
+      let ServerState {
+         players,
+         address_mapping,
+         game_objects,
+         moderation,
+         accounts,
+         progress,
+         config,
+         ..
+      } = state;
       let user_address = stream.peer_addr().expect("expect to have the user address");
+      let (tcp_read, mut tcp_write) = stream.into_split();
+      let mut tcp_read = msg_reader(tcp_read);
 
       // if it fails to do so (auth) this task will be exited
-      let auth_type = match authenticate_tcp_client(&mut stream, players.clone()).await {
+      let auth_type = match authenticate_tcp_client(
+         &mut tcp_read,
+         &mut tcp_write,
+         user_address,
+         players.clone(),
+         &moderation,
+         &accounts,
+      )
+      .await
+      {
          Ok(u) => u,
          Err(e) => {
             error!("failed to authenticate {user_address}: {e}");
@@ -64,20 +84,24 @@ fn handle_tcp_stream(
       };
 
       if let AuthType::Connection(username) = auth_type {
-         let (tcp_read, mut tcp_write) = stream.into_split();
-
+         let progress = progress.lock().await.get(&username);
          let init_player = InitPlayer {
             id: Uuid::new_v4(),
             username: username.clone(),
             location: generate_spawn_location(players.clone(), game_objects.clone()).await,
             z_level: 0, // Start on ground floor
-            hp: 100,
-            max_hp: 100,
-            level: 1,
+            hp: max_hp_for_level(progress.level),
+            max_hp: max_hp_for_level(progress.level),
+            mana: max_mana_for_level(progress.level),
+            max_mana: max_mana_for_level(progress.level),
+            level: progress.level,
             direction: Direction::South,
+            experience: progress.experience,
+            inventory: progress.inventory,
+            tick_rate_ms: config.tick_rate_ms,
          };
 
-         let ser = bincode::serialize(&TcpServerMsg::InitOk(init_player.clone())).unwrap();
+         let ser = encode(&TcpServerMsg::InitOk(init_player.clone())).unwrap();
          if tcp_write.write_all(&ser).await.is_err() {
             error!("failed to send init ok to user: {username}");
             return;
@@ -87,12 +111,37 @@ fn handle_tcp_stream(
             id: init_player.id,
             username: username.clone(),
             client_request_id: 0,
+            next_step_at: Instant::now(),
             location: init_player.location,
             hp: init_player.hp,
             max_hp: init_player.max_hp,
+            mana: init_player.mana,
+            max_mana: init_player.max_mana,
             level: init_player.level,
+            experience: init_player.experience,
             direction: init_player.direction,
             is_dead: false,
+            recent_damage: vec![],
+            pending_death: None,
+            blessings: progress.blessings,
+            home_town: None,
+            inventory: init_player.inventory.clone(),
+            ignored: HashSet::new(),
+            role: moderation.lock().await.role_of(&username),
+            is_ghost: false,
+            muted_until: None,
+            guild: None,
+            attack_target: None,
+            weapon: None,
+            last_attack: Instant::now(),
+            skull: None,
+            skull_until: None,
+            frags: vec![],
+            spell_cooldowns: HashMap::new(),
+            conditions: vec![],
+            in_combat_until: None,
+            fed_until: None,
+            next_regen: Instant::now(),
             tcp_tx: tcp_write,
             tcp_socket: user_address,
             udp_socket: None,
@@ -128,13 +177,17 @@ fn handle_tcp_stream(
          }
          drop(players_lock);
 
+         _ = sc_tx.send(ServerChannel {
+            id: init_player.id,
+            msg: Sc::Login,
+         });
+
          // set up tcp reader
          setup_tcp_reader(tcp_read, sc_tx.clone(), address_mapping.clone());
       } else if let AuthType::Reconnection(uuid) = auth_type {
          debug!("reconnecting player: {uuid}");
-         let (tcp_read, mut tcp_write) = stream.into_split();
 
-         let ser = bincode::serialize(&TcpServerMsg::ReconnectOk).unwrap();
+         let ser = encode(&TcpServerMsg::ReconnectOk).unwrap();
          if tcp_write.write_all(&ser).await.is_err() {
             error!("failed to send reconnect ok to user");
             return;
@@ -159,74 +212,132 @@ enum AuthType {
    Connection(String),
 }
 
-async fn authenticate_tcp_client(tcp_stream: &mut TcpStream, players: Players) -> Result<AuthType> {
-   let mut buf = [0; 1024];
-
-   let size = tcp_stream.read(&mut buf).await?;
-   let c_msg: TcpClientMsg = bincode::deserialize(&buf[..size])?;
+/// Answers logins and character creation until the client enters the game
+/// with one of its characters, or reconnects.
+async fn authenticate_tcp_client(
+   tcp_read: &mut MsgReader<OwnedReadHalf>,
+   tcp_write: &mut OwnedWriteHalf,
+   address: SocketAddr,
+   players: Players,
+   moderation: &Mutex<Moderation>,
+   accounts: &Mutex<Accounts>,
+) -> Result<AuthType> {
+   let mut account = None;
+
+   loop {
+      let Some(c_msg) = read_msg(tcp_read).await? else {
+         bail!("the client left before entering the game");
+      };
 
-   let username = match c_msg {
-      TcpClientMsg::Init(username) => username,
-      TcpClientMsg::Reconnect(uuid) => {
-         let mut players = players.lock().await;
-         let Some(p) = players.get_mut(&uuid) else {
-            bail!("player does not exist");
-         };
-         p.tcp_socket = tcp_stream.peer_addr()?;
-         return Ok(AuthType::Reconnection(uuid));
-      }
-      _ => bail!("invalid client message"),
-   };
+      let reply = match c_msg {
+         TcpClientMsg::Reconnect(uuid) => {
+            let mut players = players.lock().await;
+            let Some(p) = players.get_mut(&uuid) else {
+               bail!("player does not exist");
+            };
+            p.tcp_socket = address;
+            return Ok(AuthType::Reconnection(uuid));
+         }
+         TcpClientMsg::Login {
+            account: name,
+            password,
+         } => {
+            let result = account::log_in(accounts, &name, &password).await;
+            if result.is_ok() {
+               info!("account {name} logged in");
+               account = Some(name);
+            }
+            result
+         }
+         TcpClientMsg::CreateCharacter(name) => match &account {
+            Some(account) => accounts.lock().await.create_character(account, &name),
+            None => Err("Log in first.".to_string()),
+         },
+         TcpClientMsg::Init(username) => {
+            let owned = match &account {
+               Some(account) => accounts.lock().await.owns(account, &username),
+               None => false,
+            };
+            match can_enter(&username, owned, &players, moderation).await {
+               Ok(()) => return Ok(AuthType::Connection(username)),
+               Err(e) => Err(e),
+            }
+         }
+         _ => bail!("invalid client message"),
+      };
 
-   info!("submitted username is: {}", username);
+      let msg = match reply {
+         Ok(characters) => TcpServerMsg::Characters(characters),
+         Err(e) => {
+            info!("{e}");
+            TcpServerMsg::InitErr(e)
+         }
+      };
+      tcp_write.write_all(&encode(&msg)?).await?;
+   }
+}
 
-   let is_username_taken = (players.lock().await)
+/// Tells why `username` cannot enter the game, if it cannot.
+async fn can_enter(
+   username: &str,
+   owned: bool,
+   players: &Players,
+   moderation: &Mutex<Moderation>,
+) -> Result<(), String> {
+   if !owned {
+      return Err(format!("{username} is not one of your characters."));
+   }
+   if moderation.lock().await.is_banned(username) {
+      return Err(format!("{username} is banned."));
+   }
+   let is_online = (players.lock().await)
       .values()
       .any(|p| p.username == username);
-
-   if is_username_taken {
-      let str = format!("username: {} is taken.", username);
-
-      info!("{}", str);
-
-      // send error to client
-      let msg = TcpServerMsg::InitErr(str);
-      let s_msg = bincode::serialize(&msg).unwrap();
-      _ = tcp_stream.write(&s_msg).await;
-
-      bail!("username: {username} is taken.");
+   if is_online {
+      return Err(format!("{username} is already online."));
    }
-
-   Ok(AuthType::Connection(username))
+   Ok(())
 }
 
 /// Spins up a task to listen to incoming TCP messages
 /// and relays them to the server channel.
 fn setup_tcp_reader(
-   mut tcp_read: OwnedReadHalf,
+   mut tcp_read: MsgReader<OwnedReadHalf>,
    sc_tx: UnboundedSender<ServerChannel>,
    address_mapping: Arc<Mutex<HashMap<SocketAddr, Uuid>>>,
 ) {
    tokio::spawn(async move {
-      let peer_addr = tcp_read.peer_addr().unwrap();
-      let mut buffer = [0; 1024];
+      let peer_addr = tcp_read.get_ref().peer_addr().unwrap();
       loop {
-         match tcp_read.read(&mut buffer).await {
-            Ok(n) if n > 0 => {
-               let Ok(msg) = bincode::deserialize::<TcpClientMsg>(&buffer[..n]) else {
-                  error!("could not deserialize msg from client. closing connection.");
-                  break;
-               };
-
+         match read_msg::<TcpClientMsg, _>(&mut tcp_read).await {
+            Ok(Some(msg)) => {
                trace!("received TCP msg from {peer_addr:?}");
 
-               let user_id = *address_mapping.lock().await.get(&peer_addr).unwrap();
+               // kicked players lose their mapping before their client notices
+               let Some(user_id) = address_mapping.lock().await.get(&peer_addr).copied() else {
+                  break;
+               };
 
                let sc = match msg {
                   TcpClientMsg::ChatMsg(m) => Sc::ChatMsg(m),
+                  TcpClientMsg::ChannelMsg { channel, msg } => Sc::ChannelMsg { channel, msg },
+                  TcpClientMsg::JoinChannel(channel) => Sc::JoinChannel(channel),
+                  TcpClientMsg::LeaveChannel(channel) => Sc::LeaveChannel(channel),
+                  TcpClientMsg::Command(command) => Sc::Command(command),
                   TcpClientMsg::Disconnect => Sc::Disconnect,
                   TcpClientMsg::Ping(p_id) => Sc::Ping(p_id),
                   TcpClientMsg::Respawn(_) => Sc::Respawn,
+                  TcpClientMsg::TradeOffer { to, items } => Sc::TradeOffer { to, items },
+                  TcpClientMsg::TradeAccept => Sc::TradeAccept,
+                  TcpClientMsg::TradeCancel => Sc::TradeCancel,
+                  TcpClientMsg::VipAdd(name) => Sc::VipAdd(name),
+                  TcpClientMsg::VipRemove(name) => Sc::VipRemove(name),
+                  TcpClientMsg::Attack(target) => Sc::Attack(target),
+                  TcpClientMsg::PartyMsg(msg) => Sc::PartyMsg(msg),
+                  TcpClientMsg::GuildMsg(msg) => Sc::GuildMsg(msg),
+                  TcpClientMsg::UseItem(kind) => Sc::UseItem(kind),
+                  TcpClientMsg::Look(location) => Sc::Look(location),
+                  TcpClientMsg::Use(location) => Sc::Use(location),
                   _ => {
                      warn!("unwanted msg: {msg:?}. skipping...");
                      continue;
@@ -240,8 +351,12 @@ fn setup_tcp_reader(
 
                _ = sc_tx.send(sc);
             }
-            _ => {
-               info!("{:?} closed TCP connnection or tcp read failed.", peer_addr);
+            Err(e) => {
+               error!("could not read msg from {peer_addr:?}: {e}. closing connection.");
+               break;
+            }
+            Ok(None) => {
+               info!("{:?} closed TCP connnection.", peer_addr);
 
                let Some(user_id) = address_mapping.lock().await.get(&peer_addr).copied() else {
                   break;
@@ -253,6 +368,7 @@ fn setup_tcp_reader(
                };
 
                _ = sc_tx.send(disconnect);
+               break;
             }
          }
       }
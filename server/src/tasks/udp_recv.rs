@@ -1,6 +1,6 @@
 use crate::{Player, Sc, ServerChannel};
 use anyhow::Result;
-use shared::network::udp::UdpClientMsg;
+use shared::network::udp::{MAX_DATAGRAM_SIZE, UdpClientMsg};
 use std::{collections::HashMap, net::SocketAddr, sync::Arc};
 use thin_logger::log::{debug, error};
 use tokio::{
@@ -17,7 +17,7 @@ pub fn udp_recv_task(
    players: Arc<Mutex<HashMap<Uuid, Player>>>,
 ) -> JoinHandle<Result<()>> {
    tokio::spawn(async move {
-      let mut buf = [0; 1024];
+      let mut buf = vec![0; MAX_DATAGRAM_SIZE];
       while let Ok((size, src)) = udp_socket.recv_from(&mut buf).await {
          let Ok(msg) = bincode::deserialize::<UdpClientMsg>(&buf[..size]) else {
             debug!("failed to deserialize UDP message from: {src}");
@@ -41,11 +41,11 @@ pub fn udp_recv_task(
             } => Sc::Ping(client_request_id),
             UdpClientMsg::PlayerMove {
                client_request_id,
-               location,
+               step,
                ..
             } => Sc::PlayerMove {
                client_request_id,
-               location,
+               step,
             },
             UdpClientMsg::MoveObject { from, to, .. } => Sc::MoveObject { from, to },
          };
@@ -0,0 +1,50 @@
+use crate::{Player, storage};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shared::{Temples, network::tcp::TcpServerMsg};
+use std::collections::{BTreeMap, HashMap};
+use thin_logger::log::{error, info};
+use uuid::Uuid;
+
+const HOME_TOWNS_PATH: &str = "data/home_towns.json";
+
+/// Every character's home town, saved to disk on every change. Characters
+/// without one respawn in the first temple of the map.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct HomeTowns(pub BTreeMap<String, String>); // username -> town
+
+impl HomeTowns {
+   pub fn load() -> Result<HomeTowns> {
+      storage::load_json(HOME_TOWNS_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(HOME_TOWNS_PATH, self) {
+         error!("failed to save home towns: {e:#}");
+      }
+   }
+}
+
+pub fn handle_login(player_id: Uuid, players: &mut HashMap<Uuid, Player>, homes: &HomeTowns) {
+   if let Some(player) = players.get_mut(&player_id) {
+      player.home_town = homes.0.get(&player.username).cloned();
+   }
+}
+
+/// Stepping into the temple of another town makes it the player's home town.
+pub async fn visit(player: &mut Player, temples: &Temples, homes: &mut HomeTowns) {
+   let Some(temple) = temples.at(player.location) else {
+      return;
+   };
+   if player.home_town.as_ref() == Some(&temple.town) {
+      return;
+   }
+
+   info!("{} is now a citizen of {}", player.username, temple.town);
+   player.home_town = Some(temple.town.clone());
+   homes.0.insert(player.username.clone(), temple.town.clone());
+   homes.save();
+
+   let msg = format!("You are now a citizen of {}.", temple.town);
+   player.send_tcp_msg(&TcpServerMsg::ServerMsg(msg)).await;
+}
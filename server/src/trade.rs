@@ -0,0 +1,368 @@
+use crate::Player;
+use shared::{Item, TradeWindow, is_adjacent, network::tcp::TcpServerMsg};
+use std::collections::HashMap;
+use thin_logger::log::{debug, info, warn};
+use uuid::Uuid;
+
+/// Two-phase trade: the initiator puts up an offer, the partner answers with
+/// theirs and the swap happens once both accepted the same pair of offers.
+#[derive(Debug)]
+pub struct Trade {
+   pub initiator: Uuid,
+   pub partner: Uuid,
+   pub initiator_offer: Vec<Item>,
+   pub partner_offer: Option<Vec<Item>>,
+   pub initiator_accepted: bool,
+   pub partner_accepted: bool,
+}
+
+impl Trade {
+   pub fn other(&self, player_id: Uuid) -> Uuid {
+      if player_id == self.initiator {
+         self.partner
+      } else {
+         self.initiator
+      }
+   }
+
+   fn window_for(&self, player_id: Uuid, partner: String) -> TradeWindow {
+      let initiator_offer = Some(self.initiator_offer.clone());
+      if player_id == self.initiator {
+         TradeWindow {
+            partner,
+            own_offer: initiator_offer,
+            partner_offer: self.partner_offer.clone(),
+            own_accepted: self.initiator_accepted,
+            partner_accepted: self.partner_accepted,
+         }
+      } else {
+         TradeWindow {
+            partner,
+            own_offer: self.partner_offer.clone(),
+            partner_offer: initiator_offer,
+            own_accepted: self.partner_accepted,
+            partner_accepted: self.initiator_accepted,
+         }
+      }
+   }
+}
+
+/// Ongoing trades. A player takes part in at most one at a time.
+#[derive(Debug, Default)]
+pub struct Trades(pub Vec<Trade>);
+
+impl Trades {
+   pub fn get(&self, player_id: Uuid) -> Option<&Trade> {
+      self
+         .0
+         .iter()
+         .find(|t| t.initiator == player_id || t.partner == player_id)
+   }
+
+   pub fn get_mut(&mut self, player_id: Uuid) -> Option<&mut Trade> {
+      self
+         .0
+         .iter_mut()
+         .find(|t| t.initiator == player_id || t.partner == player_id)
+   }
+
+   pub fn remove(&mut self, player_id: Uuid) -> Option<Trade> {
+      let idx = self
+         .0
+         .iter()
+         .position(|t| t.initiator == player_id || t.partner == player_id)?;
+      Some(self.0.swap_remove(idx))
+   }
+}
+
+pub async fn handle_trade_offer(
+   player_id: Uuid,
+   to: String,
+   items: Vec<Item>,
+   players: &mut HashMap<Uuid, Player>,
+   trades: &mut Trades,
+) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   let has_items = player.inventory.contains_all(&items);
+   // it would show up in the window without anything changing hands
+   let has_empty_stack = items.iter().any(|item| item.count == 0);
+   let location = player.location;
+
+   // the partner answering the request is the only offer allowed inside a trade.
+   // anything else means someone changed their offer.
+   if let Some(trade) = trades.get_mut(player_id) {
+      let answers_request = trade.partner == player_id
+         && trade.partner_offer.is_none()
+         && players
+            .get(&trade.initiator)
+            .is_some_and(|p| p.username == to);
+
+      if !answers_request || !has_items || has_empty_stack {
+         cancel_trade(player_id, "the offer was changed", players, trades).await;
+         return;
+      }
+
+      trade.partner_offer = Some(items);
+      send_trade_update(trade, players).await;
+      return;
+   }
+
+   let Some(partner) = players.values().find(|p| p.username == to) else {
+      close_trade_window(player_id, format!("{to} is not online."), players).await;
+      return;
+   };
+   let partner_id = partner.id;
+
+   let rejection = if partner_id == player_id {
+      Some("You cannot trade with yourself.".to_string())
+   } else if items.is_empty() {
+      Some("Select the items you want to offer first.".to_string())
+   } else if has_empty_stack {
+      Some("You cannot offer none of an item.".to_string())
+   } else if !has_items {
+      Some("You do not have the items you offered.".to_string())
+   } else if !is_adjacent(location, partner.location) {
+      Some(format!("You need to stand next to {to} to trade."))
+   } else if trades.get(partner_id).is_some() {
+      Some(format!("{to} is already trading."))
+   } else {
+      None
+   };
+
+   if let Some(reason) = rejection {
+      close_trade_window(player_id, reason, players).await;
+      return;
+   }
+
+   debug!("{player_id} opened a trade with {partner_id}");
+
+   let trade = Trade {
+      initiator: player_id,
+      partner: partner_id,
+      initiator_offer: items,
+      partner_offer: None,
+      initiator_accepted: false,
+      partner_accepted: false,
+   };
+   send_trade_update(&trade, players).await;
+   trades.0.push(trade);
+}
+
+pub async fn handle_trade_accept(
+   player_id: Uuid,
+   players: &mut HashMap<Uuid, Player>,
+   trades: &mut Trades,
+) {
+   let Some(trade) = trades.get_mut(player_id) else {
+      return;
+   };
+
+   if trade.partner_offer.is_none() {
+      debug!("{player_id} accepted a trade that has no counter offer yet");
+      return;
+   }
+
+   if player_id == trade.initiator {
+      trade.initiator_accepted = true;
+   } else {
+      trade.partner_accepted = true;
+   }
+
+   if !(trade.initiator_accepted && trade.partner_accepted) {
+      send_trade_update(trade, players).await;
+      return;
+   }
+
+   let trade = trades.remove(player_id).expect("trade exists");
+   complete_trade(trade, players).await;
+}
+
+pub async fn cancel_trade(
+   player_id: Uuid,
+   reason: &str,
+   players: &mut HashMap<Uuid, Player>,
+   trades: &mut Trades,
+) {
+   let Some(trade) = trades.remove(player_id) else {
+      return;
+   };
+
+   debug!(
+      "trade between {} and {} cancelled",
+      trade.initiator, trade.partner
+   );
+
+   for id in [trade.initiator, trade.partner] {
+      close_trade_window(id, format!("Trade cancelled: {reason}."), players).await;
+   }
+}
+
+/// Must be called whenever a player moves.
+pub async fn cancel_trade_if_apart(
+   player_id: Uuid,
+   players: &mut HashMap<Uuid, Player>,
+   trades: &mut Trades,
+) {
+   let Some(trade) = trades.get(player_id) else {
+      return;
+   };
+
+   let other_id = trade.other(player_id);
+   let still_adjacent = match (players.get(&player_id), players.get(&other_id)) {
+      (Some(a), Some(b)) => is_adjacent(a.location, b.location),
+      _ => false,
+   };
+
+   if !still_adjacent {
+      cancel_trade(player_id, "you moved too far apart", players, trades).await;
+   }
+}
+
+/// Swaps both offers. Nothing changes hands unless both sides still hold
+/// everything they offered.
+async fn complete_trade(trade: Trade, players: &mut HashMap<Uuid, Player>) {
+   let partner_offer = trade.partner_offer.unwrap_or_default();
+
+   let [Some(initiator), Some(partner)] =
+      players.get_disjoint_mut([&trade.initiator, &trade.partner])
+   else {
+      warn!("trade participant vanished before the swap");
+      return;
+   };
+
+   let msg = if initiator.inventory.contains_all(&trade.initiator_offer)
+      && partner.inventory.contains_all(&partner_offer)
+   {
+      initiator.inventory.remove_all(&trade.initiator_offer);
+      partner.inventory.remove_all(&partner_offer);
+
+      for item in &trade.initiator_offer {
+         partner.inventory.add(*item);
+      }
+      for item in &partner_offer {
+         initiator.inventory.add(*item);
+      }
+
+      info!(
+         "trade completed between {} and {}",
+         initiator.username, partner.username
+      );
+
+      for p in [&mut *initiator, &mut *partner] {
+         p.send_tcp_msg(&TcpServerMsg::Inventory(p.inventory.clone()))
+            .await;
+      }
+
+      "Trade completed.".to_string()
+   } else {
+      "Trade failed: the offered items are gone.".to_string()
+   };
+
+   for p in [initiator, partner] {
+      p.send_tcp_msg(&TcpServerMsg::TradeClosed(msg.clone()))
+         .await;
+   }
+}
+
+async fn send_trade_update(trade: &Trade, players: &mut HashMap<Uuid, Player>) {
+   let username = |id: Uuid| {
+      players
+         .get(&id)
+         .map(|p| p.username.clone())
+         .unwrap_or_default()
+   };
+   let initiator_name = username(trade.initiator);
+   let partner_name = username(trade.partner);
+
+   for (id, partner) in [
+      (trade.initiator, partner_name),
+      (trade.partner, initiator_name),
+   ] {
+      if let Some(p) = players.get_mut(&id) {
+         p.send_tcp_msg(&TcpServerMsg::TradeUpdate(trade.window_for(id, partner)))
+            .await;
+      }
+   }
+}
+
+async fn close_trade_window(player_id: Uuid, reason: String, players: &mut HashMap<Uuid, Player>) {
+   if let Some(p) = players.get_mut(&player_id) {
+      p.send_tcp_msg(&TcpServerMsg::TradeClosed(reason)).await;
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use crate::player::test_player;
+   use shared::{Inventory, ItemKind};
+
+   /// Two players next to each other, the first offering gold to the second.
+   async fn offered() -> (Uuid, Uuid, HashMap<Uuid, Player>, Trades) {
+      let a = test_player("a", (5, 5, 0)).await;
+      let b = test_player("b", (6, 5, 0)).await;
+      let (a_id, b_id) = (a.id, b.id);
+      let mut players = HashMap::from([(a_id, a), (b_id, b)]);
+      let mut trades = Trades::default();
+
+      let gold = vec![Item::new(ItemKind::GoldCoin, 40)];
+      handle_trade_offer(a_id, "b".to_string(), gold, &mut players, &mut trades).await;
+      (a_id, b_id, players, trades)
+   }
+
+   #[tokio::test]
+   async fn test_accept() {
+      let (a, b, mut players, mut trades) = offered().await;
+
+      // nothing to accept before the partner answered
+      handle_trade_accept(a, &mut players, &mut trades).await;
+      assert!(!trades.get(a).unwrap().initiator_accepted);
+
+      let meat = vec![Item::new(ItemKind::Meat, 2)];
+      handle_trade_offer(b, "a".to_string(), meat, &mut players, &mut trades).await;
+      handle_trade_accept(a, &mut players, &mut trades).await;
+      assert!(trades.get(b).is_some());
+      handle_trade_accept(b, &mut players, &mut trades).await;
+      assert!(trades.0.is_empty());
+
+      let inventory = |id| &players[&id].inventory;
+      assert_eq!(inventory(a).count(ItemKind::GoldCoin), 60);
+      assert_eq!(inventory(a).count(ItemKind::Meat), 5);
+      assert_eq!(inventory(b).count(ItemKind::GoldCoin), 140);
+      assert_eq!(inventory(b).count(ItemKind::Meat), 1);
+   }
+
+   #[tokio::test]
+   async fn test_cancel() {
+      let (a, b, mut players, mut trades) = offered().await;
+      let meat = vec![Item::new(ItemKind::Meat, 2)];
+      handle_trade_offer(b, "a".to_string(), meat, &mut players, &mut trades).await;
+      handle_trade_accept(a, &mut players, &mut trades).await;
+
+      cancel_trade(b, "b cancelled", &mut players, &mut trades).await;
+      assert!(trades.0.is_empty());
+      for id in [a, b] {
+         assert_eq!(players[&id].inventory, Inventory::starter());
+      }
+
+      // changing an offer cancels the trade too
+      let (a, _, mut players, mut trades) = offered().await;
+      let gold = vec![Item::new(ItemKind::GoldCoin, 1)];
+      handle_trade_offer(a, "b".to_string(), gold, &mut players, &mut trades).await;
+      assert!(trades.0.is_empty());
+   }
+
+   #[tokio::test]
+   async fn test_offer_rejected() {
+      let (a, b, mut players, mut trades) = offered().await;
+      let nothing = vec![Item::new(ItemKind::Meat, 0)];
+      handle_trade_offer(b, "a".to_string(), nothing, &mut players, &mut trades).await;
+      assert!(trades.get(a).is_none());
+
+      let too_much = vec![Item::new(ItemKind::Meat, 4)];
+      handle_trade_offer(b, "a".to_string(), too_much, &mut players, &mut trades).await;
+      assert!(trades.get(b).is_none());
+   }
+}
@@ -0,0 +1,151 @@
+use crate::{Player, chat::send_server_msg, storage};
+use anyhow::Result;
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use shared::{VipEntry, network::tcp::TcpServerMsg};
+use std::collections::{BTreeSet, HashMap};
+use thin_logger::log::error;
+use uuid::Uuid;
+
+const VIP_PATH: &str = "data/vip.json";
+const MAX_VIP_ENTRIES: usize = 50;
+
+/// Every character's VIP list, saved to disk on every change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VipLists(pub HashMap<String, BTreeSet<String>>); // username -> names on their list
+
+impl VipLists {
+   pub fn load() -> Result<VipLists> {
+      storage::load_json(VIP_PATH)
+   }
+
+   fn save(&self) {
+      if let Err(e) = storage::save_json(VIP_PATH, self) {
+         error!("failed to save VIP lists: {e:#}");
+      }
+   }
+
+   fn has(&self, owner: &str, name: &str) -> bool {
+      self.0.get(owner).is_some_and(|list| list.contains(name))
+   }
+}
+
+fn entries(owner: &Player, players: &HashMap<Uuid, Player>, vip: &VipLists) -> Vec<VipEntry> {
+   let Some(list) = vip.0.get(&owner.username) else {
+      return vec![];
+   };
+
+   list
+      .iter()
+      .map(|name| VipEntry {
+         name: name.clone(),
+         online: players
+            .values()
+            .any(|p| p.username == *name && owner.can_see(p)),
+      })
+      .collect()
+}
+
+async fn send_vip_list(player_id: Uuid, players: &mut HashMap<Uuid, Player>, vip: &VipLists) {
+   let Some(player) = players.get(&player_id) else {
+      return;
+   };
+   let msg = TcpServerMsg::VipList(entries(player, players, vip));
+
+   if let Some(player) = players.get_mut(&player_id) {
+      player.send_tcp_msg(&msg).await;
+   }
+}
+
+/// Everyone with `player` on their list who may see them. Ghosts only show
+/// up for game masters.
+pub fn watchers_of(player: &Player, players: &HashMap<Uuid, Player>, vip: &VipLists) -> Vec<Uuid> {
+   players
+      .values()
+      .filter(|p| vip.has(&p.username, &player.username) && p.can_see(player))
+      .map(|p| p.id)
+      .collect()
+}
+
+/// Tells the `watchers` of `username` that they logged in or out.
+pub async fn notify_watchers(
+   username: &str,
+   online: bool,
+   watchers: &[Uuid],
+   players: &mut HashMap<Uuid, Player>,
+) {
+   let msg = TcpServerMsg::VipStatus {
+      name: username.to_string(),
+      online,
+   };
+
+   let futures = players
+      .values_mut()
+      .filter(|p| watchers.contains(&p.id))
+      .map(|p| p.send_tcp_msg(&msg));
+   join_all(futures).await;
+}
+
+pub async fn handle_login(player_id: Uuid, players: &mut HashMap<Uuid, Player>, vip: &VipLists) {
+   send_vip_list(player_id, players, vip).await;
+
+   if let Some(player) = players.get(&player_id) {
+      let watchers = watchers_of(player, players, vip);
+      let username = player.username.clone();
+      notify_watchers(&username, true, &watchers, players).await;
+   }
+}
+
+pub async fn handle_vip_add(
+   player_id: Uuid,
+   name: String,
+   players: &mut HashMap<Uuid, Player>,
+   vip: &mut VipLists,
+) {
+   let Some(owner) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+
+   let name = name.trim().to_string();
+   let size = vip.0.get(&owner).map_or(0, BTreeSet::len);
+   let error = if name.is_empty() || name == owner {
+      Some("You cannot add that name to your VIP list.".to_string())
+   } else if vip.has(&owner, &name) {
+      Some(format!("{name} is already on your VIP list."))
+   } else if size >= MAX_VIP_ENTRIES {
+      Some(format!("Your VIP list is full ({MAX_VIP_ENTRIES} names)."))
+   } else {
+      None
+   };
+
+   if let Some(error) = error {
+      send_server_msg(player_id, error, players).await;
+      return;
+   }
+
+   vip.0.entry(owner).or_default().insert(name);
+   vip.save();
+   send_vip_list(player_id, players, vip).await;
+}
+
+pub async fn handle_vip_remove(
+   player_id: Uuid,
+   name: String,
+   players: &mut HashMap<Uuid, Player>,
+   vip: &mut VipLists,
+) {
+   let Some(owner) = players.get(&player_id).map(|p| p.username.clone()) else {
+      return;
+   };
+
+   let Some(list) = vip.0.get_mut(&owner) else {
+      return;
+   };
+   if list.remove(&name) {
+      if list.is_empty() {
+         vip.0.remove(&owner);
+      }
+      vip.save();
+   }
+   send_vip_list(player_id, players, vip).await;
+}
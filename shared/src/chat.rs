@@ -0,0 +1,105 @@
+use crate::{Location, constants::*};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum SpeechMode {
+   Whisper,
+   Say,
+   Yell,
+}
+
+impl SpeechMode {
+   /// Yelling carries across floors, everything else stays on the speaker's floor.
+   pub fn can_hear(&self, speaker: Location, listener: Location) -> bool {
+      let dx = speaker.0.abs_diff(listener.0);
+      let dy = speaker.1.abs_diff(listener.1);
+      let range = match self {
+         SpeechMode::Whisper => WHISPER_RANGE,
+         SpeechMode::Say => SAY_RANGE,
+         SpeechMode::Yell => YELL_RANGE,
+      };
+      (*self == SpeechMode::Yell || speaker.2 == listener.2) && dx <= range && dy <= range
+   }
+}
+
+/// Where a chat message belongs.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum ChatChannel {
+   /// Speech heard by the players around the speaker.
+   Local(SpeechMode),
+   /// A private conversation with the named player.
+   Private(String),
+   /// A channel players join and leave by name.
+   Named(String),
+   /// Everyone in the speaker's party.
+   Party,
+   /// Everyone online in the speaker's guild.
+   Guild,
+}
+
+/// What a line typed in the local chat means.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Speech {
+   Local { mode: SpeechMode, msg: String },
+   Private { to: String, msg: String },
+}
+
+/// `*name* text` is a private message, `#w text` whispers and `#y text` yells.
+/// Anything else is said out loud.
+pub fn parse_speech(text: &str) -> Speech {
+   let text = text.trim();
+
+   if let Some(rest) = text.strip_prefix('*')
+      && let Some((to, msg)) = rest.split_once('*')
+      && !to.trim().is_empty()
+   {
+      return Speech::Private {
+         to: to.trim().to_string(),
+         msg: msg.trim().to_string(),
+      };
+   }
+
+   let (mode, msg) = if let Some(msg) = text.strip_prefix("#w ") {
+      (SpeechMode::Whisper, msg.trim().to_string())
+   } else if let Some(msg) = text.strip_prefix("#y ") {
+      (SpeechMode::Yell, msg.trim().to_uppercase())
+   } else {
+      (SpeechMode::Say, text.to_string())
+   };
+
+   Speech::Local { mode, msg }
+}
+
+pub fn is_valid_channel_name(name: &str) -> bool {
+   (1..=20).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_parse_speech() {
+      assert_eq!(
+         parse_speech("*Bob* hi there"),
+         Speech::Private {
+            to: "Bob".to_string(),
+            msg: "hi there".to_string()
+         }
+      );
+      assert_eq!(
+         parse_speech("#y help"),
+         Speech::Local {
+            mode: SpeechMode::Yell,
+            msg: "HELP".to_string()
+         }
+      );
+      assert_eq!(
+         parse_speech("** not a name"),
+         Speech::Local {
+            mode: SpeechMode::Say,
+            msg: "** not a name".to_string()
+         }
+      );
+   }
+}
@@ -0,0 +1,622 @@
+use crate::Role;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+/// A slash command typed into the chat, already parsed and type checked.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Command {
+   PrivateMsg {
+      to: String,
+      msg: String,
+   },
+   Who,
+   Online,
+   Ignore(String),
+   Help(Option<String>),
+   Goto(String),
+   Teleport {
+      x: u32,
+      y: u32,
+      z: Option<u32>,
+   },
+   Summon {
+      monster: String,
+      count: u32,
+   },
+   Kick(String),
+   Ban(String),
+   Unban(String),
+   Mute {
+      name: String,
+      minutes: u32,
+   },
+   Broadcast(String),
+   SetHp {
+      name: String,
+      hp: u32,
+   },
+   SetLevel {
+      name: String,
+      level: u32,
+   },
+   Ghost,
+   SetRole {
+      name: String,
+      role: Role,
+   },
+   Party(PartyAction),
+   Guild(GuildAction),
+   Bless,
+   Loot,
+   /// The latest deaths of a character, the player's own by default.
+   Deaths(Option<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum PartyAction {
+   /// Invites a player, or takes the invitation back if they were invited already.
+   Invite(String),
+   Accept(String), // leader
+   Leave,
+   Kick(String),
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum GuildAction {
+   /// Lists the members of the player's guild.
+   Info,
+   Create(String),
+   Invite(String),
+   Join(String), // guild
+   Leave,
+   Kick(String),
+   Promote(String),
+   Demote(String),
+}
+
+impl Command {
+   /// The [`CommandSpec`] this command was parsed from.
+   pub fn spec(&self) -> Option<&'static CommandSpec> {
+      COMMANDS.iter().find(|spec| (spec.is)(self))
+   }
+}
+
+/// Describes one command. To add a command, add its variant to [`Command`],
+/// an entry to [`COMMANDS`] that parses and recognizes it, and handle it on
+/// the server.
+pub struct CommandSpec {
+   pub name: &'static str,
+   pub aliases: &'static [&'static str],
+   pub usage: &'static str,
+   pub description: &'static str,
+   pub min_role: Role,
+   parse: fn(&mut Args) -> Result<Command, ArgError>,
+   /// Whether a parsed command is this one.
+   is: fn(&Command) -> bool,
+}
+
+impl CommandSpec {
+   pub fn matches(&self, name: &str) -> bool {
+      self.name.eq_ignore_ascii_case(name)
+         || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+   }
+}
+
+pub const COMMANDS: &[CommandSpec] = &[
+   CommandSpec {
+      name: "w",
+      aliases: &["msg", "tell"],
+      usage: "/w <name> <message>",
+      description: "Sends a private message. Quote names that contain spaces.",
+      min_role: Role::Player,
+      parse: |args| {
+         Ok(Command::PrivateMsg {
+            to: args.word("name")?,
+            msg: args.rest("message")?,
+         })
+      },
+      is: |command| matches!(command, Command::PrivateMsg { .. }),
+   },
+   CommandSpec {
+      name: "who",
+      aliases: &[],
+      usage: "/who",
+      description: "Lists the players around you.",
+      min_role: Role::Player,
+      parse: |_| Ok(Command::Who),
+      is: |command| matches!(command, Command::Who),
+   },
+   CommandSpec {
+      name: "online",
+      aliases: &[],
+      usage: "/online",
+      description: "Lists everyone who is online.",
+      min_role: Role::Player,
+      parse: |_| Ok(Command::Online),
+      is: |command| matches!(command, Command::Online),
+   },
+   CommandSpec {
+      name: "ignore",
+      aliases: &[],
+      usage: "/ignore <name>",
+      description: "Stops or resumes showing messages from a player.",
+      min_role: Role::Player,
+      parse: |args| Ok(Command::Ignore(args.word("name")?)),
+      is: |command| matches!(command, Command::Ignore(_)),
+   },
+   CommandSpec {
+      name: "help",
+      aliases: &["?"],
+      usage: "/help [command]",
+      description: "Lists the commands or explains one of them.",
+      min_role: Role::Player,
+      parse: |args| Ok(Command::Help(args.opt_word())),
+      is: |command| matches!(command, Command::Help(_)),
+   },
+   CommandSpec {
+      name: "party",
+      aliases: &["p"],
+      usage: "/party <invite|accept|leave|kick> [name]",
+      description: "Invites players to your party, joins a party you were invited to, leaves your \
+                    party or kicks someone out of it.",
+      min_role: Role::Player,
+      parse: |args| {
+         let action = args.word("action")?;
+         let action = match action.to_ascii_lowercase().as_str() {
+            "invite" => PartyAction::Invite(args.word("name")?),
+            "accept" => PartyAction::Accept(args.word("name")?),
+            "leave" => PartyAction::Leave,
+            "kick" => PartyAction::Kick(args.word("name")?),
+            _ => {
+               return Err(ArgError::Invalid {
+                  name: "action",
+                  value: action,
+               });
+            }
+         };
+         Ok(Command::Party(action))
+      },
+      is: |command| matches!(command, Command::Party(_)),
+   },
+   CommandSpec {
+      name: "guild",
+      aliases: &["g"],
+      usage: "/guild [create|invite|join|leave|kick|promote|demote] [name]",
+      description: "Shows your guild, or manages it. Quote guild names that contain spaces.",
+      min_role: Role::Player,
+      parse: |args| {
+         let Some(action) = args.opt_word() else {
+            return Ok(Command::Guild(GuildAction::Info));
+         };
+         let action = match action.to_ascii_lowercase().as_str() {
+            "create" => GuildAction::Create(args.word("name")?),
+            "invite" => GuildAction::Invite(args.word("name")?),
+            "join" => GuildAction::Join(args.word("name")?),
+            "leave" => GuildAction::Leave,
+            "kick" => GuildAction::Kick(args.word("name")?),
+            "promote" => GuildAction::Promote(args.word("name")?),
+            "demote" => GuildAction::Demote(args.word("name")?),
+            _ => {
+               return Err(ArgError::Invalid {
+                  name: "action",
+                  value: action,
+               });
+            }
+         };
+         Ok(Command::Guild(action))
+      },
+      is: |command| matches!(command, Command::Guild(_)),
+   },
+   CommandSpec {
+      name: "bless",
+      aliases: &[],
+      usage: "/bless",
+      description: "Buys a blessing with gold. Blessings make you lose less when you die.",
+      min_role: Role::Player,
+      parse: |_| Ok(Command::Bless),
+      is: |command| matches!(command, Command::Bless),
+   },
+   CommandSpec {
+      name: "loot",
+      aliases: &[],
+      usage: "/loot",
+      description: "Takes the items out of the corpses next to you.",
+      min_role: Role::Player,
+      parse: |_| Ok(Command::Loot),
+      is: |command| matches!(command, Command::Loot),
+   },
+   CommandSpec {
+      name: "deaths",
+      aliases: &[],
+      usage: "/deaths [name]",
+      description: "Shows how you, or someone else, died lately.",
+      min_role: Role::Player,
+      parse: |args| Ok(Command::Deaths(args.opt_word())),
+      is: |command| matches!(command, Command::Deaths(_)),
+   },
+   CommandSpec {
+      name: "mute",
+      aliases: &[],
+      usage: "/mute <name> <minutes>",
+      description: "Keeps a player from talking for a while. 0 minutes unmutes.",
+      min_role: Role::Tutor,
+      parse: |args| {
+         Ok(Command::Mute {
+            name: args.word("name")?,
+            minutes: args.parse("minutes")?,
+         })
+      },
+      is: |command| matches!(command, Command::Mute { .. }),
+   },
+   CommandSpec {
+      name: "goto",
+      aliases: &[],
+      usage: "/goto <name>",
+      description: "Teleports you next to a player.",
+      min_role: Role::GameMaster,
+      parse: |args| Ok(Command::Goto(args.word("name")?)),
+      is: |command| matches!(command, Command::Goto(_)),
+   },
+   CommandSpec {
+      name: "tp",
+      aliases: &["teleport"],
+      usage: "/tp <x> <y> [z]",
+      description: "Teleports you to a tile, on your current floor unless z is given.",
+      min_role: Role::GameMaster,
+      parse: |args| {
+         Ok(Command::Teleport {
+            x: args.parse("x")?,
+            y: args.parse("y")?,
+            z: args.opt_parse("z")?,
+         })
+      },
+      is: |command| matches!(command, Command::Teleport { .. }),
+   },
+   CommandSpec {
+      name: "summon",
+      aliases: &[],
+      usage: "/summon <monster> [count]",
+      description: "Summons monsters around you.",
+      min_role: Role::GameMaster,
+      parse: |args| {
+         Ok(Command::Summon {
+            monster: args.word("monster")?,
+            count: args.opt_parse("count")?.unwrap_or(1),
+         })
+      },
+      is: |command| matches!(command, Command::Summon { .. }),
+   },
+   CommandSpec {
+      name: "kick",
+      aliases: &[],
+      usage: "/kick <name>",
+      description: "Disconnects a player.",
+      min_role: Role::GameMaster,
+      parse: |args| Ok(Command::Kick(args.word("name")?)),
+      is: |command| matches!(command, Command::Kick(_)),
+   },
+   CommandSpec {
+      name: "ban",
+      aliases: &[],
+      usage: "/ban <name>",
+      description: "Disconnects a player and keeps them from logging in again.",
+      min_role: Role::GameMaster,
+      parse: |args| Ok(Command::Ban(args.word("name")?)),
+      is: |command| matches!(command, Command::Ban(_)),
+   },
+   CommandSpec {
+      name: "unban",
+      aliases: &[],
+      usage: "/unban <name>",
+      description: "Lets a banned player log in again.",
+      min_role: Role::GameMaster,
+      parse: |args| Ok(Command::Unban(args.word("name")?)),
+      is: |command| matches!(command, Command::Unban(_)),
+   },
+   CommandSpec {
+      name: "broadcast",
+      aliases: &["b"],
+      usage: "/broadcast <message>",
+      description: "Sends a server message to everyone online.",
+      min_role: Role::GameMaster,
+      parse: |args| Ok(Command::Broadcast(args.rest("message")?)),
+      is: |command| matches!(command, Command::Broadcast(_)),
+   },
+   CommandSpec {
+      name: "sethp",
+      aliases: &[],
+      usage: "/sethp <name> <hp>",
+      description: "Sets a player's health, capped at their max health.",
+      min_role: Role::GameMaster,
+      parse: |args| {
+         Ok(Command::SetHp {
+            name: args.word("name")?,
+            hp: args.parse("hp")?,
+         })
+      },
+      is: |command| matches!(command, Command::SetHp { .. }),
+   },
+   CommandSpec {
+      name: "setlevel",
+      aliases: &[],
+      usage: "/setlevel <name> <level>",
+      description: "Sets a player's level.",
+      min_role: Role::GameMaster,
+      parse: |args| {
+         Ok(Command::SetLevel {
+            name: args.word("name")?,
+            level: args.parse("level")?,
+         })
+      },
+      is: |command| matches!(command, Command::SetLevel { .. }),
+   },
+   CommandSpec {
+      name: "ghost",
+      aliases: &[],
+      usage: "/ghost",
+      description: "Toggles invisibility. Monsters and players below game master ignore you.",
+      min_role: Role::GameMaster,
+      parse: |_| Ok(Command::Ghost),
+      is: |command| matches!(command, Command::Ghost),
+   },
+   CommandSpec {
+      name: "role",
+      aliases: &[],
+      usage: "/role <name> <player|tutor|gm|admin>",
+      description: "Changes the role of a player.",
+      min_role: Role::Admin,
+      parse: |args| {
+         Ok(Command::SetRole {
+            name: args.word("name")?,
+            role: args.parse("role")?,
+         })
+      },
+      is: |command| matches!(command, Command::SetRole { .. }),
+   },
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+   let name = name.trim_start_matches('/');
+   COMMANDS.iter().find(|spec| spec.matches(name))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ArgError {
+   Missing(&'static str),
+   Invalid { name: &'static str, value: String },
+   Unexpected(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum CommandError {
+   Unknown(String),
+   BadArguments {
+      usage: &'static str,
+      error: ArgError,
+   },
+}
+
+impl fmt::Display for CommandError {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      match self {
+         CommandError::Unknown(name) => {
+            write!(f, "Unknown command /{name}. Type /help for a list.")
+         }
+         CommandError::BadArguments { usage, error } => {
+            match error {
+               ArgError::Missing(name) => write!(f, "Missing <{name}>.")?,
+               ArgError::Invalid { name, value } => {
+                  write!(f, "\"{value}\" is not a valid <{name}>.")?
+               }
+               ArgError::Unexpected(rest) => write!(f, "Unexpected \"{rest}\".")?,
+            }
+            write!(f, " Usage: {usage}")
+         }
+      }
+   }
+}
+
+/// Returns `None` when `input` is not a command at all.
+pub fn parse_command(input: &str) -> Option<Result<Command, CommandError>> {
+   let input = input.trim().strip_prefix('/')?;
+   let (name, rest) = input.split_once(' ').unwrap_or((input, ""));
+
+   let Some(spec) = find_command(name) else {
+      return Some(Err(CommandError::Unknown(name.to_string())));
+   };
+
+   let mut args = Args::new(rest);
+   let result = (spec.parse)(&mut args)
+      .and_then(|command| args.finish().map(|_| command))
+      .map_err(|error| CommandError::BadArguments {
+         usage: spec.usage,
+         error,
+      });
+
+   Some(result)
+}
+
+/// The arguments after the command name, consumed left to right.
+pub struct Args<'a> {
+   rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+   pub fn new(rest: &'a str) -> Args<'a> {
+      Args { rest: rest.trim() }
+   }
+
+   /// The next word, or the next `"quoted words"`.
+   pub fn opt_word(&mut self) -> Option<String> {
+      if self.rest.is_empty() {
+         return None;
+      }
+
+      let (word, rest) = match self.rest.strip_prefix('"') {
+         Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+         None => self.rest.split_once(' ').unwrap_or((self.rest, "")),
+      };
+      self.rest = rest.trim_start();
+      Some(word.to_string())
+   }
+
+   pub fn word(&mut self, name: &'static str) -> Result<String, ArgError> {
+      self.opt_word().ok_or(ArgError::Missing(name))
+   }
+
+   pub fn parse<T: FromStr>(&mut self, name: &'static str) -> Result<T, ArgError> {
+      let word = self.word(name)?;
+      word
+         .parse()
+         .map_err(|_| ArgError::Invalid { name, value: word })
+   }
+
+   pub fn opt_parse<T: FromStr>(&mut self, name: &'static str) -> Result<Option<T>, ArgError> {
+      match self.rest.is_empty() {
+         true => Ok(None),
+         false => self.parse(name).map(Some),
+      }
+   }
+
+   /// Everything that is left, which must not be empty.
+   pub fn rest(&mut self, name: &'static str) -> Result<String, ArgError> {
+      let rest = std::mem::take(&mut self.rest);
+      match rest.is_empty() {
+         true => Err(ArgError::Missing(name)),
+         false => Ok(rest.to_string()),
+      }
+   }
+
+   fn finish(&self) -> Result<(), ArgError> {
+      match self.rest.is_empty() {
+         true => Ok(()),
+         false => Err(ArgError::Unexpected(self.rest.to_string())),
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_parse_command() {
+      assert_eq!(parse_command("hello"), None);
+      assert_eq!(parse_command("/who"), Some(Ok(Command::Who)));
+      assert_eq!(
+         parse_command("/tell \"Sir Bob\" how are you?"),
+         Some(Ok(Command::PrivateMsg {
+            to: "Sir Bob".to_string(),
+            msg: "how are you?".to_string()
+         }))
+      );
+      assert_eq!(
+         parse_command("/dance"),
+         Some(Err(CommandError::Unknown("dance".to_string())))
+      );
+   }
+
+   #[test]
+   fn test_every_command_has_one_spec() {
+      let name = || "Bob".to_string();
+      let commands = [
+         Command::PrivateMsg {
+            to: name(),
+            msg: name(),
+         },
+         Command::Who,
+         Command::Online,
+         Command::Ignore(name()),
+         Command::Help(None),
+         Command::Goto(name()),
+         Command::Teleport {
+            x: 1,
+            y: 2,
+            z: None,
+         },
+         Command::Summon {
+            monster: name(),
+            count: 1,
+         },
+         Command::Kick(name()),
+         Command::Ban(name()),
+         Command::Unban(name()),
+         Command::Mute {
+            name: name(),
+            minutes: 1,
+         },
+         Command::Broadcast(name()),
+         Command::SetHp {
+            name: name(),
+            hp: 1,
+         },
+         Command::SetLevel {
+            name: name(),
+            level: 1,
+         },
+         Command::Ghost,
+         Command::SetRole {
+            name: name(),
+            role: Role::Admin,
+         },
+         Command::Party(PartyAction::Leave),
+         Command::Guild(GuildAction::Info),
+         Command::Bless,
+         Command::Loot,
+         Command::Deaths(None),
+      ];
+      for command in &commands {
+         let specs = COMMANDS.iter().filter(|spec| (spec.is)(command)).count();
+         assert_eq!(specs, 1, "{command:?} has {specs} specs");
+      }
+      for spec in COMMANDS {
+         assert!(
+            commands.iter().any(|command| (spec.is)(command)),
+            "/{} recognizes none of the commands",
+            spec.name
+         );
+      }
+
+      assert_eq!(Command::Who.spec().unwrap().min_role, Role::Player);
+      assert_eq!(commands[16].spec().unwrap().name, "role");
+      assert_eq!(commands[16].spec().unwrap().min_role, Role::Admin);
+   }
+
+   #[test]
+   fn test_argument_errors() {
+      assert_eq!(
+         parse_command("/w Bob"),
+         Some(Err(CommandError::BadArguments {
+            usage: "/w <name> <message>",
+            error: ArgError::Missing("message")
+         }))
+      );
+      assert_eq!(
+         parse_command("/online now"),
+         Some(Err(CommandError::BadArguments {
+            usage: "/online",
+            error: ArgError::Unexpected("now".to_string())
+         }))
+      );
+      assert_eq!(
+         parse_command("/tp 10 x"),
+         Some(Err(CommandError::BadArguments {
+            usage: "/tp <x> <y> [z]",
+            error: ArgError::Invalid {
+               name: "y",
+               value: "x".to_string()
+            }
+         }))
+      );
+
+      let mut args = Args::new("12 abc");
+      assert_eq!(args.parse::<u32>("x"), Ok(12));
+      assert_eq!(
+         args.parse::<u32>("y"),
+         Err(ArgError::Invalid {
+            name: "y",
+            value: "abc".to_string()
+         })
+      );
+   }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionKind {
+   Poison,
+   Burning,
+   Haste,
+   Paralyze,
+   Regeneration,
+}
+
+impl ConditionKind {
+   /// How much longer each step takes while the condition lasts.
+   pub fn move_delay_factor(self) -> f32 {
+      match self {
+         ConditionKind::Haste => 0.7,
+         ConditionKind::Paralyze => 2.0,
+         _ => 1.0,
+      }
+   }
+}
+
+pub fn move_delay_factor(conditions: &[ConditionKind]) -> f32 {
+   conditions
+      .iter()
+      .map(|condition| condition.move_delay_factor())
+      .product()
+}
+
+/// A condition as handed out by a spell, a monster or a field. It lasts
+/// `ticks` times `interval_ms`. Damage and regeneration apply `amount` on
+/// every tick, the speed conditions ignore it.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct ConditionSpec {
+   pub kind: ConditionKind,
+   pub ticks: u32,
+   pub interval_ms: u64,
+   #[serde(default)]
+   pub amount: u32,
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_move_delay_factor() {
+      assert_eq!(move_delay_factor(&[]), 1.0);
+      assert_eq!(move_delay_factor(&[ConditionKind::Poison]), 1.0);
+      assert_eq!(move_delay_factor(&[ConditionKind::Haste]), 0.7);
+      assert_eq!(
+         move_delay_factor(&[ConditionKind::Burning, ConditionKind::Paralyze]),
+         2.0
+      );
+   }
+}
@@ -0,0 +1,150 @@
+use crate::constants::*;
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{
+   fs,
+   io::ErrorKind,
+   net::SocketAddr,
+   path::{Path, PathBuf},
+};
+use thin_logger::log::LevelFilter;
+
+const MAP_PATH: &str = "assets/basic-map.tmx";
+
+/// The server's settings. Each one comes from the first of the command
+/// line, the environment, the TOML file and the defaults that has it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+   pub tcp_addr: SocketAddr,
+   pub udp_addr: SocketAddr,
+   pub tick_rate_ms: u64,
+   pub log_level: String,
+   pub map_path: PathBuf,
+   /// Whether players can attack each other outside protection zones.
+   pub pvp: bool,
+}
+
+impl Default for ServerConfig {
+   fn default() -> Self {
+      ServerConfig {
+         tcp_addr: SERVER_TCP_ADDR.parse().unwrap(),
+         udp_addr: SERVER_UDP_ADDR.parse().unwrap(),
+         tick_rate_ms: SERVER_TICK_RATE,
+         log_level: "info".to_string(),
+         map_path: MAP_PATH.into(),
+         pvp: true,
+      }
+   }
+}
+
+impl ServerConfig {
+   pub fn check(&self) -> Result<()> {
+      parse_log_level(&self.log_level)?;
+      if self.tick_rate_ms == 0 {
+         return Err(anyhow!("tick_rate_ms must be at least 1"));
+      }
+      Ok(())
+   }
+
+   pub fn log_level(&self) -> LevelFilter {
+      parse_log_level(&self.log_level).unwrap_or(LevelFilter::Info)
+   }
+}
+
+/// The client's settings, layered like the server's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+   /// What the login screen starts with, as `host:port`.
+   pub server_addr: String,
+   /// The server takes UDP on the same host as TCP, on this port.
+   pub server_udp_port: u16,
+   pub log_level: String,
+   pub map_path: PathBuf,
+   /// How many times to try reconnecting before going back to the login screen.
+   pub connection_retries: u8,
+   /// Where the client keeps what it remembers between sessions.
+   pub data_dir: PathBuf,
+}
+
+impl Default for ClientConfig {
+   fn default() -> Self {
+      ClientConfig {
+         server_addr: SERVER_TCP_ADDR.to_string(),
+         server_udp_port: SERVER_UDP_ADDR.parse::<SocketAddr>().unwrap().port(),
+         log_level: "debug".to_string(),
+         map_path: MAP_PATH.into(),
+         connection_retries: MAX_CONNECTION_RETRIES,
+         data_dir: "client-data".into(),
+      }
+   }
+}
+
+impl ClientConfig {
+   pub fn check(&self) -> Result<()> {
+      parse_log_level(&self.log_level).map(|_| ())
+   }
+
+   pub fn log_level(&self) -> LevelFilter {
+      parse_log_level(&self.log_level).unwrap_or(LevelFilter::Debug)
+   }
+}
+
+fn parse_log_level(level: &str) -> Result<LevelFilter> {
+   level.parse().map_err(|_| {
+      anyhow!("unknown log level {level:?}, expected off, error, warn, info, debug or trace")
+   })
+}
+
+/// Reads `path` as TOML over the defaults, or `default_path` when no path
+/// was given. Anything it leaves out keeps its default. Only a missing file
+/// at `default_path` reads as the defaults alone, a path given on purpose has
+/// to exist.
+pub fn load_toml<T: DeserializeOwned + Default>(
+   path: Option<&Path>,
+   default_path: impl AsRef<Path>,
+) -> Result<T> {
+   let is_default = path.is_none();
+   let path = path.unwrap_or(default_path.as_ref());
+   match fs::read_to_string(path) {
+      Ok(text) => {
+         toml::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))
+      }
+      Err(e) if e.kind() == ErrorKind::NotFound && is_default => Ok(T::default()),
+      Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+   }
+}
+
+/// `config` as it would be written in its TOML file, to show what is in use.
+pub fn to_toml<T: Serialize>(config: &T) -> String {
+   toml::to_string(config).unwrap_or_else(|e| format!("<{e}>"))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_partial_config() {
+      let config: ServerConfig = toml::from_str("tick_rate_ms = 50").unwrap();
+      assert_eq!(config.tick_rate_ms, 50);
+      assert_eq!(config.tcp_addr, ServerConfig::default().tcp_addr);
+      assert!(config.check().is_ok());
+
+      assert!(toml::from_str::<ServerConfig>("tick_rate = 50").is_err());
+      let config: ClientConfig = toml::from_str("log_level = \"loud\"").unwrap();
+      assert!(config.check().is_err());
+
+      let printed: ClientConfig = toml::from_str(&to_toml(&ClientConfig::default())).unwrap();
+      assert_eq!(printed.server_addr, SERVER_TCP_ADDR);
+   }
+
+   #[test]
+   fn test_missing_config_file() {
+      let missing = Path::new("no-such-config.toml");
+      let config: ServerConfig = load_toml(None, missing).unwrap();
+      assert_eq!(config.tick_rate_ms, ServerConfig::default().tick_rate_ms);
+      assert!(load_toml::<ServerConfig>(Some(missing), "server.toml").is_err());
+   }
+}
@@ -8,6 +8,14 @@ pub const SERVER_TICK_RATE: u64 = 16; // how often the server loops. ms.
 pub const SERVER_UDP_ADDR: &str = "127.0.0.1:5000";
 pub const SERVER_TCP_ADDR: &str = "127.0.0.1:8080";
 
+// How far away (in tiles) speech can be heard.
+pub const WHISPER_RANGE: u32 = 1;
+pub const SAY_RANGE: u32 = 7;
+pub const YELL_RANGE: u32 = 14;
+
+// How far away (in tiles) party members can be and still share experience.
+pub const SHARED_EXP_RANGE: u32 = 30;
+
 // Client
 pub const CAMERA_WIDTH: u32 = 19;
 pub const CAMERA_HEIGHT: u32 = 15;
@@ -18,4 +26,8 @@ pub const MAP_DEPTH: u32 = 2; // Number of z-levels (floors)
 
 pub const BASE_MOVE_DELAY: f32 = 0.2; // expressed in seconds (1 tile / 0.2 secs)
 
+// How far in the past other players and creatures are drawn, in seconds, so
+// there is a snapshot on either side of what is drawn despite jitter.
+pub const INTERPOLATION_DELAY: f64 = 0.1;
+
 pub const MAX_CONNECTION_RETRIES: u8 = 5;
@@ -0,0 +1,231 @@
+use crate::{ConditionSpec, Direction, GameObject, ItemKind, Missile};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+const CREATURES_PATH: &str = "assets/creatures.json";
+
+/// A kind of creature as defined in `assets/creatures.json`. Creatures in the
+/// map are placed with the tile of their `south` sprite, which is also what
+/// tells them apart in [`GameObject::Creature`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatureType {
+   pub name: String,
+   pub hp: u32,
+   pub experience: u64,
+   /// Time between two steps.
+   pub move_cooldown_ms: u64,
+   pub attacks: Vec<CreatureAttack>,
+   /// Taken off the damage of every hit the creature receives.
+   #[serde(default)]
+   pub armor: u32,
+   #[serde(default)]
+   pub loot: Vec<Loot>,
+   pub sprites: Sprites,
+   #[serde(default)]
+   pub behaviors: Vec<Behavior>,
+}
+
+impl CreatureType {
+   /// E.g. "an orc", for messages.
+   pub fn with_article(&self) -> String {
+      let name = self.name.to_lowercase();
+      let article = match name.starts_with(['a', 'e', 'i', 'o', 'u']) {
+         true => "an",
+         false => "a",
+      };
+      format!("{article} {name}")
+   }
+
+   /// The first of its behaviors `f` picks out.
+   pub fn behavior<'a, T>(&'a self, f: impl Fn(&'a Behavior) -> Option<T>) -> Option<T> {
+      self.behaviors.iter().find_map(f)
+   }
+
+   /// Catches the values the game would otherwise panic on once it rolls them.
+   fn check(&self) -> Result<()> {
+      for attack in &self.attacks {
+         if attack.min > attack.max {
+            bail!("attack min {} is above its max {}", attack.min, attack.max);
+         }
+         check_chance(attack.condition_chance)?;
+      }
+      for loot in &self.loot {
+         if loot.max == 0 {
+            bail!("loot of {:?} has a max of 0", loot.item);
+         }
+         check_chance(loot.chance)?;
+      }
+      for behavior in &self.behaviors {
+         if let Behavior::Summon { chance, .. } = behavior {
+            check_chance(*chance)?;
+         }
+      }
+      Ok(())
+   }
+}
+
+fn check_chance(chance: f64) -> Result<()> {
+   if !(0.0..=1.0).contains(&chance) {
+      bail!("chance {chance} is not between 0 and 1");
+   }
+   Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreatureAttack {
+   pub min: u32,
+   pub max: u32,
+   pub cooldown_ms: u64,
+   /// How many tiles away the target can be. Melee attacks have a range of 1.
+   #[serde(default = "melee_range")]
+   pub range: u32,
+   #[serde(default)]
+   pub condition: Option<ConditionSpec>,
+   /// The chance of every hit to also give the condition.
+   #[serde(default)]
+   pub condition_chance: f64,
+   /// Shown flying to the target of a ranged attack.
+   #[serde(default)]
+   pub missile: Option<Missile>,
+}
+
+fn melee_range() -> u32 {
+   1
+}
+
+/// Up to `max` items of a kind, dropped with the given chance.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Loot {
+   pub item: ItemKind,
+   pub chance: f64,
+   pub max: u32,
+}
+
+/// Tile ids in the creature tileset, by the direction the creature faces.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Sprites {
+   pub north: u32,
+   pub south: u32,
+   pub east: u32,
+   pub west: u32,
+}
+
+impl Sprites {
+   pub fn facing(&self, direction: Direction) -> u32 {
+      match direction {
+         Direction::North => self.north,
+         Direction::South => self.south,
+         Direction::East => self.east,
+         Direction::West => self.west,
+      }
+   }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Behavior {
+   /// Stays this many tiles away from its target and attacks from there.
+   KeepDistance { distance: u32 },
+   /// Runs away once its health drops below this share of the maximum.
+   Flee { below: f32 },
+   /// Calls creatures to its side now and then, up to `max` around it.
+   Summon {
+      creature: String,
+      chance: f64,
+      max: u32,
+   },
+}
+
+/// Every kind of creature, read once at startup.
+#[derive(Debug, Default, Clone)]
+pub struct Creatures(pub Vec<CreatureType>);
+
+impl Creatures {
+   pub fn load() -> Result<Creatures> {
+      let json =
+         fs::read_to_string(CREATURES_PATH).with_context(|| format!("reading {CREATURES_PATH}"))?;
+      let creatures: Vec<CreatureType> =
+         serde_json::from_str(&json).with_context(|| format!("parsing {CREATURES_PATH}"))?;
+      for creature in &creatures {
+         creature
+            .check()
+            .with_context(|| format!("checking {} in {CREATURES_PATH}", creature.name))?;
+      }
+      Ok(Creatures(creatures))
+   }
+
+   /// The kind of creature placed with tile `id`.
+   pub fn by_id(&self, id: u32) -> Option<&CreatureType> {
+      self.0.iter().find(|creature| creature.sprites.south == id)
+   }
+
+   pub fn by_name(&self, name: &str) -> Option<&CreatureType> {
+      self
+         .0
+         .iter()
+         .find(|creature| creature.name.eq_ignore_ascii_case(name))
+   }
+
+   /// The kind of creature `object` is, if it is one.
+   pub fn of(&self, object: &GameObject) -> Option<&CreatureType> {
+      match object {
+         GameObject::Creature { id, .. } => self.by_id(*id),
+         _ => None,
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+   use itertools::Itertools;
+
+   #[test]
+   fn test_creature_definitions() {
+      let json = include_str!("../../assets/creatures.json");
+      let creatures = Creatures(serde_json::from_str(json).unwrap());
+
+      assert!(creatures.0.iter().map(|c| c.sprites.south).all_unique());
+      assert_eq!(creatures.by_name("orc").unwrap().with_article(), "an orc");
+      for creature in &creatures.0 {
+         creature.check().unwrap();
+         assert!(
+            !creature.attacks.is_empty(),
+            "{} cannot attack",
+            creature.name
+         );
+         for behavior in &creature.behaviors {
+            if let Behavior::Summon { creature, .. } = behavior {
+               assert!(
+                  creatures.by_name(creature).is_some(),
+                  "no creature {creature}"
+               );
+            }
+         }
+      }
+   }
+
+   #[test]
+   fn test_check_rejects_bad_rolls() {
+      let json = include_str!("../../assets/creatures.json");
+      let creatures: Vec<CreatureType> = serde_json::from_str(json).unwrap();
+      let creature = creatures.into_iter().find(|c| !c.loot.is_empty()).unwrap();
+
+      let mut bad = creature.clone();
+      bad.attacks[0].min = bad.attacks[0].max + 1;
+      assert!(bad.check().is_err());
+
+      let mut bad = creature.clone();
+      bad.attacks[0].condition_chance = 1.5;
+      assert!(bad.check().is_err());
+
+      let mut bad = creature.clone();
+      bad.loot[0].max = 0;
+      assert!(bad.check().is_err());
+
+      let mut bad = creature;
+      bad.loot[0].chance = -0.1;
+      assert!(bad.check().is_err());
+   }
+}
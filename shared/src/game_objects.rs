@@ -1,20 +1,24 @@
-use crate::{Direction, Location, calculate_new_direction, constants::*};
+use crate::{Creatures, Direction, Location, calculate_new_direction, constants::*};
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path};
 use thin_logger::log::trace;
-use tiled::Loader;
+use tiled::{Loader, Map};
+use uuid::Uuid;
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct GameObjects(pub HashMap<Location, GameObject>);
 
-#[allow(clippy::new_without_default)]
-impl GameObjects {
-   pub fn new() -> GameObjects {
-      let map = {
-         let mut loader = Loader::new();
-         loader.load_tmx_map("assets/basic-map.tmx").unwrap()
-      };
+/// Reads the map both the server and the client are built from.
+pub fn load_map(path: impl AsRef<Path>) -> Result<Map> {
+   let path = path.as_ref();
+   Loader::new()
+      .load_tmx_map(path)
+      .with_context(|| format!("failed to load the map {}", path.display()))
+}
 
+impl GameObjects {
+   pub fn new(map: &Map, creatures: &Creatures) -> GameObjects {
       let mut all_objects = HashMap::new();
 
       // Iterate through groups to get objects with proper z-levels
@@ -26,24 +30,21 @@ impl GameObjects {
             for inner_layer in group_layer.layers() {
                if let tiled::LayerType::Objects(object_layer) = inner_layer.layer_type() {
                   for od in object_layer.object_data() {
-                     let tile_data = od.tile_data().unwrap();
+                     // plain shapes, like protection zones, are not objects
+                     let Some(tile_data) = od.tile_data() else {
+                        continue;
+                     };
                      let tiled::TilesetLocation::Map(location) = tile_data.tileset_location()
                      else {
                         panic!("Invalid tileset location layer!");
                      };
-                     let tile_id = od.tile_data().expect("expected tile data").id();
+                     let tile_id = tile_data.id();
 
                      let game_object = match tile_id {
                         149 => GameObject::FlowerPot {
                            id: tile_id,
                            tileset_location: *location,
                         },
-                        63 => GameObject::Orc {
-                           id: tile_id,
-                           tileset_location: *location,
-                           hp: 100,
-                           direction: Direction::South,
-                        },
                         83 => GameObject::Ladder {
                            id: tile_id,
                            tileset_location: *location,
@@ -54,7 +55,20 @@ impl GameObjects {
                            tileset_location: *location,
                            target_z: 0, // Goes down to base level
                         },
-                        id => todo!("game object id: {id} is not implemented"),
+                        DOOR_CLOSED | DOOR_OPEN => GameObject::Door {
+                           tileset_location: *location,
+                           is_open: tile_id == DOOR_OPEN,
+                        },
+                        id => match creatures.by_id(id) {
+                           Some(creature) => GameObject::Creature {
+                              id,
+                              tileset_location: *location,
+                              uid: Uuid::new_v4(),
+                              hp: creature.hp,
+                              direction: Direction::South,
+                           },
+                           None => todo!("game object id: {id} is not implemented"),
+                        },
                      };
 
                      let obj_location = (
@@ -86,9 +100,47 @@ impl GameObjects {
       self.0.insert(to, object);
       Some(())
    }
+
+   /// Where a step of one tile from `from` ends. `None` for longer steps,
+   /// off the map or into a closed door.
+   pub fn step(&self, from: Location, (dx, dy): (i32, i32)) -> Option<Location> {
+      if (dx, dy) == (0, 0) || dx.abs() > 1 || dy.abs() > 1 {
+         return None;
+      }
+      let (x, y) = (
+         from.0.checked_add_signed(dx)?,
+         from.1.checked_add_signed(dy)?,
+      );
+      if x >= MAP_WIDTH || y >= MAP_HEIGHT {
+         return None;
+      }
+
+      let to = (x, y, from.2);
+      match self.0.get(&to) {
+         Some(GameObject::Door { is_open: false, .. }) => None,
+         _ => Some(to),
+      }
+   }
+
+   pub fn find_monster(&self, uid: Uuid) -> Option<(Location, GameObject)> {
+      self
+         .0
+         .iter()
+         .find(|(_, obj)| obj.uid() == Some(uid))
+         .map(|(location, obj)| (*location, *obj))
+   }
 }
 
-pub const ORC_MAX_HP: u32 = 100;
+/// Index of props-tileset.tsx among the tilesets of basic-map.tmx.
+pub const PROPS_TILESET: usize = 1;
+/// Index of tibia-sprites.tsx among the tilesets of basic-map.tmx.
+pub const CREATURE_TILESET: usize = 2;
+
+/// Tiles of props-tileset.tsx a door is drawn with.
+const DOOR_CLOSED: u32 = 49;
+const DOOR_OPEN: u32 = 81;
+/// Tile of props-tileset.tsx a corpse is drawn with.
+const CORPSE: u32 = 137;
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 pub enum GameObject {
@@ -96,9 +148,11 @@ pub enum GameObject {
       id: u32,
       tileset_location: usize,
    },
-   Orc {
+   /// See [`crate::CreatureType`] for what kind of creature `id` is.
+   Creature {
       id: u32,
       tileset_location: usize,
+      uid: Uuid, // tells creatures of the same kind apart
       hp: u32,
       direction: Direction,
    },
@@ -107,23 +161,46 @@ pub enum GameObject {
       tileset_location: usize,
       target_z: u32,
    },
+   /// Players walk through it while it is open. Nothing else does.
+   Door {
+      tileset_location: usize,
+      is_open: bool,
+   },
+   /// Where a player died and left some of their items.
+   Corpse,
 }
 
 impl GameObject {
    pub fn id(&self) -> u32 {
       match self {
          GameObject::FlowerPot { id, .. } => *id,
-         GameObject::Orc { id, .. } => *id,
+         GameObject::Creature { id, .. } => *id,
          GameObject::Ladder { id, .. } => *id,
+         GameObject::Door { is_open: true, .. } => DOOR_OPEN,
+         GameObject::Door { is_open: false, .. } => DOOR_CLOSED,
+         GameObject::Corpse => CORPSE,
+      }
+   }
+
+   pub fn uid(&self) -> Option<Uuid> {
+      match self {
+         GameObject::Creature { uid, .. } => Some(*uid),
+         _ => None,
       }
    }
 
    pub fn is_monster(&self) -> bool {
-      matches!(self, GameObject::Orc { .. })
+      matches!(self, GameObject::Creature { .. })
+   }
+
+   /// Monsters are clicked to attack them and corpses to loot them, the
+   /// rest can be dragged around.
+   pub fn is_movable(&self) -> bool {
+      !matches!(self, GameObject::Creature { .. } | GameObject::Corpse)
    }
 
    pub fn change_direction(&mut self, direction: Direction) {
-      if let GameObject::Orc { direction: d, .. } = self {
+      if let GameObject::Creature { direction: d, .. } = self {
          *d = direction
       }
    }
@@ -133,12 +210,16 @@ impl GameObject {
          GameObject::FlowerPot {
             tileset_location, ..
          } => *tileset_location,
-         GameObject::Orc {
+         GameObject::Creature {
             tileset_location, ..
          } => *tileset_location,
          GameObject::Ladder {
             tileset_location, ..
          } => *tileset_location,
+         GameObject::Door {
+            tileset_location, ..
+         } => *tileset_location,
+         GameObject::Corpse => PROPS_TILESET,
       }
    }
 }
@@ -147,6 +228,36 @@ impl GameObject {
 mod tests {
    use super::*;
 
+   #[test]
+   fn test_step() {
+      let ladder = GameObject::Ladder {
+         id: 83,
+         tileset_location: 0,
+         target_z: 1,
+      };
+      let door = GameObject::Door {
+         tileset_location: 1,
+         is_open: false,
+      };
+      let mut game_objects = GameObjects(HashMap::from([((3, 2, 0), ladder), ((2, 1, 0), door)]));
+
+      assert_eq!(game_objects.step((2, 2, 0), (-1, 1)), Some((1, 3, 0)));
+      assert_eq!(game_objects.step((2, 2, 0), (1, 0)), Some((3, 2, 0)));
+      assert_eq!(game_objects.step((2, 2, 0), (0, -1)), None);
+      game_objects.0.insert(
+         (2, 1, 0),
+         GameObject::Door {
+            tileset_location: 1,
+            is_open: true,
+         },
+      );
+      assert_eq!(game_objects.step((2, 2, 0), (0, -1)), Some((2, 1, 0)));
+      assert_eq!(game_objects.step((2, 2, 0), (2, 0)), None);
+      assert_eq!(game_objects.step((2, 2, 0), (0, 0)), None);
+      assert_eq!(game_objects.step((0, 2, 0), (-1, 0)), None);
+      assert_eq!(game_objects.step((MAP_WIDTH - 1, 2, 0), (1, 0)), None);
+   }
+
    #[test]
    fn test_load_map() {
       let map = {
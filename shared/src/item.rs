@@ -0,0 +1,167 @@
+use crate::{Missile, Weapon};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+   GoldCoin,
+   Meat,
+   FlowerPot,
+   Bow,
+   Arrow,
+   Wand,
+}
+
+impl ItemKind {
+   pub fn name(&self) -> &'static str {
+      match self {
+         ItemKind::GoldCoin => "gold coin",
+         ItemKind::Meat => "meat",
+         ItemKind::FlowerPot => "flower pot",
+         ItemKind::Bow => "bow",
+         ItemKind::Arrow => "arrow",
+         ItemKind::Wand => "wand",
+      }
+   }
+
+   /// How the item hits when wielded, if it is a weapon at all.
+   pub fn weapon(&self) -> Option<Weapon> {
+      match self {
+         ItemKind::Bow => Some(Weapon {
+            range: 5,
+            min: 8,
+            max: 18,
+            ammunition: Some(ItemKind::Arrow),
+            missile: Some(Missile::Arrow),
+         }),
+         ItemKind::Wand => Some(Weapon {
+            range: 4,
+            min: 10,
+            max: 20,
+            ammunition: None,
+            missile: Some(Missile::Energy),
+         }),
+         _ => None,
+      }
+   }
+
+   /// How long eating one keeps the player fed, if it can be eaten at all.
+   pub fn food_duration(&self) -> Option<Duration> {
+      match self {
+         ItemKind::Meat => Some(Duration::from_secs(180)),
+         _ => None,
+      }
+   }
+}
+
+/// A stack of `count` items of the same kind.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Item {
+   pub kind: ItemKind,
+   pub count: u32,
+}
+
+impl Item {
+   pub fn new(kind: ItemKind, count: u32) -> Item {
+      Item { kind, count }
+   }
+}
+
+impl fmt::Display for Item {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      write!(f, "{}x {}", self.count, self.kind.name())
+   }
+}
+
+/// Items carried by a player. Stacks of the same kind are always merged.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Inventory(pub Vec<Item>);
+
+impl Inventory {
+   /// What every new character starts with.
+   pub fn starter() -> Inventory {
+      let mut inventory = Inventory::default();
+      inventory.add(Item::new(ItemKind::GoldCoin, 100));
+      inventory.add(Item::new(ItemKind::Meat, 3));
+      inventory
+   }
+
+   pub fn count(&self, kind: ItemKind) -> u32 {
+      self
+         .0
+         .iter()
+         .filter(|item| item.kind == kind)
+         .map(|item| item.count)
+         .sum()
+   }
+
+   /// Whether every item in `items` can be taken out of the inventory at once.
+   pub fn contains_all(&self, items: &[Item]) -> bool {
+      let mut required = Inventory::default();
+      for item in items {
+         required.add(*item);
+      }
+      required
+         .0
+         .iter()
+         .all(|item| self.count(item.kind) >= item.count)
+   }
+
+   pub fn add(&mut self, item: Item) {
+      if item.count == 0 {
+         return;
+      }
+      match self.0.iter_mut().find(|i| i.kind == item.kind) {
+         Some(stack) => stack.count += item.count,
+         None => self.0.push(item),
+      }
+   }
+
+   /// Removes all `items` or nothing at all. Returns `false` if they were not
+   /// all present.
+   pub fn remove_all(&mut self, items: &[Item]) -> bool {
+      if !self.contains_all(items) {
+         return false;
+      }
+      for item in items {
+         if let Some(stack) = self.0.iter_mut().find(|i| i.kind == item.kind) {
+            stack.count -= item.count;
+         }
+      }
+      self.0.retain(|item| item.count > 0);
+      true
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_add_merges_stacks() {
+      let mut inventory = Inventory::default();
+      inventory.add(Item::new(ItemKind::GoldCoin, 10));
+      inventory.add(Item::new(ItemKind::Meat, 1));
+      inventory.add(Item::new(ItemKind::GoldCoin, 5));
+
+      assert_eq!(inventory.0.len(), 2);
+      assert_eq!(inventory.count(ItemKind::GoldCoin), 15);
+   }
+
+   #[test]
+   fn test_remove_all_is_all_or_nothing() {
+      let mut inventory = Inventory::starter();
+      let too_much = [
+         Item::new(ItemKind::Meat, 1),
+         Item::new(ItemKind::GoldCoin, 60),
+         Item::new(ItemKind::GoldCoin, 60),
+      ];
+
+      assert!(!inventory.remove_all(&too_much));
+      assert_eq!(inventory, Inventory::starter());
+
+      assert!(inventory.remove_all(&[Item::new(ItemKind::Meat, 3)]));
+      assert_eq!(inventory.count(ItemKind::Meat), 0);
+      assert_eq!(inventory.0.len(), 1);
+   }
+}
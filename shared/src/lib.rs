@@ -1,12 +1,30 @@
+pub mod chat;
+pub mod commands;
+pub mod conditions;
+pub mod config;
 pub mod constants;
+pub mod creatures;
 pub mod game_objects;
+pub mod item;
 pub mod network;
+pub mod ranged;
+pub mod spells;
+pub mod zones;
 
+pub use chat::*;
+pub use commands::*;
+pub use conditions::*;
+pub use config::*;
+pub use creatures::*;
 pub use game_objects::*;
+pub use item::*;
 pub use network::*;
+pub use ranged::*;
 use serde::{Deserialize, Serialize};
-use std::cmp::Ordering;
+pub use spells::*;
+use std::{cmp::Ordering, fmt, str::FromStr};
 use uuid::Uuid;
+pub use zones::*;
 
 pub type Location = (u32, u32, u32); // (x, y, z) coordinates
 
@@ -23,6 +41,9 @@ pub struct OtherPlayer {
    pub username: String,
    pub location: Location,
    pub direction: Direction,
+   pub guild: Option<String>,
+   pub skull: Option<Skull>,
+   pub hp_percent: u8,
 }
 
 /// Player initiation state that server instructs
@@ -35,8 +56,185 @@ pub struct InitPlayer {
    pub z_level: u32,
    pub hp: u32,
    pub max_hp: u32,
+   pub mana: u32,
+   pub max_mana: u32,
    pub level: u32,
    pub direction: Direction,
+   pub experience: u64,
+   pub inventory: Inventory,
+   /// How often the server sends snapshots, tagged with the tick they are from.
+   pub tick_rate_ms: u64,
+}
+
+/// The numbers the client shows for its own character.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct PlayerStats {
+   pub hp: u32,
+   pub max_hp: u32,
+   pub mana: u32,
+   pub max_mana: u32,
+   pub level: u32,
+   pub experience: u64,
+}
+
+/// Experience needed to reach `level`, following Tibia's curve.
+pub fn experience_for_level(level: u32) -> u64 {
+   let l = level as i64;
+   (50 * (l * l * l - 6 * l * l + 17 * l - 12) / 3).max(0) as u64
+}
+
+pub fn level_for_experience(experience: u64) -> u32 {
+   let mut level = 1;
+   while experience_for_level(level + 1) <= experience {
+      level += 1;
+   }
+   level
+}
+
+pub fn max_hp_for_level(level: u32) -> u32 {
+   100 + 10 * level.saturating_sub(1)
+}
+
+pub fn max_mana_for_level(level: u32) -> u32 {
+   50 + 10 * level.saturating_sub(1)
+}
+
+/// A party as seen by one of its members.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PartyInfo {
+   pub leader: String,
+   pub members: Vec<String>, // leader included
+   pub invited: Vec<String>,
+}
+
+/// The shield drawn above a player's head.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum PartyShield {
+   Leader,
+   Member,
+   /// Invited to our party.
+   Invited,
+   /// Invited us to their party.
+   Inviter,
+}
+
+impl PartyInfo {
+   pub fn shield_of(&self, username: &str) -> Option<PartyShield> {
+      if self.leader == username {
+         Some(PartyShield::Leader)
+      } else if self.members.iter().any(|m| m == username) {
+         Some(PartyShield::Member)
+      } else if self.invited.iter().any(|i| i == username) {
+         Some(PartyShield::Invited)
+      } else {
+         None
+      }
+   }
+}
+
+/// PvP marks. Attacking an unmarked player earns a white skull, killing too
+/// many of them a red one. Marked players can be attacked without penalty.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Skull {
+   White,
+   Red,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum AttackTarget {
+   Monster(Uuid),
+   Player(String), // username
+}
+
+/// A name on a player's VIP list.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VipEntry {
+   pub name: String,
+   pub online: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GuildRank {
+   Member,
+   ViceLeader,
+   Leader,
+}
+
+impl fmt::Display for GuildRank {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      match self {
+         GuildRank::Member => write!(f, "Member"),
+         GuildRank::ViceLeader => write!(f, "Vice-Leader"),
+         GuildRank::Leader => write!(f, "Leader"),
+      }
+   }
+}
+
+pub fn is_valid_guild_name(name: &str) -> bool {
+   (3..=20).contains(&name.len())
+      && name.chars().all(|c| c.is_ascii_alphabetic() || c == ' ')
+      && !name.starts_with(' ')
+      && !name.ends_with(' ')
+}
+
+/// Account roles, from least to most privileged.
+#[derive(
+   Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+pub enum Role {
+   #[default]
+   Player,
+   Tutor,
+   GameMaster,
+   Admin,
+}
+
+impl FromStr for Role {
+   type Err = ();
+
+   fn from_str(s: &str) -> Result<Role, ()> {
+      match s.to_ascii_lowercase().as_str() {
+         "player" => Ok(Role::Player),
+         "tutor" => Ok(Role::Tutor),
+         "gm" | "gamemaster" => Ok(Role::GameMaster),
+         "admin" => Ok(Role::Admin),
+         _ => Err(()),
+      }
+   }
+}
+
+impl fmt::Display for Role {
+   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+      match self {
+         Role::Player => write!(f, "player"),
+         Role::Tutor => write!(f, "tutor"),
+         Role::GameMaster => write!(f, "game master"),
+         Role::Admin => write!(f, "admin"),
+      }
+   }
+}
+
+/// A trade between two players as seen by one of them. An offer is `None`
+/// until that side has put something on the table.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TradeWindow {
+   pub partner: String,
+   pub own_offer: Option<Vec<Item>>,
+   pub partner_offer: Option<Vec<Item>>,
+   pub own_accepted: bool,
+   pub partner_accepted: bool,
+}
+
+pub fn is_adjacent(a: Location, b: Location) -> bool {
+   let dx = (a.0 as i32 - b.0 as i32).abs();
+   let dy = (a.1 as i32 - b.1 as i32).abs();
+   a.2 == b.2 && dx <= 1 && dy <= 1
+}
+
+pub fn is_on_screen(a: Location, b: Location) -> bool {
+   a.2 == b.2
+      && a.0.abs_diff(b.0) <= constants::CAMERA_WIDTH / 2
+      && a.1.abs_diff(b.1) <= constants::CAMERA_HEIGHT / 2
 }
 
 pub fn calculate_new_direction(prev: Location, target: Location) -> Direction {
@@ -53,3 +251,18 @@ pub fn calculate_new_direction(prev: Location, target: Location) -> Direction {
       Ordering::Greater => Direction::West,
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_experience_levels() {
+      assert_eq!(experience_for_level(1), 0);
+      assert_eq!(experience_for_level(2), 100);
+      assert_eq!(experience_for_level(8), 4200);
+      assert_eq!(level_for_experience(0), 1);
+      assert_eq!(level_for_experience(199), 2);
+      assert_eq!(level_for_experience(200), 3);
+   }
+}
@@ -1,16 +1,64 @@
-use crate::{InitPlayer, Location};
-use serde::{Deserialize, Serialize};
+use crate::{
+   AttackTarget, ChatChannel, Command, ConditionKind, InitPlayer, Inventory, Item, ItemKind,
+   Location, PartyInfo, PlayerStats, Skull, Spell, TradeWindow, VipEntry,
+};
+use anyhow::Result;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tokio::io::AsyncRead;
+use tokio_util::{
+   bytes::{Bytes, BytesMut},
+   codec::{Encoder, FramedRead, LengthDelimitedCodec},
+};
 use uuid::Uuid;
 
 // SERVER -> CLIENT
 #[derive(Debug, Serialize, Deserialize)]
 pub enum TcpServerMsg {
    Pong(u32),
-   ChatMsg { username: String, msg: String },
+   ChatMsg {
+      username: String,
+      msg: String,
+      channel: ChatChannel,
+   },
+   ServerMsg(String),
    InitOk(InitPlayer),
    ReconnectOk,
+   /// Why logging in, creating a character or entering the game failed.
    InitErr(String),
-   RespawnOk,
+   /// The characters of the account logged in with.
+   Characters(Vec<String>),
+   /// Where the player came back to life, and with how much health.
+   RespawnOk {
+      hp: u32,
+      location: Location,
+   },
+   Inventory(Inventory),
+   TradeUpdate(TradeWindow),
+   TradeClosed(String), // reason
+   ChannelJoined(String),
+   ChannelLeft(String),
+   /// Moves the player somewhere that is not a step away, like a GM teleport.
+   Teleport(Location),
+   VipList(Vec<VipEntry>),
+   /// A name on the VIP list logged in or out.
+   VipStatus {
+      name: String,
+      online: bool,
+   },
+   Stats(PlayerStats),
+   /// `None` once the player is no longer in a party.
+   Party(Option<PartyInfo>),
+   PartyInvite(String), // leader
+   /// The player's guild, `None` after leaving it.
+   Guild(Option<String>),
+   Skull(Option<Skull>),
+   /// Every condition the player has right now.
+   Conditions(Vec<ConditionKind>),
+   /// A line for the status bar, like what the player looked at.
+   Status(String),
+   /// Every spell, sent once after entering the game.
+   Spells(Vec<Spell>),
 }
 
 // CLIENT -> SERVER
@@ -27,8 +75,105 @@ pub enum TcpClientMsg {
    },
    Disconnect,
    Ping(u32),
+   /// Local speech. See [`crate::parse_speech`].
    ChatMsg(String),
+   ChannelMsg {
+      channel: String,
+      msg: String,
+   },
+   JoinChannel(String),
+   LeaveChannel(String),
+   Command(Command),
+   /// Answered with [`TcpServerMsg::Characters`] or [`TcpServerMsg::InitErr`].
+   Login {
+      account: String,
+      password: String,
+   },
+   CreateCharacter(String),
+   /// Enters the game with one of the account's characters.
    Init(String),
    Reconnect(Uuid),
    Respawn(Uuid),
+   /// Opens a trade with `to`, or answers the one they opened.
+   TradeOffer {
+      to: String,
+      items: Vec<Item>,
+   },
+   TradeAccept,
+   TradeCancel,
+   VipAdd(String),
+   VipRemove(String),
+   /// What to attack, or `None` to stop attacking.
+   Attack(Option<AttackTarget>),
+   PartyMsg(String),
+   GuildMsg(String),
+   /// Uses one item of this kind from the inventory, e.g. eats it.
+   UseItem(ItemKind),
+   /// Answered with [`TcpServerMsg::Status`].
+   Look(Location),
+   /// Uses what is on a tile next to the player, like a ladder or a door.
+   Use(Location),
+}
+
+/// The messages coming in on a TCP stream. The stream splits and joins
+/// writes as it likes, so every message goes out behind its length.
+pub type MsgReader<R> = FramedRead<R, LengthDelimitedCodec>;
+
+pub fn msg_reader<R: AsyncRead>(read: R) -> MsgReader<R> {
+   FramedRead::new(read, LengthDelimitedCodec::new())
+}
+
+/// The next message, or `None` once the other side closed the stream.
+pub async fn read_msg<T, R>(reader: &mut MsgReader<R>) -> Result<Option<T>>
+where
+   T: DeserializeOwned,
+   R: AsyncRead + Unpin,
+{
+   match reader.next().await {
+      Some(frame) => Ok(Some(bincode::deserialize(&frame?)?)),
+      None => Ok(None),
+   }
+}
+
+/// `msg` as it is written to a TCP stream, for a [`MsgReader`] to read.
+pub fn encode<T: Serialize>(msg: &T) -> Result<Vec<u8>> {
+   let mut frame = BytesMut::new();
+   LengthDelimitedCodec::new().encode(Bytes::from(bincode::serialize(msg)?), &mut frame)?;
+   Ok(frame.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[tokio::test]
+   async fn test_messages_split_and_joined() {
+      let msgs = [
+         TcpServerMsg::Pong(1),
+         TcpServerMsg::ServerMsg("x".repeat(5000)),
+         TcpServerMsg::Pong(2),
+      ];
+      let stream: Vec<u8> = msgs.iter().flat_map(|m| encode(m).unwrap()).collect();
+
+      // written in odd pieces, so messages are cut and run together
+      let (mut write, read) = tokio::io::duplex(64);
+      tokio::spawn(async move {
+         use tokio::io::AsyncWriteExt;
+         for piece in stream.chunks(7) {
+            write.write_all(piece).await.unwrap();
+         }
+      });
+
+      let mut reader = msg_reader(read);
+      for expected in msgs {
+         let msg: TcpServerMsg = read_msg(&mut reader).await.unwrap().unwrap();
+         assert_eq!(format!("{msg:?}"), format!("{expected:?}"));
+      }
+      assert!(
+         read_msg::<TcpServerMsg, _>(&mut reader)
+            .await
+            .unwrap()
+            .is_none()
+      );
+   }
 }
@@ -1,8 +1,13 @@
-use crate::{Direction, GameObjects, Location};
+use crate::{Direction, GameObjects, Location, MagicEffect, Projectile, Skull};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+/// The most a UDP datagram can carry, which receive buffers are sized to.
+pub const MAX_DATAGRAM_SIZE: usize = 65_536;
+
 // SERVER -> CLIENT
+/// `tick` is the game loop tick the state was taken at. Packets can arrive
+/// out of order, so the client goes by it rather than by arrival.
 #[derive(Debug, Serialize, Deserialize)]
 pub enum UdpServerMsg {
    PlayerMove {
@@ -10,11 +15,18 @@ pub enum UdpServerMsg {
       client_request_id: u32,
    },
    OtherPlayer {
+      tick: u64,
       username: String,
       location: Location,
       direction: Direction,
+      guild: Option<String>,
+      skull: Option<Skull>,
+      hp_percent: u8,
+   },
+   Objects {
+      tick: u64,
+      objects: GameObjects,
    },
-   Objects(GameObjects),
    Pong(u32),
    PlayerHealthUpdate {
       hp: u32,
@@ -25,15 +37,22 @@ pub enum UdpServerMsg {
    DamageNumber {
       damage: u32,
    },
+   MagicEffect {
+      effect: MagicEffect,
+      tiles: Vec<Location>,
+   },
+   Projectile(Projectile),
 }
 
 // CLIENT -> SERVER
 #[derive(Debug, Serialize, Deserialize)]
 pub enum UdpClientMsg {
+   /// A step of one tile, diagonals included. The server answers with
+   /// [`UdpServerMsg::PlayerMove`] whether it took the step or not.
    PlayerMove {
       id: Uuid,
       client_request_id: u32,
-      location: Location,
+      step: (i32, i32),
    },
    Ping {
       id: Uuid,
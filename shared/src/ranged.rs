@@ -0,0 +1,117 @@
+use crate::{ItemKind, Location};
+use serde::{Deserialize, Serialize};
+
+/// What the player hits with when not wielding anything.
+pub const FISTS: Weapon = Weapon {
+   range: 1,
+   min: 5,
+   max: 15,
+   ammunition: None,
+   missile: None,
+};
+
+/// How far and how hard a weapon hits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Weapon {
+   pub range: u32,
+   pub min: u32,
+   pub max: u32,
+   /// Used up, one per shot.
+   pub ammunition: Option<ItemKind>,
+   pub missile: Option<Missile>,
+}
+
+/// What the client animates flying from the attacker to the target.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Missile {
+   Arrow,
+   Spear,
+   Energy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Projectile {
+   pub missile: Missile,
+   pub from: Location,
+   pub to: Location,
+}
+
+/// Steps between two tiles of the same floor, diagonals included.
+pub fn distance(a: Location, b: Location) -> u32 {
+   a.0.abs_diff(b.0).max(a.1.abs_diff(b.1))
+}
+
+/// The tiles on the straight line from `from` to `to`, both included.
+pub fn line_between(from: Location, to: Location) -> Vec<Location> {
+   let (mut x, mut y) = (from.0 as i64, from.1 as i64);
+   let (x1, y1) = (to.0 as i64, to.1 as i64);
+   let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+   let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+   let mut err = dx + dy;
+
+   let mut tiles = vec![];
+   loop {
+      tiles.push((x as u32, y as u32, from.2));
+      if x == x1 && y == y1 {
+         return tiles;
+      }
+      let e2 = 2 * err;
+      if e2 >= dy {
+         err += dy;
+         x += sx;
+      }
+      if e2 <= dx {
+         err += dx;
+         y += sy;
+      }
+   }
+}
+
+/// Whether a missile can fly from `from` to `to` without going through a
+/// tile for which `blocks` is true. The ends of the line do not count.
+pub fn has_line_of_sight(from: Location, to: Location, blocks: impl Fn(Location) -> bool) -> bool {
+   if from.2 != to.2 {
+      return false;
+   }
+   let tiles = line_between(from, to);
+   let between = tiles.len().saturating_sub(2);
+   tiles
+      .iter()
+      .skip(1)
+      .take(between)
+      .all(|&tile| !blocks(tile))
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_line_between() {
+      assert_eq!(line_between((2, 2, 0), (2, 2, 0)), vec![(2, 2, 0)]);
+      assert_eq!(
+         line_between((0, 0, 1), (3, 0, 1)),
+         vec![(0, 0, 1), (1, 0, 1), (2, 0, 1), (3, 0, 1)]
+      );
+      assert_eq!(
+         line_between((3, 3, 0), (0, 0, 0)),
+         vec![(3, 3, 0), (2, 2, 0), (1, 1, 0), (0, 0, 0)]
+      );
+      assert_eq!(
+         line_between((0, 0, 0), (4, 2, 0)),
+         vec![(0, 0, 0), (1, 1, 0), (2, 1, 0), (3, 2, 0), (4, 2, 0)]
+      );
+   }
+
+   #[test]
+   fn test_line_of_sight() {
+      let wall = |(x, _, _): Location| x == 2;
+      assert!(!has_line_of_sight((0, 0, 0), (4, 0, 0), wall));
+      assert!(has_line_of_sight((0, 0, 0), (1, 5, 0), wall));
+      // standing on the blocking tile itself does not matter
+      assert!(has_line_of_sight((2, 0, 0), (4, 0, 0), wall));
+      assert!(has_line_of_sight((2, 0, 0), (2, 0, 0), wall));
+      assert!(!has_line_of_sight((0, 0, 0), (0, 0, 1), |_| false));
+   }
+}
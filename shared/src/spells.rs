@@ -0,0 +1,137 @@
+use crate::{ConditionSpec, Direction, Location};
+use serde::{Deserialize, Serialize};
+
+/// A spell as defined in `assets/spells.json`. It is cast by saying its words.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Spell {
+   pub name: String,
+   pub words: String,
+   pub mana: u32,
+   pub level: u32,
+   pub cooldown_ms: u64,
+   pub kind: SpellKind,
+   pub area: SpellArea,
+   pub effect: MagicEffect,
+   /// Given to the players the spell reaches.
+   #[serde(default)]
+   pub condition: Option<ConditionSpec>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpellKind {
+   Heal {
+      min: u32,
+      max: u32,
+   },
+   Damage {
+      min: u32,
+      max: u32,
+   },
+   /// Only gives its condition to the players it reaches, the caster included.
+   Buff,
+   /// Only gives its condition to the other players it reaches.
+   Curse,
+}
+
+impl SpellKind {
+   /// Whether the spell is aimed at others rather than at the caster's side.
+   pub fn is_hostile(self) -> bool {
+      matches!(self, SpellKind::Damage { .. } | SpellKind::Curse)
+   }
+}
+
+/// The tiles a spell reaches, relative to the caster and where they face.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpellArea {
+   /// Only the caster's own tile.
+   Caster,
+   /// The eight tiles around the caster.
+   Around,
+   /// A straight line in front of the caster.
+   Beam { length: u32 },
+   /// A cone in front of the caster that widens every other tile.
+   Wave { length: u32 },
+}
+
+impl SpellArea {
+   pub fn tiles(self, caster: Location, direction: Direction) -> Vec<Location> {
+      let (x, y, z) = (caster.0 as i64, caster.1 as i64, caster.2);
+
+      // `forward` steps away from the caster, `side` across the direction faced
+      let (fx, fy) = match direction {
+         Direction::North => (0, -1),
+         Direction::South => (0, 1),
+         Direction::East => (1, 0),
+         Direction::West => (-1, 0),
+      };
+      let (sx, sy) = (fy, fx);
+
+      let offsets: Vec<(i64, i64)> = match self {
+         SpellArea::Caster => vec![(0, 0)],
+         SpellArea::Around => (-1..=1)
+            .flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+            .filter(|&offset| offset != (0, 0))
+            .collect(),
+         SpellArea::Beam { length } => (1..=length as i64)
+            .map(|forward| (fx * forward, fy * forward))
+            .collect(),
+         SpellArea::Wave { length } => (1..=length as i64)
+            .flat_map(|forward| {
+               let half_width = forward / 2;
+               (-half_width..=half_width)
+                  .map(move |side| (fx * forward + sx * side, fy * forward + sy * side))
+            })
+            .collect(),
+      };
+
+      offsets
+         .into_iter()
+         .map(|(dx, dy)| (x + dx, y + dy))
+         .filter(|&(x, y)| x >= 0 && y >= 0)
+         .map(|(x, y)| (x as u32, y as u32, z))
+         .collect()
+   }
+}
+
+/// What the client animates on the tiles a spell reached.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MagicEffect {
+   Healing,
+   Energy,
+   Fire,
+}
+
+#[cfg(test)]
+mod tests {
+   use super::*;
+
+   #[test]
+   fn test_spell_areas() {
+      let caster = (5, 5, 0);
+      assert_eq!(
+         SpellArea::Caster.tiles(caster, Direction::North),
+         vec![caster]
+      );
+      assert_eq!(SpellArea::Around.tiles(caster, Direction::North).len(), 8);
+      assert_eq!(
+         SpellArea::Beam { length: 3 }.tiles(caster, Direction::East),
+         vec![(6, 5, 0), (7, 5, 0), (8, 5, 0)]
+      );
+      assert_eq!(
+         SpellArea::Wave { length: 2 }.tiles(caster, Direction::South),
+         vec![(5, 6, 0), (4, 7, 0), (5, 7, 0), (6, 7, 0)]
+      );
+
+      // tiles off the map are dropped
+      let corner = (0, 0, 0);
+      assert_eq!(SpellArea::Around.tiles(corner, Direction::North).len(), 3);
+      assert!(
+         SpellArea::Beam { length: 3 }
+            .tiles(corner, Direction::West)
+            .is_empty()
+      );
+   }
+}
@@ -0,0 +1,135 @@
+use crate::{ConditionKind, ConditionSpec, Location, constants::*};
+use std::collections::{HashMap, HashSet};
+use tiled::{Map, ObjectShape};
+
+/// The rectangles of the given class in the map's object layers, as their
+/// name and the tiles they cover, in the order they appear in the map.
+fn load_rectangles(map: &Map, class: &str) -> Vec<(String, Vec<Location>)> {
+   let mut rectangles = vec![];
+
+   for (group_idx, layer) in map.layers().enumerate() {
+      let tiled::LayerType::Group(group_layer) = layer.layer_type() else {
+         continue;
+      };
+      let z_level = group_idx as u32;
+
+      for inner_layer in group_layer.layers() {
+         let tiled::LayerType::Objects(object_layer) = inner_layer.layer_type() else {
+            continue;
+         };
+         for od in object_layer.object_data() {
+            let ObjectShape::Rect { width, height } = od.shape else {
+               continue;
+            };
+            if od.user_type != class {
+               continue;
+            }
+
+            let (x, y) = ((od.x / TILE_WIDTH) as u32, (od.y / TILE_HEIGHT) as u32);
+            let (w, h) = ((width / TILE_WIDTH) as u32, (height / TILE_HEIGHT) as u32);
+            let tiles = (y..y + h)
+               .flat_map(|ty| (x..x + w).map(move |tx| (tx, ty, z_level)))
+               .collect();
+            rectangles.push((od.name.clone(), tiles));
+         }
+      }
+   }
+
+   rectangles
+}
+
+/// Every tile covered by a rectangle of the given class.
+fn load_tiles(map: &Map, class: &str) -> HashSet<Location> {
+   load_rectangles(map, class)
+      .into_iter()
+      .flat_map(|(_, tiles)| tiles)
+      .collect()
+}
+
+/// Rectangles of class `protection_zone` mark tiles where nobody can attack
+/// or be attacked.
+#[derive(Debug, Default, Clone)]
+pub struct ProtectionZones(HashSet<Location>);
+
+impl ProtectionZones {
+   pub fn from_map(map: &Map) -> ProtectionZones {
+      ProtectionZones(load_tiles(map, "protection_zone"))
+   }
+
+   pub fn contains(&self, location: Location) -> bool {
+      self.0.contains(&location)
+   }
+}
+
+const POISON_FIELD: ConditionSpec = ConditionSpec {
+   kind: ConditionKind::Poison,
+   ticks: 5,
+   interval_ms: 2000,
+   amount: 5,
+};
+
+const FIRE_FIELD: ConditionSpec = ConditionSpec {
+   kind: ConditionKind::Burning,
+   ticks: 3,
+   interval_ms: 2000,
+   amount: 10,
+};
+
+/// Rectangles of class `poison_field` or `fire_field` mark tiles that hand
+/// out a condition to whoever steps on them.
+#[derive(Debug, Default, Clone)]
+pub struct Fields(pub HashMap<Location, ConditionSpec>);
+
+impl Fields {
+   pub fn from_map(map: &Map) -> Fields {
+      let poison = load_tiles(map, "poison_field")
+         .into_iter()
+         .map(|tile| (tile, POISON_FIELD));
+      let fire = load_tiles(map, "fire_field")
+         .into_iter()
+         .map(|tile| (tile, FIRE_FIELD));
+      Fields(poison.chain(fire).collect())
+   }
+
+   pub fn condition_at(&self, location: Location) -> Option<ConditionSpec> {
+      self.0.get(&location).copied()
+   }
+}
+
+/// Where players respawn. Rectangles of class `temple` are named after the
+/// town they belong to.
+#[derive(Debug, Clone)]
+pub struct Temple {
+   pub town: String,
+   pub tiles: Vec<Location>,
+}
+
+/// The temples in the map. The first one is where players without a home
+/// town respawn.
+#[derive(Debug, Default, Clone)]
+pub struct Temples(pub Vec<Temple>);
+
+impl Temples {
+   pub fn from_map(map: &Map) -> Temples {
+      let temples = load_rectangles(map, "temple")
+         .into_iter()
+         .map(|(town, tiles)| Temple { town, tiles })
+         .collect();
+      Temples(temples)
+   }
+
+   /// The temple `location` is in, if any.
+   pub fn at(&self, location: Location) -> Option<&Temple> {
+      self
+         .0
+         .iter()
+         .find(|temple| temple.tiles.contains(&location))
+   }
+
+   /// The temple of `town`, or the first one if the town has none.
+   pub fn of_town(&self, town: Option<&str>) -> Option<&Temple> {
+      town
+         .and_then(|town| self.0.iter().find(|temple| temple.town == town))
+         .or(self.0.first())
+   }
+}